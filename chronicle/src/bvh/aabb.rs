@@ -0,0 +1,183 @@
+use cgmath::{Vector3, Matrix4};
+
+use super::Ray;
+
+/// Axis aligned bounding box, an empty box has `min` above `max`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Vector3<f32>,
+    pub max: Vector3<f32>
+}
+
+impl Aabb {
+    pub fn new(min: Vector3<f32>, max: Vector3<f32>) -> Self {
+        Aabb {
+            min: min,
+            max: max
+        }
+    }
+
+    pub fn empty() -> Self {
+        Aabb {
+            min: Vector3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+            max: Vector3::new(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY)
+        }
+    }
+
+    pub fn from_points<'a, I: IntoIterator<Item = &'a Vector3<f32>>>(points: I) -> Self {
+        let mut aabb = Aabb::empty();
+        for point in points {
+            aabb.grow_point(point);
+        }
+
+        aabb
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    pub fn grow_point(&mut self, point: &Vector3<f32>) {
+        self.min = Vector3::new(self.min.x.min(point.x), self.min.y.min(point.y), self.min.z.min(point.z));
+        self.max = Vector3::new(self.max.x.max(point.x), self.max.y.max(point.y), self.max.z.max(point.z));
+    }
+
+    pub fn grow(&mut self, other: &Aabb) {
+        self.min = Vector3::new(self.min.x.min(other.min.x), self.min.y.min(other.min.y), self.min.z.min(other.min.z));
+        self.max = Vector3::new(self.max.x.max(other.max.x), self.max.y.max(other.max.y), self.max.z.max(other.max.z));
+    }
+
+    pub fn get_center(&self) -> Vector3<f32> {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn get_extent(&self) -> Vector3<f32> {
+        self.max - self.min
+    }
+
+    /// Surface area of the box, the cost of visiting it in the surface area heuristic
+    pub fn get_surface_area(&self) -> f32 {
+        if self.is_empty() {
+            return 0.0;
+        }
+
+        let extent = self.get_extent();
+        2.0 * (extent.x * extent.y + extent.y * extent.z + extent.z * extent.x)
+    }
+
+    pub fn overlaps(&self, other: &Aabb) -> bool {
+        self.min.x <= other.max.x && self.max.x >= other.min.x
+            && self.min.y <= other.max.y && self.max.y >= other.min.y
+            && self.min.z <= other.max.z && self.max.z >= other.min.z
+    }
+
+    pub fn contains_point(&self, point: &Vector3<f32>) -> bool {
+        point.x >= self.min.x && point.x <= self.max.x
+            && point.y >= self.min.y && point.y <= self.max.y
+            && point.z >= self.min.z && point.z <= self.max.z
+    }
+
+    /// Squared distance from a point to the closest point of the box, 0 inside of it
+    pub fn distance_squared(&self, point: &Vector3<f32>) -> f32 {
+        let mut distance_squared = 0.0;
+        for axis in 0..3 {
+            let outside = (self.min[axis] - point[axis]).max(point[axis] - self.max[axis]).max(0.0);
+            distance_squared += outside * outside;
+        }
+
+        distance_squared
+    }
+
+    /// Box around the transformed box, which may be larger than the box around the transformed contents
+    pub fn transform(&self, matrix: &Matrix4<f32>) -> Aabb {
+        if self.is_empty() {
+            return *self;
+        }
+
+        // Every output axis is the translation plus the extremes of each column scaled by the input range
+        let mut aabb = Aabb::new(matrix.w.truncate(), matrix.w.truncate());
+        for (column, axis) in [matrix.x, matrix.y, matrix.z].iter().zip(0..3) {
+            let a = column.truncate() * self.min[axis];
+            let b = column.truncate() * self.max[axis];
+            aabb.min += Vector3::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z));
+            aabb.max += Vector3::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z));
+        }
+
+        aabb
+    }
+
+    /// Distance along the ray at which it enters the box, if it does so before `max_distance`
+    pub fn intersect_ray(&self, ray: &Ray, max_distance: f32) -> Option<f32> {
+        let mut near = 0.0f32;
+        let mut far = max_distance;
+        for axis in 0..3 {
+            let t0 = (self.min[axis] - ray.origin[axis]) * ray.inverse_direction[axis];
+            let t1 = (self.max[axis] - ray.origin[axis]) * ray.inverse_direction[axis];
+            near = near.max(t0.min(t1));
+            far = far.min(t0.max(t1));
+        }
+
+        match near <= far {
+            true => Some(near),
+            false => None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit() -> Aabb {
+        Aabb::new(Vector3::new(-1.0, -1.0, -1.0), Vector3::new(1.0, 1.0, 1.0))
+    }
+
+    #[test]
+    fn empty_box_grows_around_points() {
+        let mut aabb = Aabb::empty();
+        assert!(aabb.is_empty());
+        assert_eq!(aabb.get_surface_area(), 0.0);
+
+        aabb.grow_point(&Vector3::new(1.0, 2.0, 3.0));
+        aabb.grow_point(&Vector3::new(-1.0, 0.0, 5.0));
+        assert_eq!(aabb, Aabb::new(Vector3::new(-1.0, 0.0, 3.0), Vector3::new(1.0, 2.0, 5.0)));
+        assert_eq!(aabb.get_surface_area(), 2.0 * (4.0 + 4.0 + 4.0));
+    }
+
+    #[test]
+    fn transform_covers_rotated_box() {
+        let rotation = Matrix4::from_angle_z(cgmath::Deg(45.0));
+        let transformed = unit().transform(&(Matrix4::from_translation(Vector3::new(5.0, 0.0, 0.0)) * rotation));
+
+        let half_diagonal = 2.0f32.sqrt();
+        assert!((transformed.min - Vector3::new(5.0 - half_diagonal, -half_diagonal, -1.0)).x.abs() < 1e-5);
+        assert!((transformed.max - Vector3::new(5.0 + half_diagonal, half_diagonal, 1.0)).y.abs() < 1e-5);
+        assert!((transformed.max.z - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn ray_enters_box() {
+        let ray = Ray::new(Vector3::new(-5.0, 0.0, 0.0), Vector3::new(1.0, 0.0, 0.0));
+        assert_eq!(unit().intersect_ray(&ray, f32::INFINITY), Some(4.0));
+        assert_eq!(unit().intersect_ray(&ray, 3.0), None);
+
+        // Starting inside enters right away
+        let inside = Ray::new(Vector3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 1.0, 0.0));
+        assert_eq!(unit().intersect_ray(&inside, f32::INFINITY), Some(0.0));
+
+        let away = Ray::new(Vector3::new(-5.0, 0.0, 0.0), Vector3::new(-1.0, 0.0, 0.0));
+        assert_eq!(unit().intersect_ray(&away, f32::INFINITY), None);
+    }
+
+    #[test]
+    fn overlap_and_distance() {
+        let touching = Aabb::new(Vector3::new(1.0, 0.0, 0.0), Vector3::new(2.0, 1.0, 1.0));
+        let apart = Aabb::new(Vector3::new(1.5, 0.0, 0.0), Vector3::new(2.0, 1.0, 1.0));
+        assert!(unit().overlaps(&touching));
+        assert!(!unit().overlaps(&apart));
+
+        assert!(unit().contains_point(&Vector3::new(0.5, -1.0, 0.0)));
+        assert_eq!(unit().distance_squared(&Vector3::new(0.5, 0.0, 0.0)), 0.0);
+        assert_eq!(unit().distance_squared(&Vector3::new(4.0, 5.0, 0.0)), 9.0 + 16.0);
+    }
+}
\ No newline at end of file
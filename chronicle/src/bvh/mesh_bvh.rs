@@ -0,0 +1,285 @@
+use cgmath::{Vector3, Matrix4};
+
+use super::{Aabb, Ray, RayHit, ClosestPoint, intersect_triangle, closest_point_on_triangle, triangle_overlaps_aabb};
+use super::tree::BvhTree;
+use crate::resources::Mesh;
+
+/// Bounding volume hierarchy over the triangles of a mesh, built with the surface area heuristic
+pub struct MeshBvh {
+    tree: BvhTree,
+    /// Triangle corners in leaf slot order
+    triangles: Vec<[Vector3<f32>; 3]>
+}
+
+impl MeshBvh {
+    /// `indices` is a triangle list into `positions`
+    pub fn new(positions: &[Vector3<f32>], indices: &[u32]) -> Self {
+        let corners: Vec<[Vector3<f32>; 3]> = indices.chunks_exact(3)
+            .map(|triangle| [
+                positions[triangle[0] as usize],
+                positions[triangle[1] as usize],
+                positions[triangle[2] as usize]
+            ])
+            .collect();
+
+        let bounds: Vec<Aabb> = corners.iter().map(|corners| Aabb::from_points(corners.iter())).collect();
+        let tree = BvhTree::new(&bounds);
+        let triangles = tree.primitives.iter().map(|primitive| corners[*primitive as usize]).collect();
+
+        MeshBvh {
+            tree: tree,
+            triangles: triangles
+        }
+    }
+
+    pub fn from_mesh(mesh: &Mesh) -> Self {
+        let positions: Vec<Vector3<f32>> = mesh.vertices.iter().map(|vertex| vertex.position).collect();
+        Self::new(&positions, &mesh.indices)
+    }
+
+    pub fn get_bounds(&self) -> Aabb {
+        self.tree.get_bounds()
+    }
+
+    pub fn get_triangle_count(&self) -> usize {
+        self.triangles.len()
+    }
+
+    /// Closest triangle hit by the ray before `max_distance`
+    pub fn intersect_ray(&self, ray: &Ray, max_distance: f32) -> Option<RayHit> {
+        let mut closest: Option<RayHit> = None;
+
+        self.tree.nearest(
+            max_distance,
+            |bounds, max_distance| bounds.intersect_ray(ray, max_distance),
+            |slot, max_distance| {
+                let (distance, barycentrics) = intersect_triangle(ray, &self.triangles[slot], max_distance)?;
+                closest = Some(RayHit {
+                    distance: distance,
+                    primitive: self.tree.primitives[slot] as usize,
+                    barycentrics: barycentrics
+                });
+                Some(distance)
+            }
+        );
+
+        closest
+    }
+
+    /// Every triangle that touches the box
+    pub fn overlap_aabb(&self, aabb: &Aabb) -> Vec<usize> {
+        let mut primitives = Vec::new();
+        self.overlap_aabb_transformed(aabb, None, |primitive| primitives.push(primitive));
+        primitives
+    }
+
+    /// Closest point on any triangle within `max_distance` of `point`
+    pub fn closest_point(&self, point: &Vector3<f32>, max_distance: f32) -> Option<ClosestPoint> {
+        self.closest_point_transformed(point, None, max_distance)
+    }
+
+    /// Overlap query against the mesh placed by `matrix`, the box is in the space `matrix` maps to
+    pub(super) fn overlap_aabb_transformed<F: FnMut(usize)>(&self, aabb: &Aabb, matrix: Option<&Matrix4<f32>>, mut visit: F) {
+        self.tree.overlapping(
+            |bounds| transform_bounds(bounds, matrix).overlaps(aabb),
+            |slot| {
+                if triangle_overlaps_aabb(&transform_triangle(&self.triangles[slot], matrix), aabb) {
+                    visit(self.tree.primitives[slot] as usize);
+                }
+            }
+        );
+    }
+
+    /// Closest point query against the mesh placed by `matrix`. Measuring after the transform rather than
+    /// transforming the point keeps distances right under non uniform scale
+    pub(super) fn closest_point_transformed(&self, point: &Vector3<f32>, matrix: Option<&Matrix4<f32>>, max_distance: f32) -> Option<ClosestPoint> {
+        let mut closest: Option<ClosestPoint> = None;
+
+        // Distances are compared squared and only rooted for the result
+        self.tree.nearest(
+            max_distance * max_distance,
+            |bounds, max_distance_squared| {
+                let distance_squared = transform_bounds(bounds, matrix).distance_squared(point);
+                match distance_squared <= max_distance_squared {
+                    true => Some(distance_squared),
+                    false => None
+                }
+            },
+            |slot, max_distance_squared| {
+                let triangle = transform_triangle(&self.triangles[slot], matrix);
+                let (position, barycentrics) = closest_point_on_triangle(point, &triangle);
+                let offset = position - point;
+                let distance_squared = offset.x * offset.x + offset.y * offset.y + offset.z * offset.z;
+                if distance_squared > max_distance_squared {
+                    return None;
+                }
+
+                closest = Some(ClosestPoint {
+                    distance: distance_squared.sqrt(),
+                    primitive: self.tree.primitives[slot] as usize,
+                    barycentrics: barycentrics,
+                    position: position
+                });
+                Some(distance_squared)
+            }
+        );
+
+        closest
+    }
+}
+
+fn transform_bounds(bounds: &Aabb, matrix: Option<&Matrix4<f32>>) -> Aabb {
+    match matrix {
+        Some(matrix) => bounds.transform(matrix),
+        None => *bounds
+    }
+}
+
+fn transform_triangle(triangle: &[Vector3<f32>; 3], matrix: Option<&Matrix4<f32>>) -> [Vector3<f32>; 3] {
+    match matrix {
+        Some(matrix) => triangle.map(|corner| (matrix * corner.extend(1.0)).truncate()),
+        None => *triangle
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::InnerSpace;
+
+    /// Small random triangles scattered through a 10 unit cube, with a fixed seed
+    fn triangle_soup(triangle_count: u32) -> (Vec<Vector3<f32>>, Vec<u32>) {
+        let mut seed = 12345u32;
+        let mut random = move || {
+            seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+            (seed >> 8) as f32 / (1u32 << 24) as f32
+        };
+
+        let mut positions = Vec::new();
+        let mut indices = Vec::new();
+        for i in 0..triangle_count {
+            let center = Vector3::new(random(), random(), random()) * 10.0;
+            for _ in 0..3 {
+                positions.push(center + Vector3::new(random(), random(), random()) * 0.5);
+            }
+            indices.extend([i * 3, i * 3 + 1, i * 3 + 2]);
+        }
+
+        (positions, indices)
+    }
+
+    fn triangle_at(positions: &[Vector3<f32>], indices: &[u32], primitive: usize) -> [Vector3<f32>; 3] {
+        [
+            positions[indices[primitive * 3] as usize],
+            positions[indices[primitive * 3 + 1] as usize],
+            positions[indices[primitive * 3 + 2] as usize]
+        ]
+    }
+
+    #[test]
+    fn empty_mesh_has_no_hits() {
+        let bvh = MeshBvh::new(&[], &[]);
+        assert!(bvh.get_bounds().is_empty());
+        assert!(bvh.intersect_ray(&Ray::new(Vector3::new(0.0, 0.0, 0.0), Vector3::unit_z()), f32::INFINITY).is_none());
+        assert!(bvh.overlap_aabb(&Aabb::new(Vector3::new(-1.0, -1.0, -1.0), Vector3::new(1.0, 1.0, 1.0))).is_empty());
+        assert!(bvh.closest_point(&Vector3::new(0.0, 0.0, 0.0), f32::INFINITY).is_none());
+    }
+
+    #[test]
+    fn bounds_cover_every_triangle() {
+        let (positions, indices) = triangle_soup(500);
+        let bvh = MeshBvh::new(&positions, &indices);
+        assert_eq!(bvh.get_triangle_count(), 500);
+        assert_eq!(bvh.get_bounds(), Aabb::from_points(positions.iter()));
+    }
+
+    #[test]
+    fn ray_hits_match_brute_force() {
+        let (positions, indices) = triangle_soup(1000);
+        let bvh = MeshBvh::new(&positions, &indices);
+
+        let mut hit_count = 0;
+        for i in 0..500 {
+            let t = i as f32 / 500.0;
+            let ray = Ray::new(
+                Vector3::new(t * 12.0 - 1.0, (t * 37.0).fract() * 12.0 - 1.0, -2.0),
+                Vector3::new((t * 5.0).sin() * 0.3, (t * 3.0).cos() * 0.3, 1.0).normalize()
+            );
+
+            let mut expected: Option<(f32, usize)> = None;
+            for primitive in 0..1000 {
+                let max_distance = expected.map(|(distance, _)| distance).unwrap_or(f32::INFINITY);
+                if let Some((distance, _)) = intersect_triangle(&ray, &triangle_at(&positions, &indices, primitive), max_distance) {
+                    expected = Some((distance, primitive));
+                }
+            }
+
+            let hit = bvh.intersect_ray(&ray, f32::INFINITY);
+            assert_eq!(hit.map(|hit| hit.primitive), expected.map(|(_, primitive)| primitive));
+            hit_count += hit.is_some() as usize;
+        }
+
+        assert!(hit_count > 0);
+    }
+
+    #[test]
+    fn ray_stops_at_max_distance() {
+        let positions = [Vector3::new(-1.0, -1.0, 5.0), Vector3::new(1.0, -1.0, 5.0), Vector3::new(0.0, 1.0, 5.0)];
+        let bvh = MeshBvh::new(&positions, &[0, 1, 2]);
+        let ray = Ray::new(Vector3::new(0.0, 0.0, 0.0), Vector3::unit_z());
+
+        let hit = bvh.intersect_ray(&ray, 10.0).expect("Failed to hit the triangle (ray misses)");
+        assert!((hit.distance - 5.0).abs() < 1e-5);
+        assert_eq!(hit.primitive, 0);
+        assert!(bvh.intersect_ray(&ray, 4.0).is_none());
+    }
+
+    #[test]
+    fn overlap_matches_brute_force() {
+        let (positions, indices) = triangle_soup(1000);
+        let bvh = MeshBvh::new(&positions, &indices);
+        let aabb = Aabb::new(Vector3::new(2.0, 3.0, 4.0), Vector3::new(5.0, 5.5, 6.0));
+
+        let mut overlapping = bvh.overlap_aabb(&aabb);
+        overlapping.sort();
+        let expected: Vec<usize> = (0..1000)
+            .filter(|primitive| triangle_overlaps_aabb(&triangle_at(&positions, &indices, *primitive), &aabb))
+            .collect();
+
+        assert!(!expected.is_empty());
+        assert_eq!(overlapping, expected);
+    }
+
+    #[test]
+    fn closest_point_matches_brute_force() {
+        let (positions, indices) = triangle_soup(1000);
+        let bvh = MeshBvh::new(&positions, &indices);
+
+        for point in [Vector3::new(5.0, 5.0, 5.0), Vector3::new(-3.0, 2.0, 11.0), Vector3::new(20.0, -4.0, 0.5)] {
+            let expected = (0..1000)
+                .map(|primitive| {
+                    let (position, _) = closest_point_on_triangle(&point, &triangle_at(&positions, &indices, primitive));
+                    (position - point).magnitude()
+                })
+                .fold(f32::INFINITY, f32::min);
+
+            let closest = bvh.closest_point(&point, f32::INFINITY).expect("Failed to find a closest point (mesh is empty)");
+            assert!((closest.distance - expected).abs() < 1e-4);
+            assert!(((closest.position - point).magnitude() - closest.distance).abs() < 1e-4);
+        }
+
+        assert!(bvh.closest_point(&Vector3::new(100.0, 100.0, 100.0), 1.0).is_none());
+    }
+
+    #[test]
+    fn transformed_closest_point_uses_world_distances() {
+        let positions = [Vector3::new(0.0, 0.0, 0.0), Vector3::new(1.0, 0.0, 0.0), Vector3::new(0.0, 1.0, 0.0)];
+        let bvh = MeshBvh::new(&positions, &[0, 1, 2]);
+        let matrix = Matrix4::from_nonuniform_scale(1.0, 1.0, 4.0) * Matrix4::from_translation(Vector3::new(0.0, 0.0, 1.0));
+
+        let closest = bvh.closest_point_transformed(&Vector3::new(0.1, 0.1, 0.0), Some(&matrix), f32::INFINITY)
+            .expect("Failed to find a closest point (mesh is empty)");
+        assert!((closest.distance - 4.0).abs() < 1e-5);
+        assert!((closest.position - Vector3::new(0.1, 0.1, 4.0)).magnitude() < 1e-5);
+    }
+}
\ No newline at end of file
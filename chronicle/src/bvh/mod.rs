@@ -0,0 +1,11 @@
+pub mod aabb;
+pub use aabb::*;
+pub mod ray;
+pub use ray::*;
+pub mod triangle;
+pub use triangle::*;
+mod tree;
+pub mod mesh_bvh;
+pub use mesh_bvh::*;
+pub mod scene_bvh;
+pub use scene_bvh::*;
\ No newline at end of file
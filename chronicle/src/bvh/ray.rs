@@ -0,0 +1,63 @@
+use cgmath::{Vector2, Vector3, InnerSpace};
+
+#[derive(Debug, Clone, Copy)]
+pub struct Ray {
+    pub origin: Vector3<f32>,
+    pub direction: Vector3<f32>,
+    pub inverse_direction: Vector3<f32>
+}
+
+impl Ray {
+    /// Distances along the ray are measured in multiples of `direction`, which is not normalized
+    pub fn new(origin: Vector3<f32>, direction: Vector3<f32>) -> Self {
+        Ray {
+            origin: origin,
+            direction: direction,
+            inverse_direction: Vector3::new(1.0 / direction.x, 1.0 / direction.y, 1.0 / direction.z)
+        }
+    }
+
+    pub fn at(&self, distance: f32) -> Vector3<f32> {
+        self.origin + self.direction * distance
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayHit {
+    pub distance: f32,
+    /// Index of the triangle in the index buffer of the mesh
+    pub primitive: usize,
+    /// Weights of the second and third vertex, the first one has `1 - x - y`
+    pub barycentrics: Vector2<f32>
+}
+
+/// Möller-Trumbore intersection, both faces are hit. Returns the distance and barycentrics
+pub fn intersect_triangle(ray: &Ray, triangle: &[Vector3<f32>; 3], max_distance: f32) -> Option<(f32, Vector2<f32>)> {
+    let edge_1 = triangle[1] - triangle[0];
+    let edge_2 = triangle[2] - triangle[0];
+
+    let p = ray.direction.cross(edge_2);
+    let determinant = edge_1.dot(p);
+    if determinant.abs() < 1e-12 {
+        return None;
+    }
+    let inverse_determinant = 1.0 / determinant;
+
+    let s = ray.origin - triangle[0];
+    let u = s.dot(p) * inverse_determinant;
+    if u < 0.0 || u > 1.0 {
+        return None;
+    }
+
+    let q = s.cross(edge_1);
+    let v = ray.direction.dot(q) * inverse_determinant;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+
+    let distance = edge_2.dot(q) * inverse_determinant;
+    match distance > 0.0 && distance < max_distance {
+        true => Some((distance, Vector2::new(u, v))),
+        false => None
+    }
+}
\ No newline at end of file
@@ -0,0 +1,334 @@
+use std::sync::Arc;
+use cgmath::{Vector3, Matrix4, SquareMatrix};
+
+use super::{Aabb, Ray, RayHit, ClosestPoint, MeshBvh};
+use super::tree::BvhTree;
+
+struct BvhInstance {
+    mesh_bvh: Arc<MeshBvh>,
+    matrix: Matrix4<f32>,
+    /// `None` when the matrix collapses the mesh, rays then pass through it
+    inverse_matrix: Option<Matrix4<f32>>
+}
+
+/// A result of a query against a `SceneBvh`, with the instance it was found in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InstanceHit<T> {
+    pub instance: usize,
+    pub hit: T
+}
+
+/// Top level of a two level bounding volume hierarchy, a tree over placed instances of `MeshBvh`s.
+/// Changes only take effect on `update`
+pub struct SceneBvh {
+    instances: Vec<BvhInstance>,
+    /// World space bounds of every instance
+    instance_bounds: Vec<Aabb>,
+    tree: BvhTree,
+    rebuild_pending: bool,
+    refit_pending: bool
+}
+
+impl Default for SceneBvh {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SceneBvh {
+    pub fn new() -> Self {
+        SceneBvh {
+            instances: Vec::new(),
+            instance_bounds: Vec::new(),
+            tree: BvhTree::new(&[]),
+            rebuild_pending: false,
+            refit_pending: false
+        }
+    }
+
+    /// Places a mesh in the scene, returns the index of the instance
+    pub fn add_instance(&mut self, mesh_bvh: Arc<MeshBvh>, matrix: &Matrix4<f32>) -> usize {
+        self.instance_bounds.push(mesh_bvh.get_bounds().transform(matrix));
+        self.instances.push(BvhInstance {
+            mesh_bvh: mesh_bvh,
+            matrix: *matrix,
+            inverse_matrix: matrix.invert()
+        });
+        self.rebuild_pending = true;
+
+        self.instances.len() - 1
+    }
+
+    pub fn clear(&mut self) {
+        self.instances.clear();
+        self.instance_bounds.clear();
+        self.rebuild_pending = true;
+    }
+
+    pub fn get_instance_count(&self) -> usize {
+        self.instances.len()
+    }
+
+    pub fn get_mesh_bvh(&self, instance: usize) -> &Arc<MeshBvh> {
+        &self.instances[instance].mesh_bvh
+    }
+
+    pub fn get_matrix(&self, instance: usize) -> &Matrix4<f32> {
+        &self.instances[instance].matrix
+    }
+
+    /// Moves an instance, the tree is refitted around it on the next `update`
+    pub fn set_matrix(&mut self, instance: usize, matrix: &Matrix4<f32>) {
+        let bvh_instance = &mut self.instances[instance];
+        bvh_instance.matrix = *matrix;
+        bvh_instance.inverse_matrix = matrix.invert();
+
+        self.instance_bounds[instance] = bvh_instance.mesh_bvh.get_bounds().transform(matrix);
+        self.refit_pending = true;
+    }
+
+    /// Rebuilds the tree when instances were added or removed, otherwise refits it around moved instances
+    pub fn update(&mut self) {
+        if self.rebuild_pending {
+            self.rebuild();
+        } else if self.refit_pending {
+            self.tree.refit(&self.instance_bounds);
+            self.refit_pending = false;
+        }
+    }
+
+    /// Builds the tree from scratch, which restores its quality once instances moved far from where it was built
+    pub fn rebuild(&mut self) {
+        self.tree = BvhTree::new(&self.instance_bounds);
+        self.rebuild_pending = false;
+        self.refit_pending = false;
+    }
+
+    pub fn get_bounds(&self) -> Aabb {
+        self.tree.get_bounds()
+    }
+
+    /// Closest triangle of any instance hit by the ray before `max_distance`
+    pub fn intersect_ray(&self, ray: &Ray, max_distance: f32) -> Option<InstanceHit<RayHit>> {
+        let mut closest: Option<InstanceHit<RayHit>> = None;
+
+        self.tree.nearest(
+            max_distance,
+            |bounds, max_distance| bounds.intersect_ray(ray, max_distance),
+            |slot, max_distance| {
+                let instance = self.tree.primitives[slot] as usize;
+                let inverse_matrix = self.instances[instance].inverse_matrix?;
+
+                // The direction is not renormalized, so distances along the local ray match the ones along `ray`
+                let local_ray = Ray::new(
+                    (inverse_matrix * ray.origin.extend(1.0)).truncate(),
+                    (inverse_matrix * ray.direction.extend(0.0)).truncate()
+                );
+                let hit = self.instances[instance].mesh_bvh.intersect_ray(&local_ray, max_distance)?;
+
+                closest = Some(InstanceHit {
+                    instance: instance,
+                    hit: hit
+                });
+                Some(hit.distance)
+            }
+        );
+
+        closest
+    }
+
+    /// Every triangle of every instance that touches the box, as instance and primitive
+    pub fn overlap_aabb(&self, aabb: &Aabb) -> Vec<InstanceHit<usize>> {
+        let mut overlapping = Vec::new();
+
+        self.tree.overlapping(
+            |bounds| bounds.overlaps(aabb),
+            |slot| {
+                let instance = self.tree.primitives[slot] as usize;
+                if !self.instance_bounds[instance].overlaps(aabb) {
+                    return;
+                }
+
+                let bvh_instance = &self.instances[instance];
+                bvh_instance.mesh_bvh.overlap_aabb_transformed(aabb, Some(&bvh_instance.matrix), |primitive| {
+                    overlapping.push(InstanceHit {
+                        instance: instance,
+                        hit: primitive
+                    });
+                });
+            }
+        );
+
+        overlapping
+    }
+
+    /// Closest point on any instance within `max_distance` of `point`
+    pub fn closest_point(&self, point: &Vector3<f32>, max_distance: f32) -> Option<InstanceHit<ClosestPoint>> {
+        let mut closest: Option<InstanceHit<ClosestPoint>> = None;
+
+        self.tree.nearest(
+            max_distance,
+            |bounds, max_distance| {
+                let distance = bounds.distance_squared(point).sqrt();
+                match distance <= max_distance {
+                    true => Some(distance),
+                    false => None
+                }
+            },
+            |slot, max_distance| {
+                let instance = self.tree.primitives[slot] as usize;
+                let bvh_instance = &self.instances[instance];
+                let hit = bvh_instance.mesh_bvh.closest_point_transformed(point, Some(&bvh_instance.matrix), max_distance)?;
+
+                closest = Some(InstanceHit {
+                    instance: instance,
+                    hit: hit
+                });
+                Some(hit.distance)
+            }
+        );
+
+        closest
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::InnerSpace;
+
+    /// Unit cube around the origin, as 12 triangles
+    fn cube() -> Arc<MeshBvh> {
+        let positions: Vec<Vector3<f32>> = (0..8)
+            .map(|corner| Vector3::new(
+                if corner & 1 == 0 { -0.5 } else { 0.5 },
+                if corner & 2 == 0 { -0.5 } else { 0.5 },
+                if corner & 4 == 0 { -0.5 } else { 0.5 }
+            ))
+            .collect();
+        let indices = [
+            0, 2, 1, 1, 2, 3,
+            4, 5, 6, 5, 7, 6,
+            0, 1, 4, 1, 5, 4,
+            2, 6, 3, 3, 6, 7,
+            0, 4, 2, 2, 4, 6,
+            1, 3, 5, 3, 7, 5
+        ];
+
+        Arc::new(MeshBvh::new(&positions, &indices))
+    }
+
+    /// A row of cubes along x, 3 units apart
+    fn row_of_cubes(count: usize) -> SceneBvh {
+        let mesh_bvh = cube();
+        let mut scene = SceneBvh::new();
+        for i in 0..count {
+            scene.add_instance(mesh_bvh.clone(), &Matrix4::from_translation(Vector3::new(i as f32 * 3.0, 0.0, 0.0)));
+        }
+        scene.update();
+
+        scene
+    }
+
+    #[test]
+    fn empty_scene_has_no_hits() {
+        let mut scene = SceneBvh::new();
+        scene.update();
+
+        assert!(scene.get_bounds().is_empty());
+        assert!(scene.intersect_ray(&Ray::new(Vector3::new(0.0, 0.0, 0.0), Vector3::unit_x()), f32::INFINITY).is_none());
+        assert!(scene.overlap_aabb(&Aabb::new(Vector3::new(-1.0, -1.0, -1.0), Vector3::new(1.0, 1.0, 1.0))).is_empty());
+        assert!(scene.closest_point(&Vector3::new(0.0, 0.0, 0.0), f32::INFINITY).is_none());
+    }
+
+    #[test]
+    fn ray_hits_closest_instance() {
+        let scene = row_of_cubes(10);
+
+        let ray = Ray::new(Vector3::new(100.0, 0.1, 0.2), -Vector3::unit_x());
+        let hit = scene.intersect_ray(&ray, f32::INFINITY).expect("Failed to hit a cube (ray misses)");
+        assert_eq!(hit.instance, 9);
+        assert!((hit.hit.distance - (100.0 - 27.5)).abs() < 1e-4);
+
+        let ray = Ray::new(Vector3::new(6.0, 10.0, 0.0), -Vector3::unit_y());
+        let hit = scene.intersect_ray(&ray, f32::INFINITY).expect("Failed to hit a cube (ray misses)");
+        assert_eq!(hit.instance, 2);
+        assert!((hit.hit.distance - 9.5).abs() < 1e-4);
+
+        let between = Ray::new(Vector3::new(1.5, 10.0, 0.0), -Vector3::unit_y());
+        assert!(scene.intersect_ray(&between, f32::INFINITY).is_none());
+    }
+
+    #[test]
+    fn ray_distances_stay_in_world_units_under_scale() {
+        let mut scene = SceneBvh::new();
+        scene.add_instance(cube(), &Matrix4::from_nonuniform_scale(4.0, 1.0, 1.0));
+        scene.update();
+
+        let ray = Ray::new(Vector3::new(10.0, 0.0, 0.0), -Vector3::unit_x());
+        let hit = scene.intersect_ray(&ray, f32::INFINITY).expect("Failed to hit the cube (ray misses)");
+        assert!((hit.hit.distance - 8.0).abs() < 1e-4);
+        assert!((ray.at(hit.hit.distance) - Vector3::new(2.0, 0.0, 0.0)).magnitude() < 1e-4);
+    }
+
+    #[test]
+    fn overlap_finds_touched_instances() {
+        let scene = row_of_cubes(10);
+
+        let aabb = Aabb::new(Vector3::new(2.0, -0.1, -0.1), Vector3::new(7.0, 0.1, 0.1));
+        let mut instances: Vec<usize> = scene.overlap_aabb(&aabb).iter().map(|hit| hit.instance).collect();
+        instances.dedup();
+        assert_eq!(instances.len(), 2);
+        assert!(instances.contains(&1) && instances.contains(&2));
+
+        // Inside a cube without touching its faces
+        let inside = Aabb::new(Vector3::new(-0.1, -0.1, -0.1), Vector3::new(0.1, 0.1, 0.1));
+        assert!(scene.overlap_aabb(&inside).is_empty());
+    }
+
+    #[test]
+    fn closest_point_finds_nearest_instance() {
+        let scene = row_of_cubes(10);
+
+        let closest = scene.closest_point(&Vector3::new(12.0, 5.0, 0.0), f32::INFINITY)
+            .expect("Failed to find a closest point (scene is empty)");
+        assert_eq!(closest.instance, 4);
+        assert!((closest.hit.distance - 4.5).abs() < 1e-4);
+        assert!((closest.hit.position - Vector3::new(12.0, 0.5, 0.0)).magnitude() < 1e-4);
+
+        assert!(scene.closest_point(&Vector3::new(12.0, 5.0, 0.0), 4.0).is_none());
+    }
+
+    #[test]
+    fn refit_follows_moved_instances() {
+        let mut scene = row_of_cubes(10);
+        let ray = Ray::new(Vector3::new(0.0, 20.0, 0.0), -Vector3::unit_y());
+        assert_eq!(scene.intersect_ray(&ray, f32::INFINITY).map(|hit| hit.instance), Some(0));
+
+        // Stack the last cube on top of the first one
+        scene.set_matrix(9, &Matrix4::from_translation(Vector3::new(0.0, 5.0, 0.0)));
+        scene.update();
+
+        let hit = scene.intersect_ray(&ray, f32::INFINITY).expect("Failed to hit a cube (ray misses)");
+        assert_eq!(hit.instance, 9);
+        assert!((hit.hit.distance - 14.5).abs() < 1e-4);
+        assert_eq!(scene.get_bounds(), Aabb::new(Vector3::new(-0.5, -0.5, -0.5), Vector3::new(24.5, 5.5, 0.5)));
+
+        let far_ray = Ray::new(Vector3::new(27.0, 20.0, 0.0), -Vector3::unit_y());
+        assert!(scene.intersect_ray(&far_ray, f32::INFINITY).is_none());
+
+        // A rebuild answers the same as the refitted tree
+        scene.rebuild();
+        assert_eq!(scene.intersect_ray(&ray, f32::INFINITY).map(|hit| hit.instance), Some(9));
+    }
+
+    #[test]
+    fn collapsed_instances_are_skipped() {
+        let mut scene = SceneBvh::new();
+        scene.add_instance(cube(), &Matrix4::from_nonuniform_scale(0.0, 1.0, 1.0));
+        scene.update();
+
+        let ray = Ray::new(Vector3::new(0.0, 0.0, 10.0), -Vector3::unit_z());
+        assert!(scene.intersect_ray(&ray, f32::INFINITY).is_none());
+    }
+}
\ No newline at end of file
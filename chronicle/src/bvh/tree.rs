@@ -0,0 +1,269 @@
+use cgmath::Vector3;
+
+use super::Aabb;
+
+/// Candidate split planes per axis when evaluating the surface area heuristic
+const BIN_COUNT: usize = 16;
+/// Leaves are split even when the heuristic prefers not to, so no leaf grows past this
+const MAX_LEAF_SIZE: usize = 8;
+/// Cost of visiting a node relative to testing a primitive
+const TRAVERSAL_COST: f32 = 1.0;
+
+#[derive(Debug, Clone, Copy)]
+pub(super) struct BvhNode {
+    pub(super) bounds: Aabb,
+    /// First child for interior nodes, the second child follows it. First primitive slot for leaves
+    pub(super) first: u32,
+    /// Primitives of a leaf, 0 for interior nodes
+    pub(super) count: u32
+}
+
+/// Binary tree over the bounds of any kind of primitive, built with the binned surface area heuristic.
+/// Children are always stored after their parent
+pub(super) struct BvhTree {
+    pub(super) nodes: Vec<BvhNode>,
+    /// Primitive in every leaf slot, leaves reference a range of slots
+    pub(super) primitives: Vec<u32>
+}
+
+impl BvhTree {
+    pub(super) fn new(bounds: &[Aabb]) -> Self {
+        let mut tree = BvhTree {
+            nodes: Vec::new(),
+            primitives: (0..bounds.len() as u32).collect()
+        };
+        if bounds.is_empty() {
+            return tree;
+        }
+
+        let centroids: Vec<_> = bounds.iter().map(|bounds| bounds.get_center()).collect();
+
+        tree.nodes.push(BvhNode {
+            bounds: Aabb::empty(),
+            first: 0,
+            count: bounds.len() as u32
+        });
+
+        let mut pending = vec![0];
+        while let Some(node_idx) = pending.pop() {
+            if let Some(children) = tree.subdivide(node_idx, bounds, &centroids) {
+                pending.push(children);
+                pending.push(children + 1);
+            }
+        }
+
+        tree
+    }
+
+    pub(super) fn get_bounds(&self) -> Aabb {
+        self.nodes.first().map(|root| root.bounds).unwrap_or(Aabb::empty())
+    }
+
+    /// Fits the bounds of a node around its primitives and splits it when that is cheaper, returns the first child
+    fn subdivide(&mut self, node_idx: usize, bounds: &[Aabb], centroids: &[Vector3<f32>]) -> Option<usize> {
+        let (first, count) = (self.nodes[node_idx].first as usize, self.nodes[node_idx].count as usize);
+        let primitives = &mut self.primitives[first..first + count];
+
+        let mut node_bounds = Aabb::empty();
+        let mut centroid_bounds = Aabb::empty();
+        for primitive in primitives.iter() {
+            node_bounds.grow(&bounds[*primitive as usize]);
+            centroid_bounds.grow_point(&centroids[*primitive as usize]);
+        }
+        self.nodes[node_idx].bounds = node_bounds;
+
+        if count <= 1 {
+            return None;
+        }
+
+        // Binned surface area heuristic, finds the axis and plane with the lowest expected intersection cost
+        let centroid_extent = centroid_bounds.get_extent();
+        let mut best_split: Option<(usize, usize, f32)> = None;
+        for axis in 0..3 {
+            if centroid_extent[axis] <= 0.0 {
+                continue;
+            }
+
+            let bin_scale = BIN_COUNT as f32 / centroid_extent[axis];
+
+            let mut bin_bounds = [Aabb::empty(); BIN_COUNT];
+            let mut bin_counts = [0usize; BIN_COUNT];
+            for primitive in primitives.iter() {
+                let bin = bin_index(centroids[*primitive as usize][axis], centroid_bounds.min[axis], bin_scale);
+                bin_bounds[bin].grow(&bounds[*primitive as usize]);
+                bin_counts[bin] += 1;
+            }
+
+            // Sweeping from the right gives the cost of the right side of every plane in one pass
+            let mut right_costs = [0.0f32; BIN_COUNT];
+            let mut right_bounds = Aabb::empty();
+            let mut right_count = 0;
+            for bin in (1..BIN_COUNT).rev() {
+                right_bounds.grow(&bin_bounds[bin]);
+                right_count += bin_counts[bin];
+                right_costs[bin] = right_bounds.get_surface_area() * right_count as f32;
+            }
+
+            let mut left_bounds = Aabb::empty();
+            let mut left_count = 0;
+            for plane in 1..BIN_COUNT {
+                left_bounds.grow(&bin_bounds[plane - 1]);
+                left_count += bin_counts[plane - 1];
+                if left_count == 0 || left_count == count {
+                    continue;
+                }
+
+                let cost = left_bounds.get_surface_area() * left_count as f32 + right_costs[plane];
+                if best_split.map_or(true, |(_, _, best_cost)| cost < best_cost) {
+                    best_split = Some((axis, plane, cost));
+                }
+            }
+        }
+
+        let leaf_cost = count as f32;
+        let split = match best_split {
+            Some((axis, plane, cost)) => {
+                let split_cost = TRAVERSAL_COST + cost / node_bounds.get_surface_area().max(f32::MIN_POSITIVE);
+                match split_cost < leaf_cost || count > MAX_LEAF_SIZE {
+                    true => {
+                        let bin_scale = BIN_COUNT as f32 / centroid_extent[axis];
+                        let mut left = 0;
+                        for i in 0..count {
+                            let bin = bin_index(centroids[primitives[i] as usize][axis], centroid_bounds.min[axis], bin_scale);
+                            if bin < plane {
+                                primitives.swap(i, left);
+                                left += 1;
+                            }
+                        }
+                        Some(left)
+                    },
+                    false => None
+                }
+            },
+            // Every centroid is in the same spot, halving is the only way to keep leaves small
+            None if count > MAX_LEAF_SIZE => Some(count / 2),
+            None => None
+        };
+
+        let left_count = split?;
+        let children = self.nodes.len();
+        self.nodes.push(BvhNode {
+            bounds: Aabb::empty(),
+            first: first as u32,
+            count: left_count as u32
+        });
+        self.nodes.push(BvhNode {
+            bounds: Aabb::empty(),
+            first: (first + left_count) as u32,
+            count: (count - left_count) as u32
+        });
+        self.nodes[node_idx].first = children as u32;
+        self.nodes[node_idx].count = 0;
+
+        Some(children)
+    }
+
+    /// Fits every node around the new bounds of its primitives while keeping the structure of the tree.
+    /// Cheap, but the tree gets slower to traverse the further primitives move from where it was built
+    pub(super) fn refit(&mut self, bounds: &[Aabb]) {
+        // Children come after their parent, so walking backwards fits children first
+        for node_idx in (0..self.nodes.len()).rev() {
+            let node = self.nodes[node_idx];
+
+            let mut node_bounds = Aabb::empty();
+            if node.count > 0 {
+                for slot in node.first..node.first + node.count {
+                    node_bounds.grow(&bounds[self.primitives[slot as usize] as usize]);
+                }
+            } else {
+                node_bounds.grow(&self.nodes[node.first as usize].bounds);
+                node_bounds.grow(&self.nodes[node.first as usize + 1].bounds);
+            }
+
+            self.nodes[node_idx].bounds = node_bounds;
+        }
+    }
+
+    /// Visits the primitive slots of every leaf whose bounds pass `overlaps`
+    pub(super) fn overlapping<O: Fn(&Aabb) -> bool, V: FnMut(usize)>(&self, overlaps: O, mut visit: V) {
+        if self.nodes.is_empty() {
+            return;
+        }
+
+        let mut stack = vec![0usize];
+        while let Some(node_idx) = stack.pop() {
+            let node = &self.nodes[node_idx];
+            if !overlaps(&node.bounds) {
+                continue;
+            }
+
+            if node.count > 0 {
+                (node.first as usize..(node.first + node.count) as usize).for_each(&mut visit);
+            } else {
+                stack.push(node.first as usize);
+                stack.push(node.first as usize + 1);
+            }
+        }
+    }
+
+    /// Nearest first search. `node_distance` is a lower bound of the distance to anything inside a box, or `None`
+    /// when nothing in it can be closer than the given distance. `visit` tests a primitive slot against the current
+    /// closest distance and returns its distance when it is closer
+    pub(super) fn nearest<D, V>(&self, max_distance: f32, node_distance: D, mut visit: V)
+    where
+        D: Fn(&Aabb, f32) -> Option<f32>,
+        V: FnMut(usize, f32) -> Option<f32>
+    {
+        let root = match self.nodes.first() {
+            Some(root) => root,
+            None => return
+        };
+
+        let mut max_distance = max_distance;
+        let mut stack = match node_distance(&root.bounds, max_distance) {
+            Some(distance) => vec![(0usize, distance)],
+            None => return
+        };
+
+        while let Some((node_idx, distance)) = stack.pop() {
+            // Something closer may have been found since the node was pushed
+            if distance > max_distance {
+                continue;
+            }
+
+            let node = &self.nodes[node_idx];
+            if node.count > 0 {
+                for slot in node.first as usize..(node.first + node.count) as usize {
+                    if let Some(distance) = visit(slot, max_distance) {
+                        max_distance = max_distance.min(distance);
+                    }
+                }
+                continue;
+            }
+
+            // The nearer child is pushed last so it is visited first and shrinks the range for the other one
+            let left = node.first as usize;
+            let right = left + 1;
+            let left_distance = node_distance(&self.nodes[left].bounds, max_distance);
+            let right_distance = node_distance(&self.nodes[right].bounds, max_distance);
+            match (left_distance, right_distance) {
+                (Some(left_distance), Some(right_distance)) => {
+                    if left_distance <= right_distance {
+                        stack.push((right, right_distance));
+                        stack.push((left, left_distance));
+                    } else {
+                        stack.push((left, left_distance));
+                        stack.push((right, right_distance));
+                    }
+                },
+                (Some(left_distance), None) => stack.push((left, left_distance)),
+                (None, Some(right_distance)) => stack.push((right, right_distance)),
+                (None, None) => {}
+            }
+        }
+    }
+}
+
+fn bin_index(centroid: f32, min: f32, bin_scale: f32) -> usize {
+    (((centroid - min) * bin_scale) as usize).min(BIN_COUNT - 1)
+}
\ No newline at end of file
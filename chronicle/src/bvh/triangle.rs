@@ -0,0 +1,147 @@
+use cgmath::{Vector2, Vector3, InnerSpace};
+
+use super::Aabb;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClosestPoint {
+    pub distance: f32,
+    /// Index of the triangle in the index buffer of the mesh
+    pub primitive: usize,
+    /// Weights of the second and third vertex, the first one has `1 - x - y`
+    pub barycentrics: Vector2<f32>,
+    pub position: Vector3<f32>
+}
+
+/// Closest point of a triangle to `point` and its barycentrics, walks the voronoi regions of the corners and edges
+pub fn closest_point_on_triangle(point: &Vector3<f32>, triangle: &[Vector3<f32>; 3]) -> (Vector3<f32>, Vector2<f32>) {
+    let [a, b, c] = *triangle;
+    let ab = b - a;
+    let ac = c - a;
+
+    let ap = point - a;
+    let d1 = ab.dot(ap);
+    let d2 = ac.dot(ap);
+    if d1 <= 0.0 && d2 <= 0.0 {
+        return (a, Vector2::new(0.0, 0.0));
+    }
+
+    let bp = point - b;
+    let d3 = ab.dot(bp);
+    let d4 = ac.dot(bp);
+    if d3 >= 0.0 && d4 <= d3 {
+        return (b, Vector2::new(1.0, 0.0));
+    }
+
+    // Edges of zero length are skipped, a degenerate triangle falls through to its other edges
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 && d1 - d3 > 0.0 {
+        let v = d1 / (d1 - d3);
+        return (a + ab * v, Vector2::new(v, 0.0));
+    }
+
+    let cp = point - c;
+    let d5 = ab.dot(cp);
+    let d6 = ac.dot(cp);
+    if d6 >= 0.0 && d5 <= d6 {
+        return (c, Vector2::new(0.0, 1.0));
+    }
+
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 && d2 - d6 > 0.0 {
+        let w = d2 / (d2 - d6);
+        return (a + ac * w, Vector2::new(0.0, w));
+    }
+
+    let va = d3 * d6 - d5 * d4;
+    if va <= 0.0 && d4 - d3 >= 0.0 && d5 - d6 >= 0.0 && (d4 - d3) + (d5 - d6) > 0.0 {
+        let w = (d4 - d3) / ((d4 - d3) + (d5 - d6));
+        return (b + (c - b) * w, Vector2::new(1.0 - w, w));
+    }
+
+    let denominator = 1.0 / (va + vb + vc).max(f32::MIN_POSITIVE);
+    let v = vb * denominator;
+    let w = vc * denominator;
+    (a + ab * v + ac * w, Vector2::new(v, w))
+}
+
+/// Separating axis test of a triangle against a box, touching counts as overlapping
+pub fn triangle_overlaps_aabb(triangle: &[Vector3<f32>; 3], aabb: &Aabb) -> bool {
+    let center = aabb.get_center();
+    let half_extent = aabb.get_extent() * 0.5;
+    let corners = [triangle[0] - center, triangle[1] - center, triangle[2] - center];
+
+    // A degenerate axis projects everything onto 0 and never separates
+    let separates = |axis: Vector3<f32>| -> bool {
+        let projections = [corners[0].dot(axis), corners[1].dot(axis), corners[2].dot(axis)];
+        let radius = half_extent.x * axis.x.abs() + half_extent.y * axis.y.abs() + half_extent.z * axis.z.abs();
+        projections[0].min(projections[1]).min(projections[2]) > radius
+            || projections[0].max(projections[1]).max(projections[2]) < -radius
+    };
+
+    let box_axes = [Vector3::unit_x(), Vector3::unit_y(), Vector3::unit_z()];
+    let edges = [corners[1] - corners[0], corners[2] - corners[1], corners[0] - corners[2]];
+
+    if box_axes.iter().any(|box_axis| separates(*box_axis)) {
+        return false;
+    }
+    if separates(edges[0].cross(edges[1])) {
+        return false;
+    }
+
+    !edges.iter().any(|edge| box_axes.iter().any(|box_axis| separates(box_axis.cross(*edge))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn triangle() -> [Vector3<f32>; 3] {
+        [Vector3::new(0.0, 0.0, 0.0), Vector3::new(1.0, 0.0, 0.0), Vector3::new(0.0, 1.0, 0.0)]
+    }
+
+    #[test]
+    fn closest_point_inside_projects_onto_plane() {
+        let (position, barycentrics) = closest_point_on_triangle(&Vector3::new(0.25, 0.25, 2.0), &triangle());
+        assert!((position - Vector3::new(0.25, 0.25, 0.0)).magnitude() < 1e-6);
+        assert!((barycentrics - Vector2::new(0.25, 0.25)).magnitude() < 1e-6);
+    }
+
+    #[test]
+    fn closest_point_snaps_to_corners_and_edges() {
+        let (position, _) = closest_point_on_triangle(&Vector3::new(-1.0, -1.0, 0.0), &triangle());
+        assert_eq!(position, Vector3::new(0.0, 0.0, 0.0));
+
+        let (position, barycentrics) = closest_point_on_triangle(&Vector3::new(0.5, -1.0, 1.0), &triangle());
+        assert!((position - Vector3::new(0.5, 0.0, 0.0)).magnitude() < 1e-6);
+        assert!((barycentrics - Vector2::new(0.5, 0.0)).magnitude() < 1e-6);
+
+        let (position, barycentrics) = closest_point_on_triangle(&Vector3::new(1.0, 1.0, 0.0), &triangle());
+        assert!((position - Vector3::new(0.5, 0.5, 0.0)).magnitude() < 1e-6);
+        assert!((barycentrics - Vector2::new(0.5, 0.5)).magnitude() < 1e-6);
+    }
+
+    #[test]
+    fn closest_point_of_degenerate_triangle_is_finite() {
+        let degenerate = [Vector3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 0.0, 0.0), Vector3::new(1.0, 0.0, 0.0)];
+        let (position, barycentrics) = closest_point_on_triangle(&Vector3::new(0.5, 1.0, 0.0), &degenerate);
+        assert!((position - Vector3::new(0.5, 0.0, 0.0)).magnitude() < 1e-6);
+        assert!(barycentrics.x.is_finite() && barycentrics.y.is_finite());
+    }
+
+    #[test]
+    fn triangle_overlaps_boxes() {
+        let unit = Aabb::new(Vector3::new(-0.1, -0.1, -0.1), Vector3::new(0.1, 0.1, 0.1));
+        assert!(triangle_overlaps_aabb(&triangle(), &unit));
+
+        // Inside the bounds of the triangle, but past its hypotenuse
+        let past_edge = Aabb::new(Vector3::new(0.8, 0.8, -0.1), Vector3::new(0.9, 0.9, 0.1));
+        assert!(!triangle_overlaps_aabb(&triangle(), &past_edge));
+
+        let above = Aabb::new(Vector3::new(0.1, 0.1, 0.5), Vector3::new(0.2, 0.2, 0.6));
+        assert!(!triangle_overlaps_aabb(&triangle(), &above));
+
+        // The box swallows the whole triangle
+        let around = Aabb::new(Vector3::new(-5.0, -5.0, -5.0), Vector3::new(5.0, 5.0, 5.0));
+        assert!(triangle_overlaps_aabb(&triangle(), &around));
+    }
+}
\ No newline at end of file
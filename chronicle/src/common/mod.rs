@@ -5,7 +5,7 @@ pub use rc_cell::*;
 pub mod arc_mutex;
 pub use arc_mutex::*;
 
-use cgmath::{Vector3, Quaternion, Matrix4, SquareMatrix, Vector4};
+use cgmath::{Vector3, Quaternion, Matrix3, Matrix4, SquareMatrix, Vector4, InnerSpace};
 
 pub fn vec_remove_multiple<T>(vec: &mut Vec<T>, indices: &mut Vec<usize>) {
     indices.sort();    
@@ -22,14 +22,15 @@ pub fn quaternion_to_matrix(quaternion: &Quaternion<f32>) -> Matrix4<f32> {
 
     let quat = Vector4::<f32>::new(quaternion.v.x, quaternion.v.y, quaternion.v.z, quaternion.s);
 
+    // Columns of the rotation matrix, mat4.x[1] is the second row of the first column
     mat4.x[0 - 0] = 1.0 - 2.0 * quat.y * quat.y - 2.0 * quat.z * quat.z;
-    mat4.x[1 - 0] = 2.0 * quat.x * quat.y - 2.0 * quat.w * quat.z;
-    mat4.x[2 - 0] = 2.0 * quat.x * quat.z + 2.0 * quat.w * quat.y;
-    mat4.y[4 - 4] = 2.0 * quat.x * quat.y + 2.0 * quat.w * quat.z;
+    mat4.x[1 - 0] = 2.0 * quat.x * quat.y + 2.0 * quat.w * quat.z;
+    mat4.x[2 - 0] = 2.0 * quat.x * quat.z - 2.0 * quat.w * quat.y;
+    mat4.y[4 - 4] = 2.0 * quat.x * quat.y - 2.0 * quat.w * quat.z;
     mat4.y[5 - 4] = 1.0 - 2.0 * quat.x * quat.x - 2.0 * quat.z * quat.z;
-    mat4.y[6 - 4] = 2.0 * quat.y * quat.z - 2.0 * quat.w * quat.x;
-    mat4.z[8 - 8] = 2.0 * quat.x * quat.z - 2.0 * quat.w * quat.y;
-    mat4.z[9 - 8] = 2.0 * quat.y * quat.z + 2.0 * quat.w * quat.x;
+    mat4.y[6 - 4] = 2.0 * quat.y * quat.z + 2.0 * quat.w * quat.x;
+    mat4.z[8 - 8] = 2.0 * quat.x * quat.z + 2.0 * quat.w * quat.y;
+    mat4.z[9 - 8] = 2.0 * quat.y * quat.z - 2.0 * quat.w * quat.x;
     mat4.z[10- 8] = 1.0 - 2.0 * quat.x * quat.x - 2.0 * quat.y * quat.y;
 
     mat4
@@ -45,4 +46,22 @@ pub fn right() -> Vector3<f32> {
 
 pub fn forward() -> Vector3<f32> {
     Vector3::new(0.0, 0.0, -1.0)
+}
+
+/// Rotation that points `forward()` along `direction`, with its up axis as close to `up` as possible
+pub fn look_rotation(direction: &Vector3<f32>, up: &Vector3<f32>) -> Quaternion<f32> {
+    let forward = direction.normalize();
+
+    let mut right = forward.cross(*up);
+    if right.magnitude2() < 1e-12 {
+        // Looking straight along `up`, any perpendicular axis will do
+        right = forward.cross(match forward.z.abs() < 0.9 {
+            true => Vector3::unit_z(),
+            false => Vector3::unit_x()
+        });
+    }
+    let right = right.normalize();
+    let up = right.cross(forward);
+
+    Quaternion::from(Matrix3::from_cols(right, up, -forward))
 }
\ No newline at end of file
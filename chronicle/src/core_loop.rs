@@ -54,6 +54,9 @@ impl CoreLoop {
                             },
                             | WindowEvent::CursorMoved { position, .. } => {
                                 app.input().set_mouse_pos(Vector2::new(position.x as i32, position.y as i32));
+                            },
+                            | WindowEvent::MouseWheel { delta, .. } => {
+                                app.input().set_mouse_scroll(delta);
                             }
                             | _ => {},
                         }
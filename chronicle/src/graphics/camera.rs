@@ -1,31 +1,104 @@
-use cgmath::{Vector3, Quaternion, Matrix4, SquareMatrix, perspective, Deg};
+use cgmath::{Vector2, Vector3, Vector4, Quaternion, Matrix4, SquareMatrix, InnerSpace, perspective, ortho, Deg, Rad};
 use super::Transform;
 
 use crate::app;
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    Perspective,
+    /// `size` is the vertical extent of the view volume in world units
+    Orthographic { size: f32 },
+    /// Perspective without a far plane, depth goes from 1 at the near plane to 0 at infinity.
+    /// Requires a greater depth test and a depth clear value of 0
+    ReversedZInfinite
+}
+
+/// Lens and sensor settings of a real camera, lengths are in millimeters and scene units are meters.
+/// Depth of field and exposure only apply to the path traced output, rasterized frames only follow the field of view
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PhysicalCamera {
+    pub f_stop: f32,
+    /// Distance along the view direction that is in perfect focus
+    pub focus_distance: f32,
+    pub focal_length: f32,
+    pub sensor_height: f32,
+    /// Seconds
+    pub shutter_speed: f32,
+    pub iso: f32,
+    /// Stops added on top of the exposure of the settings above
+    pub exposure_compensation: f32
+}
+
+impl Default for PhysicalCamera {
+    /// A 50mm lens on a full frame sensor at f/2.8, 1/60s and ISO 100
+    fn default() -> Self {
+        PhysicalCamera {
+            f_stop: 2.8,
+            focus_distance: 5.0,
+            focal_length: 50.0,
+            sensor_height: 24.0,
+            shutter_speed: 1.0 / 60.0,
+            iso: 100.0,
+            exposure_compensation: 0.0
+        }
+    }
+}
+
+impl PhysicalCamera {
+    /// Vertical field of view of the lens on the sensor
+    pub fn get_fov(&self) -> Deg<f32> {
+        Deg::from(Rad(2.0 * (self.sensor_height / (2.0 * self.focal_length)).atan()))
+    }
+
+    /// Radius of the lens opening in meters
+    pub fn get_aperture_radius(&self) -> f32 {
+        self.focal_length / self.f_stop * 0.5 / 1000.0
+    }
+
+    /// Exposure value at ISO 100
+    pub fn get_ev100(&self) -> f32 {
+        (self.f_stop * self.f_stop / self.shutter_speed * 100.0 / self.iso).log2() - self.exposure_compensation
+    }
+
+    /// Scale from scene luminance to sensor output, based on the saturation based sensitivity of the sensor
+    pub fn get_exposure(&self) -> f32 {
+        1.0 / (1.2 * 2.0f32.powf(self.get_ev100()))
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct Camera {
     transform: Transform,
 
+    projection: Projection,
     aspect_ratio: Option<f32>,
     fov: f32,
     near: f32,
     far: f32,
+    jitter: Vector2<f32>,
+    physical: Option<PhysicalCamera>,
 
     proj_dirty: bool,
-    proj_matrix: Matrix4<f32>
+    viewport_size: Vector2<u32>,
+    proj_matrix: Matrix4<f32>,
+    jittered_proj_matrix: Matrix4<f32>
 }
 
 impl Camera {
     pub fn new() -> Self {
         Camera {
              transform: Transform::new(),
+             projection: Projection::Perspective,
              aspect_ratio: None,
              fov: 60.0,
              near: 0.1,
              far: 100.0,
+             jitter: Vector2::new(0.0, 0.0),
+             physical: None,
              proj_dirty: true,
-             proj_matrix: SquareMatrix::identity()
+             viewport_size: Vector2::new(0, 0),
+             proj_matrix: SquareMatrix::identity(),
+             jittered_proj_matrix: SquareMatrix::identity()
         }
     }
 
@@ -53,10 +126,28 @@ impl Camera {
         self.transform.rotate(rotation);
     }
 
+    /// Rotates the camera to face `target`
+    pub fn look_at(&mut self, target: &Vector3<f32>, up: &Vector3<f32>) {
+        self.transform.look_at(target, up);
+    }
+
     pub fn get_view_matrix(&mut self) -> &Matrix4<f32> {
         &self.transform.get_matrix(true)
     }
 
+    pub fn get_projection(&self) -> Projection {
+        self.projection
+    }
+
+    pub fn set_projection(&mut self, projection: Projection) {
+        self.projection = projection;
+        self.proj_dirty = true;
+    }
+
+    pub fn is_reversed_z(&self) -> bool {
+        self.projection == Projection::ReversedZInfinite
+    }
+
     pub fn get_fov(&self) -> f32 {
         self.fov
     }
@@ -75,6 +166,7 @@ impl Camera {
         self.proj_dirty = true;
     }
 
+    /// Ignored by `Projection::ReversedZInfinite`
     pub fn get_far(&self) -> f32 {
         self.far
     }
@@ -88,25 +180,135 @@ impl Camera {
         self.aspect_ratio
     }
 
+    /// Uses the aspect ratio of the window when `None`
     pub fn set_aspect_ratio(&mut self, aspect_ratio: Option<f32>) {
         self.aspect_ratio = aspect_ratio;
+        self.proj_dirty = true;
+    }
+
+    pub fn get_jitter(&self) -> Vector2<f32> {
+        self.jitter
+    }
+
+    /// Offsets the projection by a fraction of a pixel, for temporal techniques
+    pub fn set_jitter(&mut self, jitter: Vector2<f32>) {
+        self.jitter = jitter;
+        self.proj_dirty = true;
+    }
+
+    pub fn get_physical(&self) -> Option<&PhysicalCamera> {
+        self.physical.as_ref()
+    }
+
+    /// Enables depth of field and exposure for path traced output, the field of view follows the lens.
+    /// Without physical settings there is no depth of field and the exposure is 1
+    pub fn set_physical(&mut self, physical: Option<PhysicalCamera>) {
+        if let Some(physical) = physical.as_ref() {
+            self.fov = physical.get_fov().0;
+            self.proj_dirty = true;
+        }
+        self.physical = physical;
+    }
+
+    /// Returns false without physical settings, there is nothing to focus then
+    pub fn set_focus_distance(&mut self, focus_distance: f32) -> bool {
+        match self.physical.as_mut() {
+            Some(physical) => {
+                physical.focus_distance = focus_distance;
+                true
+            },
+            None => false
+        }
+    }
+
+    /// World space ray through a pixel of the window, returns the origin and normalized direction
+    pub fn get_screen_ray(&mut self, screen_pos: Vector2<f32>) -> (Vector3<f32>, Vector3<f32>) {
+        let view_inverse = self.get_view_matrix().invert().unwrap_or(Matrix4::identity());
+        let proj_inverse = self.get_unjittered_proj_matrix().invert().unwrap_or(Matrix4::identity());
+
+        let ndc = Vector2::new(
+            screen_pos.x / self.viewport_size.x.max(1) as f32 * 2.0 - 1.0,
+            screen_pos.y / self.viewport_size.y.max(1) as f32 * 2.0 - 1.0
+        );
+
+        let (origin, direction) = match self.projection {
+            Projection::Orthographic { .. } => {
+                let view_plane = proj_inverse * Vector4::new(ndc.x, ndc.y, 0.0, 1.0);
+                (Vector4::new(view_plane.x, view_plane.y, 0.0, 1.0), Vector4::new(0.0, 0.0, -1.0, 0.0))
+            },
+            _ => {
+                let target = proj_inverse * Vector4::new(ndc.x, ndc.y, 1.0, 1.0);
+                (Vector4::new(0.0, 0.0, 0.0, 1.0), target.truncate().normalize().extend(0.0))
+            }
+        };
+
+        ((view_inverse * origin).truncate(), (view_inverse * direction).truncate().normalize())
     }
 
+    /// Projection including the jitter, used for rendering
     pub fn get_proj_matrix(&mut self) -> &Matrix4<f32> {
-        if self.proj_dirty {
-            let aspect_ratio = self.aspect_ratio.unwrap_or_else(|| -> f32 {
-                let window = app().window();
-                window.width() as f32 / window.height() as f32
-            });
-            
-            self.proj_matrix = perspective(
+        self.update_proj_matrix();
+        &self.jittered_proj_matrix
+    }
+
+    /// Projection without the jitter, e.g. for reprojecting previous frames
+    pub fn get_unjittered_proj_matrix(&mut self) -> &Matrix4<f32> {
+        self.update_proj_matrix();
+        &self.proj_matrix
+    }
+
+    fn update_proj_matrix(&mut self) {
+        let viewport_size = {
+            let window = app().window();
+            Vector2::new(window.width(), window.height())
+        };
+
+        // Keep the previous projection while the window is minimized
+        if viewport_size.x == 0 || viewport_size.y == 0 {
+            return;
+        }
+
+        if viewport_size != self.viewport_size {
+            self.viewport_size = viewport_size;
+            self.proj_dirty = true;
+        }
+
+        if !self.proj_dirty {
+            return;
+        }
+
+        let aspect_ratio = self.aspect_ratio.unwrap_or(viewport_size.x as f32 / viewport_size.y as f32);
+        self.proj_matrix = match self.projection {
+            Projection::Perspective => perspective(
                 Deg(self.fov),
                 aspect_ratio,
                 self.near,
                 self.far
-            );
-        }
+            ),
+            Projection::Orthographic { size } => {
+                let half_height = size * 0.5;
+                let half_width = half_height * aspect_ratio;
+                ortho(-half_width, half_width, -half_height, half_height, self.near, self.far)
+            },
+            Projection::ReversedZInfinite => {
+                let focal_length = 1.0 / (Rad::from(Deg(self.fov)).0 * 0.5).tan();
+                Matrix4::new(
+                    focal_length / aspect_ratio, 0.0, 0.0, 0.0,
+                    0.0, focal_length, 0.0, 0.0,
+                    0.0, 0.0, 0.0, -1.0,
+                    0.0, 0.0, self.near, 0.0
+                )
+            }
+        };
 
-        &self.proj_matrix
+        // A clip space translation is scaled by w, so after the divide it is the same fraction of a pixel for every projection
+        let offset = Vector3::new(
+            self.jitter.x * 2.0 / viewport_size.x as f32,
+            self.jitter.y * 2.0 / viewport_size.y as f32,
+            0.0
+        );
+        self.jittered_proj_matrix = Matrix4::from_translation(offset) * self.proj_matrix;
+
+        self.proj_dirty = false;
     }
 }
\ No newline at end of file
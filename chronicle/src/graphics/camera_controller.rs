@@ -0,0 +1,381 @@
+use cgmath::{Vector2, Vector3, Quaternion, Rad, Deg, InnerSpace, Rotation, Rotation3};
+
+use super::{Camera, RenderCameraProperties};
+use crate::common::{RcCell, forward, right, up, look_rotation};
+use crate::input::{Input, MouseButton, VirtualKeyCode};
+
+/// Keeps the pitch just short of straight up or down, where yaw is undefined
+const MAX_PITCH: f32 = std::f32::consts::FRAC_PI_2 - 0.01;
+
+/// Moves a render camera from user input, `update` is called once per frame
+pub trait CameraController {
+    fn update(&mut self, input: &Input, delta_time: f32);
+}
+
+/// Fraction of the remaining distance to a goal that is covered this frame.
+/// `smoothing` is the time in seconds to cover about two thirds of it, 0 jumps straight to the goal
+fn smoothing_factor(smoothing: f32, delta_time: f32) -> f32 {
+    match smoothing > 0.0 {
+        true => 1.0 - (-delta_time / smoothing).exp(),
+        false => 1.0
+    }
+}
+
+fn to_yaw_pitch(rotation: &Quaternion<f32>) -> (f32, f32) {
+    let direction = rotation.rotate_vector(forward());
+    (f32::atan2(-direction.x, -direction.z), direction.y.clamp(-1.0, 1.0).asin())
+}
+
+fn from_yaw_pitch(yaw: f32, pitch: f32) -> Quaternion<f32> {
+    Quaternion::from_angle_y(Rad(yaw)) * Quaternion::from_angle_x(Rad(pitch))
+}
+
+/// Free flight with mouse look, WASD to move and Q/E to go up and down.
+/// Shift and control speed up and slow down movement
+pub struct FlyController {
+    camera: RcCell<RenderCameraProperties>,
+
+    /// Degrees of rotation per pixel of mouse movement
+    pub sensitivity: f32,
+    /// Units per second
+    pub speed: f32,
+    pub fast_multiplier: f32,
+    pub slow_multiplier: f32,
+    /// Seconds the rotation and velocity take to catch up with the input, see `smoothing_factor`
+    pub look_smoothing: f32,
+    pub move_smoothing: f32,
+    /// Button that has to be held to look around, `None` always looks around, e.g. with a locked cursor
+    pub look_button: Option<MouseButton>,
+
+    yaw: f32,
+    pitch: f32,
+    current_yaw: f32,
+    current_pitch: f32,
+    velocity: Vector3<f32>
+}
+
+impl FlyController {
+    pub fn new(camera: RcCell<RenderCameraProperties>) -> Self {
+        let mut controller = FlyController {
+            camera: camera,
+            sensitivity: 0.1,
+            speed: 5.0,
+            fast_multiplier: 4.0,
+            slow_multiplier: 0.25,
+            look_smoothing: 0.02,
+            move_smoothing: 0.1,
+            look_button: Some(MouseButton::Right),
+            yaw: 0.0,
+            pitch: 0.0,
+            current_yaw: 0.0,
+            current_pitch: 0.0,
+            velocity: Vector3::new(0.0, 0.0, 0.0)
+        };
+        controller.reset();
+
+        controller
+    }
+
+    /// Takes over the current rotation of the camera and stops moving, e.g. after the camera was placed by hand
+    pub fn reset(&mut self) {
+        let (yaw, pitch) = to_yaw_pitch(self.camera.as_ref().camera.get_rotation());
+        self.yaw = yaw;
+        self.pitch = pitch.clamp(-MAX_PITCH, MAX_PITCH);
+        self.current_yaw = self.yaw;
+        self.current_pitch = self.pitch;
+        self.velocity = Vector3::new(0.0, 0.0, 0.0);
+    }
+
+    pub fn get_camera(&self) -> &RcCell<RenderCameraProperties> {
+        &self.camera
+    }
+}
+
+impl CameraController for FlyController {
+    fn update(&mut self, input: &Input, delta_time: f32) {
+        if self.look_button.map_or(true, |button| input.mouse_button(button)) {
+            let mouse_delta = input.mouse_delta();
+            self.yaw -= Rad::from(Deg(mouse_delta.x * self.sensitivity)).0;
+            self.pitch = (self.pitch - Rad::from(Deg(mouse_delta.y * self.sensitivity)).0).clamp(-MAX_PITCH, MAX_PITCH);
+        }
+
+        let look_factor = smoothing_factor(self.look_smoothing, delta_time);
+        self.current_yaw += (self.yaw - self.current_yaw) * look_factor;
+        self.current_pitch += (self.pitch - self.current_pitch) * look_factor;
+        let rotation = from_yaw_pitch(self.current_yaw, self.current_pitch);
+
+        let mut direction = Vector3::new(0.0, 0.0, 0.0);
+        if input.key(VirtualKeyCode::W) {
+            direction += forward();
+        }
+        if input.key(VirtualKeyCode::S) {
+            direction -= forward();
+        }
+        if input.key(VirtualKeyCode::D) {
+            direction += right();
+        }
+        if input.key(VirtualKeyCode::A) {
+            direction -= right();
+        }
+        // Vertical movement stays vertical regardless of where the camera looks
+        direction = rotation.rotate_vector(direction);
+        if input.key(VirtualKeyCode::Q) {
+            direction += up();
+        }
+        if input.key(VirtualKeyCode::E) {
+            direction -= up();
+        }
+
+        let mut speed = self.speed;
+        if input.key(VirtualKeyCode::LShift) {
+            speed *= self.fast_multiplier;
+        }
+        if input.key(VirtualKeyCode::LControl) {
+            speed *= self.slow_multiplier;
+        }
+
+        let target_velocity = match direction.magnitude2() > 0.0 {
+            true => direction.normalize() * speed,
+            false => Vector3::new(0.0, 0.0, 0.0)
+        };
+        self.velocity += (target_velocity - self.velocity) * smoothing_factor(self.move_smoothing, delta_time);
+
+        let camera = &mut self.camera.as_mut().camera;
+        camera.set_rotation(&rotation);
+        camera.translate(&(self.velocity * delta_time));
+    }
+}
+
+/// Pivot and distance shared by the controllers that circle around a point
+struct Orbit {
+    pivot: Vector3<f32>,
+    distance: f32,
+    current_pivot: Vector3<f32>,
+    current_distance: f32
+}
+
+impl Orbit {
+    fn new(pivot: Vector3<f32>, distance: f32) -> Self {
+        Orbit {
+            pivot: pivot,
+            distance: distance,
+            current_pivot: pivot,
+            current_distance: distance
+        }
+    }
+
+    /// Moves the pivot in the view plane, scaled with the distance so the pivot follows the cursor
+    fn pan(&mut self, rotation: &Quaternion<f32>, mouse_delta: Vector2<f32>, pan_speed: f32) {
+        let offset = Vector3::new(-mouse_delta.x, mouse_delta.y, 0.0) * pan_speed * self.distance;
+        self.pivot += rotation.rotate_vector(offset);
+    }
+
+    fn zoom(&mut self, scroll: f32, zoom_speed: f32, min_distance: f32, max_distance: f32) {
+        self.distance = (self.distance * (1.0 - zoom_speed).powf(scroll)).clamp(min_distance, max_distance);
+    }
+
+    fn smooth(&mut self, factor: f32) {
+        self.current_pivot += (self.pivot - self.current_pivot) * factor;
+        self.current_distance += (self.distance - self.current_distance) * factor;
+    }
+
+    fn apply(&self, camera: &mut Camera, rotation: &Quaternion<f32>) {
+        // The camera looks along -z, so it sits on the +z side of the pivot
+        let translation = self.current_pivot + rotation.rotate_vector(Vector3::new(0.0, 0.0, self.current_distance));
+        camera.set_rotation(rotation);
+        camera.set_translation(&translation);
+    }
+}
+
+/// Distance to the pivot and rotation of a camera looking at it
+fn orbit_from_camera(camera: &Camera, pivot: &Vector3<f32>) -> (f32, Quaternion<f32>) {
+    let offset = camera.get_translation() - pivot;
+    match offset.magnitude() > 1e-6 {
+        true => (offset.magnitude(), look_rotation(&-offset, &up())),
+        false => (1.0, *camera.get_rotation())
+    }
+}
+
+/// Arcball style orbit around a pivot. Dragging rotates around the camera's own axes, so it can roll over the poles.
+/// The middle button pans the pivot and the scroll wheel zooms
+pub struct OrbitController {
+    camera: RcCell<RenderCameraProperties>,
+
+    /// Degrees of rotation per pixel of mouse movement
+    pub sensitivity: f32,
+    /// Fraction of the distance to the pivot moved per pixel of mouse movement
+    pub pan_speed: f32,
+    /// Fraction of the distance to the pivot moved per scrolled line
+    pub zoom_speed: f32,
+    pub min_distance: f32,
+    pub max_distance: f32,
+    /// Seconds the camera takes to catch up with the input, see `smoothing_factor`
+    pub smoothing: f32,
+    pub rotate_button: MouseButton,
+    pub pan_button: MouseButton,
+
+    orbit: Orbit,
+    rotation: Quaternion<f32>,
+    current_rotation: Quaternion<f32>
+}
+
+impl OrbitController {
+    /// Starts orbiting `pivot` from where the camera currently is
+    pub fn new(camera: RcCell<RenderCameraProperties>, pivot: Vector3<f32>) -> Self {
+        let (distance, rotation) = orbit_from_camera(&camera.as_ref().camera, &pivot);
+
+        OrbitController {
+            camera: camera,
+            sensitivity: 0.25,
+            pan_speed: 0.002,
+            zoom_speed: 0.1,
+            min_distance: 0.01,
+            max_distance: 10000.0,
+            smoothing: 0.05,
+            rotate_button: MouseButton::Left,
+            pan_button: MouseButton::Middle,
+            orbit: Orbit::new(pivot, distance),
+            rotation: rotation,
+            current_rotation: rotation
+        }
+    }
+
+    pub fn get_pivot(&self) -> &Vector3<f32> {
+        &self.orbit.pivot
+    }
+
+    pub fn set_pivot(&mut self, pivot: &Vector3<f32>) {
+        self.orbit.pivot = *pivot;
+    }
+
+    pub fn get_distance(&self) -> f32 {
+        self.orbit.distance
+    }
+
+    pub fn set_distance(&mut self, distance: f32) {
+        self.orbit.distance = distance.clamp(self.min_distance, self.max_distance);
+    }
+
+    pub fn get_camera(&self) -> &RcCell<RenderCameraProperties> {
+        &self.camera
+    }
+}
+
+impl CameraController for OrbitController {
+    fn update(&mut self, input: &Input, delta_time: f32) {
+        let mouse_delta = input.mouse_delta();
+        if input.mouse_button(self.rotate_button) {
+            let yaw = Quaternion::from_angle_y(Deg(-mouse_delta.x * self.sensitivity));
+            let pitch = Quaternion::from_angle_x(Deg(-mouse_delta.y * self.sensitivity));
+            self.rotation = (self.rotation * yaw * pitch).normalize();
+        }
+        if input.mouse_button(self.pan_button) {
+            self.orbit.pan(&self.rotation, mouse_delta, self.pan_speed);
+        }
+        self.orbit.zoom(input.mouse_scroll(), self.zoom_speed, self.min_distance, self.max_distance);
+
+        let factor = smoothing_factor(self.smoothing, delta_time);
+        self.orbit.smooth(factor);
+
+        // Both signs describe the same rotation, interpolating towards the closer one takes the short way around
+        let rotation = match self.current_rotation.dot(self.rotation) < 0.0 {
+            true => -self.rotation,
+            false => self.rotation
+        };
+        self.current_rotation = self.current_rotation.slerp(rotation, factor).normalize();
+
+        self.orbit.apply(&mut self.camera.as_mut().camera, &self.current_rotation);
+    }
+}
+
+/// Orbit around a pivot that keeps the world up axis upright, like a turntable.
+/// Dragging yaws around the up axis and pitches within limits, the middle button pans the pivot and the scroll wheel zooms
+pub struct TurntableController {
+    camera: RcCell<RenderCameraProperties>,
+
+    /// Degrees of rotation per pixel of mouse movement
+    pub sensitivity: f32,
+    /// Fraction of the distance to the pivot moved per pixel of mouse movement
+    pub pan_speed: f32,
+    /// Fraction of the distance to the pivot moved per scrolled line
+    pub zoom_speed: f32,
+    pub min_distance: f32,
+    pub max_distance: f32,
+    /// Seconds the camera takes to catch up with the input, see `smoothing_factor`
+    pub smoothing: f32,
+    pub rotate_button: MouseButton,
+    pub pan_button: MouseButton,
+
+    orbit: Orbit,
+    yaw: f32,
+    pitch: f32,
+    current_yaw: f32,
+    current_pitch: f32
+}
+
+impl TurntableController {
+    /// Starts orbiting `pivot` from where the camera currently is
+    pub fn new(camera: RcCell<RenderCameraProperties>, pivot: Vector3<f32>) -> Self {
+        let (distance, rotation) = orbit_from_camera(&camera.as_ref().camera, &pivot);
+        let (yaw, pitch) = to_yaw_pitch(&rotation);
+        let pitch = pitch.clamp(-MAX_PITCH, MAX_PITCH);
+
+        TurntableController {
+            camera: camera,
+            sensitivity: 0.25,
+            pan_speed: 0.002,
+            zoom_speed: 0.1,
+            min_distance: 0.01,
+            max_distance: 10000.0,
+            smoothing: 0.05,
+            rotate_button: MouseButton::Left,
+            pan_button: MouseButton::Middle,
+            orbit: Orbit::new(pivot, distance),
+            yaw: yaw,
+            pitch: pitch,
+            current_yaw: yaw,
+            current_pitch: pitch
+        }
+    }
+
+    pub fn get_pivot(&self) -> &Vector3<f32> {
+        &self.orbit.pivot
+    }
+
+    pub fn set_pivot(&mut self, pivot: &Vector3<f32>) {
+        self.orbit.pivot = *pivot;
+    }
+
+    pub fn get_distance(&self) -> f32 {
+        self.orbit.distance
+    }
+
+    pub fn set_distance(&mut self, distance: f32) {
+        self.orbit.distance = distance.clamp(self.min_distance, self.max_distance);
+    }
+
+    pub fn get_camera(&self) -> &RcCell<RenderCameraProperties> {
+        &self.camera
+    }
+}
+
+impl CameraController for TurntableController {
+    fn update(&mut self, input: &Input, delta_time: f32) {
+        let mouse_delta = input.mouse_delta();
+        if input.mouse_button(self.rotate_button) {
+            self.yaw -= Rad::from(Deg(mouse_delta.x * self.sensitivity)).0;
+            self.pitch = (self.pitch - Rad::from(Deg(mouse_delta.y * self.sensitivity)).0).clamp(-MAX_PITCH, MAX_PITCH);
+        }
+        if input.mouse_button(self.pan_button) {
+            self.orbit.pan(&from_yaw_pitch(self.yaw, self.pitch), mouse_delta, self.pan_speed);
+        }
+        self.orbit.zoom(input.mouse_scroll(), self.zoom_speed, self.min_distance, self.max_distance);
+
+        let factor = smoothing_factor(self.smoothing, delta_time);
+        self.orbit.smooth(factor);
+        self.current_yaw += (self.yaw - self.current_yaw) * factor;
+        self.current_pitch += (self.pitch - self.current_pitch) * factor;
+
+        self.orbit.apply(&mut self.camera.as_mut().camera, &from_yaw_pitch(self.current_yaw, self.current_pitch));
+    }
+}
\ No newline at end of file
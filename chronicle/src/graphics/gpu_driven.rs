@@ -0,0 +1,278 @@
+use ash::vk;
+use cgmath::{Matrix4, Vector4, SquareMatrix, Zero, InnerSpace};
+
+use super::vulkan::*;
+use super::vulkan::utility::constants::MAX_FRAMES_IN_FLIGHT;
+use crate::resources::VertexLayout;
+use crate::ArcMutex;
+use std::sync::Arc;
+
+const CULL_GROUP_SIZE: u32 = 64;
+const INITIAL_INSTANCE_CAPACITY: usize = 1024;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub(super) struct GpuInstance {
+    pub model: Matrix4<f32>,
+    pub bounds_min: Vector4<f32>,
+    pub bounds_max: Vector4<f32>,
+    pub base_color: Vector4<f32>,
+    pub index_count: u32,
+    pub first_index: u32,
+    pub vertex_offset: i32,
+    pub _padding: u32
+}
+
+impl Default for GpuInstance {
+    fn default() -> Self {
+        GpuInstance {
+            model: SquareMatrix::identity(),
+            bounds_min: Vector4::zero(),
+            bounds_max: Vector4::zero(),
+            base_color: Vector4::new(1.0, 1.0, 1.0, 1.0),
+            index_count: 0,
+            first_index: 0,
+            vertex_offset: 0,
+            _padding: 0
+        }
+    }
+}
+
+#[repr(C)]
+struct CullPushConstants {
+    planes: [Vector4<f32>; 6],
+    instance_count: u32,
+    _padding: [u32; 3]
+}
+
+#[repr(C)]
+struct DrawPushConstants {
+    view_proj: Matrix4<f32>
+}
+
+pub(super) struct GpuDrivenPass {
+    cull_desc_layout: Arc<VkDescriptorSetLayout>,
+    cull_pipeline: Arc<VkComputePipeline>,
+    draw_desc_layout: Arc<VkDescriptorSetLayout>,
+    draw_pipeline: Arc<VkGraphicsPipeline>,
+
+    /// One set per frame in flight, a frame writes its instances and draws while the gpu may still read the previous ones
+    instance_buffers: Vec<VkDataBuffer<GpuInstance>>,
+    draw_buffers: Vec<Arc<VkBuffer>>,
+    count_buffers: Vec<Arc<VkBuffer>>,
+    current_frame: usize,
+    capacity: usize,
+    instance_count: u32
+}
+
+impl GpuDrivenPass {
+    pub(super) fn new(app: &mut VkApp, render_pass: &VkRenderPass, vertex_layout: VertexLayout) -> Self {
+        let device = app.get_device();
+        let swapchain = app.get_swapchain().unwrap();
+
+        let cull_desc_layout = VkDescriptorSetLayout::new(device.clone(), &vec![
+            Self::storage_binding(0, vk::ShaderStageFlags::COMPUTE),
+            Self::storage_binding(1, vk::ShaderStageFlags::COMPUTE),
+            Self::storage_binding(2, vk::ShaderStageFlags::COMPUTE)
+        ]);
+
+        let cull_pipeline = VkComputePipeline::new(
+            device.clone(),
+            &vec![&cull_desc_layout],
+            &vec![
+                vk::PushConstantRange {
+                    stage_flags: vk::ShaderStageFlags::COMPUTE,
+                    offset: 0,
+                    size: std::mem::size_of::<CullPushConstants>() as u32
+                }
+            ],
+            String::from("gpu_driven/cull.comp")
+        );
+
+        let draw_desc_layout = VkDescriptorSetLayout::new(device.clone(), &vec![
+            Self::storage_binding(0, vk::ShaderStageFlags::VERTEX)
+        ]);
+
+        let draw_push_constants = vec![
+            vk::PushConstantRange {
+                stage_flags: vk::ShaderStageFlags::VERTEX,
+                offset: 0,
+                size: std::mem::size_of::<DrawPushConstants>() as u32
+            }
+        ];
+        let draw_shaders = vec![String::from("gpu_driven/draw.vert"), String::from("gpu_driven/draw.frag")];
+        let draw_pipeline = match vertex_layout {
+            VertexLayout::Full => VkGraphicsPipeline::new::<VkVertex>(
+                device.clone(),
+                swapchain.as_ref().get_extent(),
+                render_pass,
+                &vec![&draw_desc_layout],
+                &draw_push_constants,
+                &draw_shaders,
+                vk::PrimitiveTopology::TRIANGLE_LIST,
+                vk::CullModeFlags::BACK,
+                vk::TRUE
+            ),
+            VertexLayout::Compact => VkGraphicsPipeline::new::<VkCompactVertex>(
+                device.clone(),
+                swapchain.as_ref().get_extent(),
+                render_pass,
+                &vec![&draw_desc_layout],
+                &draw_push_constants,
+                &draw_shaders,
+                vk::PrimitiveTopology::TRIANGLE_LIST,
+                vk::CullModeFlags::BACK,
+                vk::TRUE
+            )
+        };
+
+        let (instance_buffers, draw_buffers) = Self::create_buffers(app, INITIAL_INSTANCE_CAPACITY);
+        let count_buffers = (0..MAX_FRAMES_IN_FLIGHT)
+            .map(|_| Arc::new(VkBuffer::new(
+                "Draw count BUFFER".to_owned(),
+                device.clone(),
+                app.get_allocator(),
+                std::mem::size_of::<u32>() as vk::DeviceSize,
+                vk::BufferUsageFlags::INDIRECT_BUFFER | vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::TRANSFER_DST,
+                vk::MemoryPropertyFlags::DEVICE_LOCAL,
+                None
+            )))
+            .collect();
+
+        GpuDrivenPass {
+            cull_desc_layout: cull_desc_layout,
+            cull_pipeline: cull_pipeline,
+            draw_desc_layout: draw_desc_layout,
+            draw_pipeline: draw_pipeline,
+            instance_buffers: instance_buffers,
+            draw_buffers: draw_buffers,
+            count_buffers: count_buffers,
+            current_frame: 0,
+            capacity: INITIAL_INSTANCE_CAPACITY,
+            instance_count: 0
+        }
+    }
+
+    fn storage_binding(binding: u32, stage_flags: vk::ShaderStageFlags) -> vk::DescriptorSetLayoutBinding {
+        vk::DescriptorSetLayoutBinding {
+            binding: binding,
+            descriptor_type: vk::DescriptorType::STORAGE_BUFFER,
+            descriptor_count: 1,
+            stage_flags: stage_flags,
+            p_immutable_samplers: std::ptr::null(),
+        }
+    }
+
+    fn create_buffers(app: &mut VkApp, capacity: usize) -> (Vec<VkDataBuffer<GpuInstance>>, Vec<Arc<VkBuffer>>) {
+        let instance_buffers = (0..MAX_FRAMES_IN_FLIGHT)
+            .map(|_| VkDataBuffer::new(
+                "GPU Instances",
+                app,
+                &vec![GpuInstance::default(); capacity],
+                vk::BufferUsageFlags::STORAGE_BUFFER,
+                vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+                true
+            ))
+            .collect();
+
+        let draw_buffers = (0..MAX_FRAMES_IN_FLIGHT)
+            .map(|_| Arc::new(VkBuffer::new(
+                "Draw commands BUFFER".to_owned(),
+                app.get_device(),
+                app.get_allocator(),
+                (std::mem::size_of::<vk::DrawIndexedIndirectCommand>() * capacity) as vk::DeviceSize,
+                vk::BufferUsageFlags::INDIRECT_BUFFER | vk::BufferUsageFlags::STORAGE_BUFFER,
+                vk::MemoryPropertyFlags::DEVICE_LOCAL,
+                None
+            )))
+            .collect();
+
+        (instance_buffers, draw_buffers)
+    }
+
+    pub(super) fn upload_instances(&mut self, app: &mut VkApp, instances: &Vec<GpuInstance>) {
+        if instances.len() > self.capacity {
+            app.get_device().wait_idle();
+
+            self.capacity = instances.len().next_power_of_two();
+            let (instance_buffers, draw_buffers) = Self::create_buffers(app, self.capacity);
+            self.instance_buffers = instance_buffers;
+            self.draw_buffers = draw_buffers;
+        }
+
+        self.current_frame = (self.current_frame + 1) % MAX_FRAMES_IN_FLIGHT;
+        self.instance_buffers[self.current_frame].set_data(instances);
+        self.instance_count = instances.len() as u32;
+    }
+
+    pub(super) fn cull(&self, cmd_buffer: &mut VkCmdBuffer, view_proj: &Matrix4<f32>) {
+        cmd_buffer.fill_buffer(self.count_buffers[self.current_frame].clone(), 0);
+        cmd_buffer.barrier(
+            vk::AccessFlags::TRANSFER_WRITE,
+            vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::COMPUTE_SHADER
+        );
+
+        cmd_buffer.bind_compute_pipeline(self.cull_pipeline.clone());
+        cmd_buffer.set_desc_layout(0, self.cull_desc_layout.clone());
+        cmd_buffer.set_desc_storage_buffer(0, 0, self.instance_buffers[self.current_frame].get_buffer());
+        cmd_buffer.set_desc_storage_buffer(0, 1, self.draw_buffers[self.current_frame].clone());
+        cmd_buffer.set_desc_storage_buffer(0, 2, self.count_buffers[self.current_frame].clone());
+        cmd_buffer.bind_desc_sets();
+
+        cmd_buffer.push_constant(
+            &CullPushConstants {
+                planes: frustum_planes(view_proj),
+                instance_count: self.instance_count,
+                _padding: [0; 3]
+            },
+            vk::ShaderStageFlags::COMPUTE
+        );
+        cmd_buffer.dispatch(self.instance_count.div_ceil(CULL_GROUP_SIZE), 1, 1);
+
+        cmd_buffer.barrier(
+            vk::AccessFlags::SHADER_WRITE,
+            vk::AccessFlags::INDIRECT_COMMAND_READ,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::PipelineStageFlags::DRAW_INDIRECT
+        );
+    }
+
+    pub(super) fn draw(&self, cmd_buffer: &mut VkCmdBuffer, arena: &ArcMutex<VkMeshArena>, view_proj: &Matrix4<f32>) {
+        cmd_buffer.bind_graphics_pipeline(self.draw_pipeline.clone());
+        cmd_buffer.set_desc_layout(0, self.draw_desc_layout.clone());
+        cmd_buffer.set_desc_storage_buffer(0, 0, self.instance_buffers[self.current_frame].get_buffer());
+        cmd_buffer.bind_desc_sets();
+
+        cmd_buffer.push_constant(
+            &DrawPushConstants {
+                view_proj: *view_proj
+            },
+            vk::ShaderStageFlags::VERTEX
+        );
+
+        cmd_buffer.bind_mesh_arena(&arena.as_ref());
+        cmd_buffer.draw_indexed_indirect_count(
+            self.draw_buffers[self.current_frame].clone(),
+            0,
+            self.count_buffers[self.current_frame].clone(),
+            0,
+            self.instance_count,
+            std::mem::size_of::<vk::DrawIndexedIndirectCommand>() as u32
+        );
+    }
+}
+
+// Gribb-Hartmann plane extraction, normalized so the distances are in world units
+fn frustum_planes(m: &Matrix4<f32>) -> [Vector4<f32>; 6] {
+    let row = |i: usize| Vector4::new(m.x[i], m.y[i], m.z[i], m.w[i]);
+    let (r0, r1, r2, r3) = (row(0), row(1), row(2), row(3));
+
+    let mut planes = [r3 + r0, r3 - r0, r3 + r1, r3 - r1, r3 + r2, r3 - r2];
+    for plane in planes.iter_mut() {
+        *plane /= plane.truncate().magnitude();
+    }
+
+    planes
+}
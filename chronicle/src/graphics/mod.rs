@@ -8,62 +8,192 @@ pub mod transform;
 pub use transform::*;
 pub mod camera;
 pub use camera::*;
+pub mod camera_controller;
+pub use camera_controller::*;
+
+mod gpu_driven;
+use gpu_driven::*;
+mod residency;
+use residency::*;
 
 pub type ImGuiUI = imgui::Ui;
 
 mod vulkan;
 use vulkan::*;
+use vulkan::utility::constants::{MAX_FRAMES_IN_FLIGHT, MAX_RT_TEXTURES};
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use ash::vk;
-use cgmath::{Matrix4, SquareMatrix, Vector4, Vector3, Zero};
+use cgmath::{Matrix4, SquareMatrix, Vector4, Vector3, Vector2, InnerSpace};
 
 use crate::Window;
-use crate::resources::{Model, Resource, Texture, model};
-use crate::common::{RcCell, vec_remove_multiple};
+use crate::resources::{Model, Mesh, MeshKind, Resource, Texture, Material, MaterialTexture, TextureSampler, VertexLayout};
+use crate::common::{RcCell, Timer, vec_remove_multiple};
+use crate::bvh::{MeshBvh, SceneBvh, Ray};
 
+/// Mirrors `TextureRef` in raytracing/host.glsl (scalar layout)
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct TextureRef {
+    pub index: i32,
+    pub tex_coord: u32,
+    pub rotation: f32,
+    pub offset: Vector2<f32>,
+    pub scale: Vector2<f32>
+}
+
+impl TextureRef {
+    fn new(texture: &MaterialTexture, index: i32) -> Self {
+        TextureRef {
+            index: index,
+            tex_coord: texture.tex_coord,
+            rotation: texture.transform.rotation,
+            offset: texture.transform.offset,
+            scale: texture.transform.scale
+        }
+    }
+}
+
+/// Mirrors `Material` in raytracing/host.glsl (scalar layout)
 #[repr(C)]
 struct MaterialProperties {
     pub base_color_factor: Vector4<f32>,
-    pub base_color_texture: i32,
-    pub normal_scale: f32,
-    pub normal_texture: i32,
+    pub emissive_factor: Vector3<f32>,
+    pub emissive_strength: f32,
     pub metallic_factor: f32,
     pub roughness_factor: f32,
-    pub metallic_roughness_texture: i32,
+    pub normal_scale: f32,
     pub occlusion_strength: f32,
-    pub occlusion_texture: i32,
-    pub emissive_factor: Vector3<f32>,
-    pub emissive_texture: i32,
+    pub transmission_factor: f32,
+    pub ior: f32,
+    pub specular_factor: f32,
+    pub clearcoat_factor: f32,
+    pub specular_color_factor: Vector3<f32>,
+    pub clearcoat_roughness_factor: f32,
+    pub sheen_color_factor: Vector3<f32>,
+    pub sheen_roughness_factor: f32,
+    pub clearcoat_normal_scale: f32,
+
+    pub base_color_texture: TextureRef,
+    pub normal_texture: TextureRef,
+    pub metallic_roughness_texture: TextureRef,
+    pub occlusion_texture: TextureRef,
+    pub emissive_texture: TextureRef,
+    pub transmission_texture: TextureRef,
+    pub specular_texture: TextureRef,
+    pub specular_color_texture: TextureRef,
+    pub clearcoat_texture: TextureRef,
+    pub clearcoat_roughness_texture: TextureRef,
+    pub clearcoat_normal_texture: TextureRef,
+    pub sheen_color_texture: TextureRef,
+    pub sheen_roughness_texture: TextureRef
 }
 
-impl Default for MaterialProperties {
-    fn default() -> Self {
+impl MaterialProperties {
+    /// `texture_index` maps a material texture to its index in the object's texture array, -1 if absent
+    fn new<F: Fn(&MaterialTexture) -> i32>(material: &Material, texture_index: F) -> Self {
+        let tex = |texture: &MaterialTexture| TextureRef::new(texture, texture_index(texture));
+
         MaterialProperties {
-            base_color_factor: Vector4::zero(),
-            base_color_texture: -1,
-            normal_scale: 0.0,
-            normal_texture: -1,
-            metallic_factor: 0.0,
-            roughness_factor: 0.0,
-            metallic_roughness_texture: -1,
-            occlusion_strength: 0.0,
-            occlusion_texture: -1,
-            emissive_factor: Vector3::zero(),
-            emissive_texture: -1
+            base_color_factor: material.base_color_factor,
+            emissive_factor: material.emissive_factor,
+            emissive_strength: material.emissive_strength,
+            metallic_factor: material.metallic_factor,
+            roughness_factor: material.roughness_factor,
+            normal_scale: material.normal_scale,
+            occlusion_strength: material.occlusion_strength,
+            transmission_factor: material.transmission_factor,
+            ior: material.ior,
+            specular_factor: material.specular_factor,
+            clearcoat_factor: material.clearcoat_factor,
+            specular_color_factor: material.specular_color_factor,
+            clearcoat_roughness_factor: material.clearcoat_roughness_factor,
+            sheen_color_factor: material.sheen_color_factor,
+            sheen_roughness_factor: material.sheen_roughness_factor,
+            clearcoat_normal_scale: material.clearcoat_normal_scale,
+
+            base_color_texture: tex(&material.base_color_texture),
+            normal_texture: tex(&material.normal_texture),
+            metallic_roughness_texture: tex(&material.metallic_roughness_texture),
+            occlusion_texture: tex(&material.occlusion_texture),
+            emissive_texture: tex(&material.emissive_texture),
+            transmission_texture: tex(&material.transmission_texture),
+            specular_texture: tex(&material.specular_texture),
+            specular_color_texture: tex(&material.specular_color_texture),
+            clearcoat_texture: tex(&material.clearcoat_texture),
+            clearcoat_roughness_texture: tex(&material.clearcoat_roughness_texture),
+            clearcoat_normal_texture: tex(&material.clearcoat_normal_texture),
+            sheen_color_texture: tex(&material.sheen_color_texture),
+            sheen_roughness_texture: tex(&material.sheen_roughness_texture)
         }
     }
 }
 
+/// Mirrors `ObjDesc` in raytracing/host.glsl (scalar layout), one per tlas instance
+#[repr(C)]
+struct ObjDesc {
+    texture_offset: i32,
+    vertex_layout: i32,
+    vertex_address: vk::DeviceAddress,
+    index_address: vk::DeviceAddress,
+    material_address: vk::DeviceAddress,
+    material_index: i32,
+    _padding: i32
+}
+
+/// Gpu copy of a model
+struct ResidentModel {
+    meshes: Vec<VkMesh>,
+    materials: VkDataBuffer<MaterialProperties>,
+    /// Textures of the materials, indexed by their `TextureRef`s
+    textures: Vec<MaterialTexture>
+}
+
+/// Mirrors `GlobalUniforms` in host.glsl
 #[repr(C)]
 struct RtGlobalUBO {
-    mvp: Matrix4<f32>
+    view_proj: Matrix4<f32>,
+    view_inverse: Matrix4<f32>,
+    proj_inverse: Matrix4<f32>,
+    aperture_radius: f32,
+    focus_distance: f32,
+    exposure: f32,
+    _padding: f32
 }
 
 impl Default for RtGlobalUBO {
     fn default() -> Self {
         RtGlobalUBO {
-            mvp: SquareMatrix::identity()
+            view_proj: SquareMatrix::identity(),
+            view_inverse: SquareMatrix::identity(),
+            proj_inverse: SquareMatrix::identity(),
+            aperture_radius: 0.0,
+            focus_distance: 1.0,
+            exposure: 1.0,
+            _padding: 0.0
+        }
+    }
+}
+
+impl RtGlobalUBO {
+    /// Without physical settings the camera is a pinhole with an exposure of 1
+    fn new(camera: &mut Camera) -> Self {
+        let view_matrix = *camera.get_view_matrix();
+        let proj_matrix = *camera.get_proj_matrix();
+
+        let (aperture_radius, focus_distance, exposure) = match camera.get_physical() {
+            Some(physical) => (physical.get_aperture_radius(), physical.focus_distance, physical.get_exposure()),
+            None => (0.0, 1.0, 1.0)
+        };
+
+        RtGlobalUBO {
+            view_proj: proj_matrix * view_matrix,
+            view_inverse: view_matrix.invert().unwrap_or(Matrix4::identity()),
+            proj_inverse: proj_matrix.invert().unwrap_or(Matrix4::identity()),
+            aperture_radius: aperture_radius,
+            focus_distance: focus_distance,
+            exposure: exposure,
+            _padding: 0.0
         }
     }
 }
@@ -86,7 +216,7 @@ pub struct Renderer {
     render_img: ArcMutex<VkImage>,
     render_pass: Arc<VkRenderPass>,
     present_render_pass: Arc<VkRenderPass>,
-    pipeline: Arc<VkGraphicsPipeline>,
+    pipelines: HashMap<MeshKind, Arc<VkGraphicsPipeline>>,
 
     descriptor_layout: Arc<VkDescriptorSetLayout>,
 
@@ -94,13 +224,41 @@ pub struct Renderer {
     rt_pipeline: Arc<VkRTPipeline>,
     rt_globals: Arc<VkDataBuffer<RtGlobalUBO>>,
     tlas: ArcMutex<VkTlas>,
-
-    models: HashMap<Resource<Model>, Vec<VkMesh>>,
-    textures: HashMap<Resource<Texture>, VkTexture>,
-    samplers: HashMap<u32, VkSampler>,
+    /// Rebuilt together with the tlas instances, None without any
+    rt_objects: Option<VkDataBuffer<ObjDesc>>,
+    /// Textures of every model in the tlas, `ObjDesc::texture_offset` points at the first one of a model
+    rt_textures: Vec<MaterialTexture>,
+
+    mesh_arena: ArcMutex<VkMeshArena>,
+    vertex_layout: VertexLayout,
+    gpu_driven: Option<GpuDrivenPass>,
+
+    models: ResidencyCache<Model, ResidentModel>,
+    textures: ResidencyCache<Texture, VkTexture>,
+    samplers: HashMap<TextureSampler, VkSampler>,
+    frame_index: u64,
+    residency_timer: Timer,
+    residency_grace_period: f32,
+    vram_budget: Option<u64>,
 
     cameras: Vec<RenderCamera>,
-    dynamic_models: Vec<DynamicRenderModel>
+    static_models: Vec<StaticRenderModel>,
+    dynamic_models: Vec<DynamicRenderModel>,
+    /// Built on the first raycast against a model, `None` for meshes that are not triangles
+    mesh_bvhs: HashMap<Resource<Model>, Vec<Option<Arc<MeshBvh>>>>,
+    /// One instance per mesh of every model, with the model and mesh it came from
+    scene_bvh: SceneBvh,
+    /// Model and mesh index of every bvh instance, static models come first and are followed by the dynamic ones.
+    /// Indices stay valid because every change to the model lists rebuilds the bvh
+    scene_bvh_instances: Vec<(usize, usize)>,
+    scene_bvh_dirty: bool,
+
+    lod_error_threshold: f32,
+    lod_hysteresis: f32,
+
+    tlas_dirty: bool,
+    tlas_lods_dirty: bool,
+    static_instance_count: usize
 }
 
 impl Renderer {
@@ -115,7 +273,7 @@ impl Renderer {
         let present_render_pass;
         let descriptor_layout;
         let rt_desc_layout;
-        let pipeline;
+        let pipelines;
         {
             let mut swapchain = swapchain.as_mut();
 
@@ -166,24 +324,14 @@ impl Renderer {
                     p_immutable_samplers: std::ptr::null(),
                 }
             ]);
-            
-            let push_constants = vec![
-                vk::PushConstantRange {
-                    stage_flags: vk::ShaderStageFlags::VERTEX,
-                    offset: 0,
-                    size: std::mem::size_of::<MVP>() as u32
-                }
-            ];
 
-            pipeline = VkGraphicsPipeline::new::<VkVertex>(
+
+            pipelines = Self::create_pipelines(
                 device.clone(),
                 swapchain.get_extent(),
                 &render_pass,
-                &vec![&descriptor_layout],
-                &push_constants,
-                &vec![String::from("shader.vert"), String::from("shader.frag")],
-                vk::CullModeFlags::BACK,
-                vk::TRUE
+                &descriptor_layout,
+                VertexLayout::Full
             );
 
             rt_desc_layout = VkDescriptorSetLayout::new(device.clone(), &vec![
@@ -218,7 +366,7 @@ impl Renderer {
                 vk::DescriptorSetLayoutBinding {
                     binding: 4,
                     descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
-                    descriptor_count: 1, // MAX TEXTURE COUNT
+                    descriptor_count: MAX_RT_TEXTURES as u32,
                     stage_flags: vk::ShaderStageFlags::CLOSEST_HIT_KHR,
                     p_immutable_samplers: std::ptr::null(),
                 }
@@ -255,6 +403,7 @@ impl Renderer {
             true
         ));
         let tlas = VkTlas::new();
+        let mesh_arena = VkMeshArena::new(&mut app.as_mut(), VertexLayout::Full.get_stride());
 
         let imgui = VkImGui::new(
             app.clone(),
@@ -267,30 +416,109 @@ impl Renderer {
             render_img: render_img,
             render_pass: render_pass,
             present_render_pass: present_render_pass,
-            pipeline: pipeline,
+            pipelines: pipelines,
             descriptor_layout: descriptor_layout,
 
             rt_desc_layout: rt_desc_layout,
             rt_pipeline: rt_pipeline,
             rt_globals: rt_globals,
             tlas: tlas,
+            rt_objects: None,
+            rt_textures: Vec::new(),
+
+            mesh_arena: mesh_arena,
+            vertex_layout: VertexLayout::Full,
+            gpu_driven: None,
 
-            models: HashMap::new(),
-            textures: HashMap::new(),
+            models: ResidencyCache::new(),
+            textures: ResidencyCache::new(),
             samplers: HashMap::new(),
+            frame_index: 0,
+            residency_timer: Timer::new(),
+            residency_grace_period: 5.0,
+            vram_budget: None,
 
             cameras: Vec::new(),
-            dynamic_models: Vec::new()
+            static_models: Vec::new(),
+            dynamic_models: Vec::new(),
+            mesh_bvhs: HashMap::new(),
+            scene_bvh: SceneBvh::new(),
+            scene_bvh_instances: Vec::new(),
+            scene_bvh_dirty: true,
+
+            lod_error_threshold: 1.0,
+            lod_hysteresis: 0.1,
+
+            tlas_dirty: true,
+            tlas_lods_dirty: false,
+            static_instance_count: 0
         })
     }
 
+    /// One raster pipeline per mesh kind, reading vertices in `vertex_layout`
+    fn create_pipelines(
+        device: Arc<VkLogicalDevice>,
+        extent: &vk::Extent2D,
+        render_pass: &VkRenderPass,
+        descriptor_layout: &VkDescriptorSetLayout,
+        vertex_layout: VertexLayout
+    ) -> HashMap<MeshKind, Arc<VkGraphicsPipeline>> {
+        let push_constants = vec![
+            vk::PushConstantRange {
+                stage_flags: vk::ShaderStageFlags::VERTEX,
+                offset: 0,
+                size: std::mem::size_of::<MVP>() as u32
+            }
+        ];
+        let shaders = vec![String::from("shader.vert"), String::from("shader.frag")];
+
+        let mut pipelines = HashMap::new();
+        for kind in [MeshKind::Triangles, MeshKind::Lines, MeshKind::LineStrip, MeshKind::Points] {
+            let cull_mode = match kind {
+                MeshKind::Triangles => vk::CullModeFlags::BACK,
+                _ => vk::CullModeFlags::NONE
+            };
+
+            let pipeline = match vertex_layout {
+                VertexLayout::Full => VkGraphicsPipeline::new::<VkVertex>(
+                    device.clone(),
+                    extent,
+                    render_pass,
+                    &vec![descriptor_layout],
+                    &push_constants,
+                    &shaders,
+                    VkMesh::topology(kind),
+                    cull_mode,
+                    vk::TRUE
+                ),
+                VertexLayout::Compact => VkGraphicsPipeline::new::<VkCompactVertex>(
+                    device.clone(),
+                    extent,
+                    render_pass,
+                    &vec![descriptor_layout],
+                    &push_constants,
+                    &shaders,
+                    VkMesh::topology(kind),
+                    cull_mode,
+                    vk::TRUE
+                )
+            };
+            pipelines.insert(kind, pipeline);
+        }
+
+        pipelines
+    }
+
     pub(crate) fn update(&mut self) {
         self.app.as_mut().update();
 
         self.remove_unused_resources();
+        self.update_residency();
+        self.update_lods();
 
-        self.rebuild_tlas();
+        self.update_tlas();
         self.render();
+        self.frame_index += 1;
     }
 
     pub(crate) fn imgui_frame(&mut self) -> &mut ImGuiUI {
@@ -309,7 +537,18 @@ impl Renderer {
                     indices_to_remove.push(i);
                 }
             }
-            vec_remove_multiple(&mut self.dynamic_models, &mut indices_to_remove);
+            vec_remove_multiple(&mut self.cameras, &mut indices_to_remove);
+        }
+        { // Static models
+            let mut indices_to_remove = Vec::new();
+            for (i, static_model) in self.static_models.iter().enumerate() {
+                if !static_model.is_active() {
+                    indices_to_remove.push(i);
+                }
+            }
+            self.tlas_dirty |= !indices_to_remove.is_empty();
+            self.scene_bvh_dirty |= !indices_to_remove.is_empty();
+            vec_remove_multiple(&mut self.static_models, &mut indices_to_remove);
         }
         { // Dynamic models
             let mut indices_to_remove = Vec::new();
@@ -318,30 +557,317 @@ impl Renderer {
                     indices_to_remove.push(i);
                 }
             }
+            self.tlas_dirty |= !indices_to_remove.is_empty();
+            self.scene_bvh_dirty |= !indices_to_remove.is_empty();
             vec_remove_multiple(&mut self.dynamic_models, &mut indices_to_remove);
         }
     }
 
-    fn rebuild_tlas(&mut self) {
-        let mut blas_instances = Vec::new();
+    fn update_residency(&mut self) {
+        let frame = self.frame_index;
+        let time = self.residency_timer.elapsed();
 
-        let mut custom_idx = 0;
+        let used_models: HashSet<Resource<Model>> = self.static_models.iter()
+            .map(|static_model| static_model.model_resource.clone())
+            .chain(self.dynamic_models.iter().map(|dynamic_model| dynamic_model.model_resource.clone()))
+            .collect();
+
+        self.mesh_bvhs.retain(|model_resource, _| used_models.contains(model_resource));
+
+        for model_resource in used_models.iter() {
+            self.models.touch(model_resource, frame, time);
+            for material in &model_resource.as_ref().materials {
+                for material_texture in material.as_ref().textures() {
+                    self.textures.touch(&material_texture.texture, frame, time);
+                }
+            }
+        }
+
+        let resident_memory = self.models.memory_size() + self.textures.memory_size();
+        let excess = self.vram_budget.map(|budget| resident_memory.saturating_sub(budget)).unwrap_or(0);
+        let freed = self.models.evict(frame, time, self.residency_grace_period, excess);
+        self.textures.evict(frame, time, self.residency_grace_period, excess.saturating_sub(freed));
+
+        // The swapchain waited on the fence of every frame up to this one before it started recording the last frame
+        if let Some(completed_frame) = frame.checked_sub(MAX_FRAMES_IN_FLIGHT as u64 + 1) {
+            self.models.collect_garbage(completed_frame);
+            self.textures.collect_garbage(completed_frame);
+        }
+    }
+
+    /// Picks the coarsest level of detail of every mesh whose error stays within `lod_error_threshold` pixels on screen
+    fn update_lods(&mut self) {
+        let main_camera = match self.cameras.iter().find(|camera| camera.properties.as_ref().main) {
+            Some(camera) => camera.properties.clone(),
+            None => return
+        };
+        let (view_matrix, proj_matrix, projection) = {
+            let camera = &mut main_camera.as_mut().camera;
+            (*camera.get_view_matrix(), *camera.get_unjittered_proj_matrix(), camera.get_projection())
+        };
+        let viewport_height = match self.app.as_ref().get_swapchain() {
+            Some(swapchain) => swapchain.as_ref().get_extent().height as f32,
+            None => return
+        };
+
+        // Orthographic sizes on screen do not depend on the distance to the camera
+        let camera_position = match projection {
+            Projection::Orthographic { .. } => None,
+            _ => Some(view_matrix.invert().unwrap_or(Matrix4::identity()).w.truncate())
+        };
+        // Pixels covered by one unit at a distance of one unit
+        let pixels_per_unit = proj_matrix.y.y.abs() * viewport_height * 0.5;
+
+        for static_model in self.static_models.iter_mut() {
+            let model_matrix = *static_model.properties.as_mut().transform.get_matrix(false);
+            let changed = Self::select_lods(
+                &static_model.model_resource.as_ref().meshes,
+                &model_matrix,
+                camera_position,
+                pixels_per_unit,
+                self.lod_error_threshold,
+                self.lod_hysteresis,
+                &mut static_model.lods
+            );
+
+            // Static instances are only written by a full rebuild
+            self.tlas_dirty |= changed;
+        }
+
+        for dynamic_model in self.dynamic_models.iter_mut() {
+            let model_matrix = *dynamic_model.properties.as_mut().transform.get_matrix(false);
+            let vk_meshes = &self.models.get(&dynamic_model.model_resource).unwrap().meshes;
+            let has_blases = |lods: &Vec<usize>| -> Vec<bool> {
+                vk_meshes.iter().zip(lods.iter()).map(|(vk_mesh, lod)| vk_mesh.get_blas(*lod).is_some()).collect()
+            };
+            let previous_blases = has_blases(&dynamic_model.lods);
+            let changed = Self::select_lods(
+                &dynamic_model.model_resource.as_ref().meshes,
+                &model_matrix,
+                camera_position,
+                pixels_per_unit,
+                self.lod_error_threshold,
+                self.lod_hysteresis,
+                &mut dynamic_model.lods
+            );
+
+            // Levels without a blas remove the instance, which shifts the ones after it
+            match changed && has_blases(&dynamic_model.lods) != previous_blases {
+                true => self.tlas_dirty = true,
+                false => self.tlas_lods_dirty |= changed
+            }
+        }
+    }
+
+    /// Updates the level of detail of every mesh of a model instance, returns whether any changed
+    fn select_lods(
+        meshes: &Vec<Mesh>,
+        model_matrix: &Matrix4<f32>,
+        camera_position: Option<Vector3<f32>>,
+        pixels_per_unit: f32,
+        error_threshold: f32,
+        hysteresis: f32,
+        lods: &mut Vec<usize>
+    ) -> bool {
+        let scale = model_matrix.x.truncate().magnitude()
+            .max(model_matrix.y.truncate().magnitude())
+            .max(model_matrix.z.truncate().magnitude());
+
+        let mut changed = false;
+        for (mesh, lod) in meshes.iter().zip(lods.iter_mut()) {
+            if mesh.lods.is_empty() {
+                continue;
+            }
+
+            let center = (model_matrix * ((mesh.min + mesh.max) * 0.5).extend(1.0)).truncate();
+            let radius = mesh.get_bounds_radius() * scale;
+            let screen_radius = match camera_position.map(|camera_position| (center - camera_position).magnitude()) {
+                Some(distance) if distance <= radius => f32::INFINITY,
+                Some(distance) => radius * pixels_per_unit / distance,
+                None => radius * pixels_per_unit
+            };
+
+            // Relative errors never decrease along the chain, so the last acceptable level is the coarsest one
+            let coarsest_lod = |screen_radius: f32| {
+                mesh.lods.iter()
+                    .rposition(|mesh_lod| mesh_lod.error * screen_radius <= error_threshold)
+                    .map(|i| i + 1)
+                    .unwrap_or(0)
+            };
+
+            // Coarser levels are only picked once the mesh is a bit smaller than needed and finer ones once it is a bit larger
+            let coarser_lod = coarsest_lod(screen_radius * (1.0 + hysteresis));
+            let finer_lod = coarsest_lod(screen_radius * (1.0 - hysteresis));
+            let new_lod = if coarser_lod > *lod {
+                coarser_lod
+            } else if finer_lod < *lod {
+                finer_lod
+            } else {
+                *lod
+            };
+
+            changed |= new_lod != *lod;
+            *lod = new_lod;
+        }
+
+        changed
+    }
+
+    fn update_tlas(&mut self) {
+        if self.tlas_dirty {
+            self.rebuild_tlas();
+            self.update_rt_objects();
+            self.tlas_dirty = false;
+            self.tlas_lods_dirty = false;
+            return;
+        }
+
+        // Static instances are never patched, they are laid out in front of the dynamic ones
+        let mut instance_idx = self.static_instance_count;
+        let mut refit = self.tlas_lods_dirty;
+
+        let mut tlas = self.tlas.as_mut();
         for dynamic_model in self.dynamic_models.iter() {
             let mut model_properties = dynamic_model.properties.as_mut();
-            let model_matrix = model_properties.transform.get_matrix(false);
+            let vk_meshes = &self.models.get(&dynamic_model.model_resource).unwrap().meshes;
+            let mesh_count = vk_meshes.iter()
+                .zip(dynamic_model.lods.iter())
+                .filter(|(vk_mesh, lod)| vk_mesh.get_blas(**lod).is_some())
+                .count();
+
+            if self.tlas_lods_dirty {
+                let blases = vk_meshes.iter()
+                    .zip(dynamic_model.lods.iter())
+                    .filter_map(|(vk_mesh, lod)| vk_mesh.get_blas(*lod));
+                for (i, blas) in blases.enumerate() {
+                    tlas.set_instance_blas(instance_idx + i, blas);
+                }
+            }
 
-            let vk_meshes = self.models.get(&dynamic_model.model_resource).unwrap();
-            for (i, mesh) in dynamic_model.model_resource.as_ref().meshes.iter().enumerate() {
-                let blas = vk_meshes[i].get_blas();
+            if model_properties.transform.is_changed() {
+                let model_matrix = *model_properties.transform.get_matrix(false);
+                for i in 0..mesh_count {
+                    tlas.set_instance_transform(instance_idx + i, model_matrix);
+                }
+
+                model_properties.transform.clear_changed();
+                refit = true;
+            }
+
+            instance_idx += mesh_count;
+        }
+
+        if refit {
+            tlas.refit(&mut self.app.as_mut());
+        }
+        drop(tlas);
+
+        // Levels of detail have their own index ranges
+        if self.tlas_lods_dirty {
+            self.update_rt_objects();
+        }
+        self.tlas_lods_dirty = false;
+    }
+
+    /// Writes the `ObjDesc` of every tlas instance, in the order `rebuild_tlas` lays them out
+    fn update_rt_objects(&mut self) {
+        // VERTEX_LAYOUT_FULL and VERTEX_LAYOUT_COMPACT in host.glsl
+        let vertex_layout = match self.vertex_layout {
+            VertexLayout::Full => 0,
+            VertexLayout::Compact => 1
+        };
 
+        let mut objects = Vec::new();
+        let mut texture_offsets: HashMap<*const Model, i32> = HashMap::new();
+        self.rt_textures.clear();
+
+        let instances = self.static_models.iter()
+            .map(|static_model| (&static_model.model_resource, &static_model.lods))
+            .chain(self.dynamic_models.iter().map(|dynamic_model| (&dynamic_model.model_resource, &dynamic_model.lods)));
+        let arena = self.mesh_arena.as_ref();
+        for (model_resource, lods) in instances {
+            let resident_model = self.models.get(model_resource).unwrap();
+            let texture_offset = *texture_offsets.entry(model_resource.as_ptr()).or_insert_with(|| {
+                let texture_offset = self.rt_textures.len() as i32;
+                self.rt_textures.extend(resident_model.textures.iter().cloned());
+                assert!(self.rt_textures.len() <= MAX_RT_TEXTURES, "Failed to update ray tracing objects. (More than {} textures)", MAX_RT_TEXTURES);
+                texture_offset
+            });
+
+            let model = model_resource.as_ref();
+            for ((mesh, vk_mesh), lod) in model.meshes.iter().zip(resident_model.meshes.iter()).zip(lods.iter()) {
+                if vk_mesh.get_blas(*lod).is_none() {
+                    continue;
+                }
+
+                let allocation = vk_mesh.get_allocation();
+                let first_index = vk_mesh.get_lod(*lod).first_index - allocation.first_index;
+                objects.push(ObjDesc {
+                    texture_offset: texture_offset,
+                    vertex_layout: vertex_layout,
+                    vertex_address: arena.get_vertex_address(allocation),
+                    index_address: arena.get_index_address(allocation) + first_index as u64 * 4,
+                    material_address: resident_model.materials.get_buffer().get_device_address(),
+                    material_index: mesh.material_idx as i32,
+                    _padding: 0
+                });
+            }
+        }
+        drop(arena);
+
+        self.rt_objects = match objects.is_empty() {
+            true => None,
+            false => Some(VkDataBuffer::new(
+                "RT Objects",
+                &mut self.app.as_mut(),
+                &objects,
+                vk::BufferUsageFlags::STORAGE_BUFFER,
+                vk::MemoryPropertyFlags::DEVICE_LOCAL,
+                false
+            ))
+        };
+    }
+
+    fn rebuild_tlas(&mut self) {
+        let mut blas_instances = Vec::new();
+
+        for static_model in self.static_models.iter() {
+            let mut model_properties = static_model.properties.as_mut();
+            let model_matrix = model_properties.transform.get_matrix(false);
+
+            let vk_meshes = &self.models.get(&static_model.model_resource).unwrap().meshes;
+            let blases = vk_meshes.iter()
+                .zip(static_model.lods.iter())
+                .filter_map(|(vk_mesh, lod)| vk_mesh.get_blas(*lod));
+            for blas in blases {
+                let custom_idx = blas_instances.len() as u32;
                 blas_instances.push(VkBlasInstance::new(
                     *model_matrix,
                     blas,
                     custom_idx,
                     0xFF
                 ));
+            }
+        }
+        self.static_instance_count = blas_instances.len();
 
-                custom_idx += 1;
+        for dynamic_model in self.dynamic_models.iter() {
+            let mut model_properties = dynamic_model.properties.as_mut();
+            let model_matrix = *model_properties.transform.get_matrix(false);
+            model_properties.transform.clear_changed();
+
+            let vk_meshes = &self.models.get(&dynamic_model.model_resource).unwrap().meshes;
+            let blases = vk_meshes.iter()
+                .zip(dynamic_model.lods.iter())
+                .filter_map(|(vk_mesh, lod)| vk_mesh.get_blas(*lod));
+            for blas in blases {
+                let custom_idx = blas_instances.len() as u32;
+                blas_instances.push(VkBlasInstance::new(
+                    model_matrix,
+                    blas,
+                    custom_idx,
+                    0xFF
+                ));
             }
         }
 
@@ -352,7 +878,24 @@ impl Renderer {
         );
     }
 
+    /// Every model instance with its transform and the level of detail of each of its meshes
+    fn render_instances(&self) -> Vec<(Resource<Model>, Matrix4<f32>, Vec<usize>)> {
+        let mut render_instances = Vec::with_capacity(self.static_models.len() + self.dynamic_models.len());
+
+        for static_model in self.static_models.iter() {
+            let mut model_properties = static_model.properties.as_mut();
+            render_instances.push((static_model.model_resource.clone(), *model_properties.transform.get_matrix(false), static_model.lods.clone()));
+        }
+        for dynamic_model in self.dynamic_models.iter() {
+            let mut model_properties = dynamic_model.properties.as_mut();
+            render_instances.push((dynamic_model.model_resource.clone(), *model_properties.transform.get_matrix(false), dynamic_model.lods.clone()));
+        }
+
+        render_instances
+    }
+
     fn render(&mut self) {
+        let render_instances = self.render_instances();
         let mut app = self.app.as_mut();
 
         if let Some(swapchain) = app.get_swapchain() {
@@ -387,6 +930,42 @@ impl Renderer {
             let main_camera = &mut main_camera.as_ref().expect("Failed to find main camera.").as_mut().camera;
             let view_matrix = *main_camera.get_view_matrix();
             let proj_matrix = *main_camera.get_proj_matrix();
+            unsafe {
+                *self.rt_globals.get_data_ptr() = RtGlobalUBO::new(main_camera);
+            }
+            let (clear_depth, depth_compare_op) = match main_camera.is_reversed_z() {
+                true => (0.0, vk::CompareOp::GREATER),
+                false => (1.0, vk::CompareOp::LESS)
+            };
+
+            if let Some(gpu_driven) = self.gpu_driven.as_mut() {
+                let mut instances = Vec::new();
+                for (model_resource, model_matrix, lods) in render_instances.iter() {
+                    let model = model_resource.as_ref();
+                    let vk_meshes = &self.models.get(model_resource).unwrap().meshes;
+                    for (i, mesh) in model.meshes.iter().enumerate() {
+                        if mesh.kind != MeshKind::Triangles {
+                            continue;
+                        }
+
+                        let allocation = vk_meshes[i].get_allocation();
+                        let lod = vk_meshes[i].get_lod(lods[i]);
+
+                        instances.push(GpuInstance {
+                            model: *model_matrix,
+                            bounds_min: mesh.min.extend(1.0),
+                            bounds_max: mesh.max.extend(1.0),
+                            base_color: model.materials[mesh.material_idx].as_ref().base_color_factor,
+                            index_count: lod.index_count,
+                            first_index: lod.first_index,
+                            vertex_offset: allocation.vertex_offset as i32,
+                            _padding: 0
+                        });
+                    }
+                }
+
+                gpu_driven.upload_instances(&mut app, &instances);
+            }
 
             {
                 let cmd_queue = app.get_cmd_queue();
@@ -396,16 +975,33 @@ impl Renderer {
                     cmd_buffer.reset();
                     cmd_buffer.begin(vk::CommandBufferUsageFlags::SIMULTANEOUS_USE);
 
+                    if let Some(gpu_driven) = &self.gpu_driven {
+                        gpu_driven.cull(&mut cmd_buffer, &(proj_matrix * view_matrix));
+                    }
+
                     let swapchain = swapchain.as_ref();
                     cmd_buffer.set_viewport(swapchain.get_extent());
-                    cmd_buffer.begin_render_pass(&self.render_pass, &swapchain);
+                    cmd_buffer.begin_render_pass(&self.render_pass, &swapchain, clear_depth);
+                    cmd_buffer.set_depth_compare_op(depth_compare_op);
 
-                    if true {
-                        cmd_buffer.bind_graphics_pipeline(self.pipeline.clone());
+                    if let Some(gpu_driven) = &self.gpu_driven {
+                        gpu_driven.draw(&mut cmd_buffer, &self.mesh_arena, &(proj_matrix * view_matrix));
+                    }
+
+                    let mut bound_kind = None;
+                    for (model_resource, model_matrix, lods) in render_instances.iter() {
+                        let vk_meshes = &self.models.get(model_resource).unwrap().meshes;
+                        for (i, mesh) in model_resource.as_ref().meshes.iter().enumerate() {
+                            // The gpu driven pass only draws triangles, lines and points still go through here
+                            if self.gpu_driven.is_some() && mesh.kind == MeshKind::Triangles {
+                                continue;
+                            }
+
+                            if bound_kind != Some(mesh.kind) {
+                                cmd_buffer.bind_graphics_pipeline(self.pipelines.get(&mesh.kind).unwrap().clone());
+                                bound_kind = Some(mesh.kind);
+                            }
 
-                        for dynamic_model in self.dynamic_models.iter() {
-                            let mut model_properties = dynamic_model.properties.as_mut();
-                            let model_matrix = model_properties.transform.get_matrix(false);
                             cmd_buffer.push_constant(
                                 &MVP {
                                     mvp: proj_matrix * view_matrix * model_matrix
@@ -413,30 +1009,25 @@ impl Renderer {
                                 vk::ShaderStageFlags::VERTEX
                             );
 
-                            let vk_meshes = self.models.get(&dynamic_model.model_resource).unwrap();
-                            for (i, mesh) in dynamic_model.model_resource.as_ref().meshes.iter().enumerate() {
-                                let material = dynamic_model.model_resource.as_ref().materials[mesh.material_idx].clone();
-                                let material = material.as_ref();
+                            let material = model_resource.as_ref().materials[mesh.material_idx].clone();
+                            let material = material.as_ref();
 
-                                cmd_buffer.set_desc_layout(0, self.descriptor_layout.clone());
+                            cmd_buffer.set_desc_layout(0, self.descriptor_layout.clone());
 
-                                let base_color_texture = self.textures.get_mut(&material.base_color_texture);
-                                if let Some(texture) = base_color_texture {
-                                    let sampler = self.samplers.get(&texture.mip_levels()).unwrap();
-                                    cmd_buffer.set_desc_texture(0, 0,
-                                        sampler,
-                                        texture,
-                                        vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL
-                                    );
-                                }
+                            let base_color_texture = self.textures.get_mut(&material.base_color_texture.texture);
+                            if let Some(texture) = base_color_texture {
+                                let sampler = self.samplers.get(&material.base_color_texture.sampler).unwrap();
+                                cmd_buffer.set_desc_texture(0, 0,
+                                    sampler,
+                                    texture,
+                                    vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL
+                                );
+                            }
 
-                                cmd_buffer.bind_desc_sets();
+                            cmd_buffer.bind_desc_sets();
 
-                                vk_meshes[i].draw_cmds(&mut cmd_buffer);
-                            }
+                            vk_meshes[i].draw_cmds(&mut cmd_buffer, lods[i]);
                         }
-                    } else {
-
                     }
 
                     cmd_buffer.end_render_pass();
@@ -496,6 +1087,138 @@ impl Renderer {
         }
     }
 
+    pub fn get_stats(&self) -> RenderStats {
+        let mut stats = RenderStats::default();
+        stats.resident_mesh_count = self.models.values().map(|resident_model| resident_model.meshes.len()).sum();
+        stats.resident_texture_count = self.textures.len();
+        stats.resident_memory = self.models.memory_size() + self.textures.memory_size();
+
+        let instances = self.static_models.iter()
+            .map(|static_model| (&static_model.model_resource, &static_model.lods))
+            .chain(self.dynamic_models.iter().map(|dynamic_model| (&dynamic_model.model_resource, &dynamic_model.lods)));
+        for (model_resource, lods) in instances {
+            for (mesh, lod) in model_resource.as_ref().meshes.iter().zip(lods.iter()) {
+                if mesh.kind == MeshKind::Triangles {
+                    let index_count = match lod {
+                        0 => mesh.indices.len(),
+                        lod => mesh.lods[lod - 1].indices.len()
+                    };
+                    stats.triangle_count += index_count as u64 / 3;
+                }
+            }
+        }
+
+        for resident_model in self.models.values() {
+            for blas in resident_model.meshes.iter().flat_map(|vk_mesh| vk_mesh.get_blases()) {
+                let blas = blas.as_ref();
+
+                stats.blas_count += 1;
+                stats.blas_memory += blas.get_memory_size();
+                stats.blas_memory_saved += blas.get_memory_saved();
+            }
+        }
+
+        stats
+    }
+
+    /// Seconds an unused model or texture stays on the gpu before it is released
+    pub fn set_residency_grace_period(&mut self, seconds: f32) {
+        self.residency_grace_period = seconds;
+    }
+
+    pub fn get_residency_grace_period(&self) -> f32 {
+        self.residency_grace_period
+    }
+
+    /// Device memory budget in bytes for meshes, blases and textures.
+    /// Unused entries are released before their grace period ends, least recently used first, to stay within it.
+    pub fn set_vram_budget(&mut self, budget: Option<u64>) {
+        self.vram_budget = budget;
+    }
+
+    pub fn get_vram_budget(&self) -> Option<u64> {
+        self.vram_budget
+    }
+
+    /// Screen space error in pixels a level of detail may have before a finer one is picked
+    pub fn set_lod_error_threshold(&mut self, pixels: f32) {
+        self.lod_error_threshold = pixels;
+    }
+
+    pub fn get_lod_error_threshold(&self) -> f32 {
+        self.lod_error_threshold
+    }
+
+    /// Fraction of the screen size a mesh has to move past a switching point before its level of detail changes
+    pub fn set_lod_hysteresis(&mut self, hysteresis: f32) {
+        self.lod_hysteresis = hysteresis;
+    }
+
+    pub fn get_lod_hysteresis(&self) -> f32 {
+        self.lod_hysteresis
+    }
+
+    pub fn get_vertex_layout(&self) -> VertexLayout {
+        self.vertex_layout
+    }
+
+    /// Switches the layout meshes are stored with on the gpu, every resident model is uploaded again
+    pub fn set_vertex_layout(&mut self, vertex_layout: VertexLayout) {
+        if self.vertex_layout == vertex_layout {
+            return;
+        }
+
+        self.wait_idle();
+        self.vertex_layout = vertex_layout;
+
+        {
+            let mut app = self.app.as_mut();
+            let extent = *app.get_swapchain().unwrap().as_ref().get_extent();
+            self.pipelines = Self::create_pipelines(
+                app.get_device(),
+                &extent,
+                &self.render_pass,
+                &self.descriptor_layout,
+                vertex_layout
+            );
+
+            if self.gpu_driven.is_some() {
+                self.gpu_driven = Some(GpuDrivenPass::new(&mut app, &self.render_pass, vertex_layout));
+            }
+
+            self.mesh_arena = VkMeshArena::new(&mut app, vertex_layout.get_stride());
+        }
+
+        // The old meshes live in the old arena, which is released together with the last of them
+        self.models.retire_all(self.frame_index);
+        let model_resources: Vec<Resource<Model>> = self.static_models.iter()
+            .map(|static_model| static_model.model_resource.clone())
+            .chain(self.dynamic_models.iter().map(|dynamic_model| dynamic_model.model_resource.clone()))
+            .collect();
+        for model_resource in model_resources.iter() {
+            self.store_model(model_resource);
+        }
+
+        self.tlas_dirty = true;
+    }
+
+    pub fn is_gpu_driven(&self) -> bool {
+        self.gpu_driven.is_some()
+    }
+
+    pub fn set_gpu_driven(&mut self, enabled: bool) {
+        if enabled && self.gpu_driven.is_none() {
+            self.gpu_driven = Some(GpuDrivenPass::new(
+                &mut self.app.as_mut(),
+                &self.render_pass,
+                self.vertex_layout
+            ));
+        } else if !enabled && self.gpu_driven.is_some() {
+            self.wait_idle();
+            self.gpu_driven = None;
+        }
+    }
+
     pub fn create_camera(&mut self) -> RcCell<RenderCameraProperties> {
         let properties = RcCell::new(RenderCameraProperties {
             camera: Camera::new(),
@@ -509,60 +1232,256 @@ impl Renderer {
         properties
     }
 
-    fn store_texture(&mut self, texture_resource: Resource<Texture>) {
-        if self.textures.get(&texture_resource).is_none() {
+    /// Focuses the physical camera on the surface under a pixel of the window, returns the new focus distance.
+    /// None if nothing was hit or the camera has no physical settings
+    pub fn focus_at(&mut self, camera: &RcCell<RenderCameraProperties>, screen_pos: Vector2<f32>) -> Option<f32> {
+        let (origin, direction, view_matrix) = {
+            let camera = &mut camera.as_mut().camera;
+            let (origin, direction) = camera.get_screen_ray(screen_pos);
+            (origin, direction, *camera.get_view_matrix())
+        };
+
+        let hit = self.raycast(origin, direction)?;
+
+        // The focus plane is perpendicular to the view direction, so the depth of the hit is used rather than its distance
+        let focus_distance = -(view_matrix * hit.position.extend(1.0)).z;
+        if !camera.as_mut().camera.set_focus_distance(focus_distance) {
+            return None;
+        }
+
+        Some(focus_distance)
+    }
+
+    /// The model under a pixel of the window, seen through the main camera
+    pub fn pick(&mut self, screen_pos: Vector2<f32>) -> Option<RaycastHit> {
+        let (origin, direction) = {
+            let main_camera = self.cameras.iter().find(|camera| camera.properties.as_ref().main)?;
+            let camera = &mut main_camera.properties.as_mut().camera;
+            camera.get_screen_ray(screen_pos)
+        };
+
+        self.raycast(origin, direction)
+    }
+
+    /// Closest triangle of any model along a ray. Meshes are always tested at full detail,
+    /// whichever level of detail is rendered
+    pub fn raycast(&mut self, origin: Vector3<f32>, direction: Vector3<f32>) -> Option<RaycastHit> {
+        let direction = direction.normalize();
+
+        // Models dropped since the last frame can't be hit anymore
+        self.remove_unused_resources();
+        self.update_scene_bvh();
+        let hit = self.scene_bvh.intersect_ray(&Ray::new(origin, direction), f32::INFINITY)?;
+
+        let (model_idx, mesh_idx) = self.scene_bvh_instances[hit.instance];
+        let (model, model_resource) = self.get_render_model(model_idx);
+        let mesh = &model_resource.as_ref().meshes[mesh_idx];
+
+        let corners = [0, 1, 2].map(|corner| &mesh.vertices[mesh.indices[hit.hit.primitive * 3 + corner] as usize]);
+        let weights = [1.0 - hit.hit.barycentrics.x - hit.hit.barycentrics.y, hit.hit.barycentrics.x, hit.hit.barycentrics.y];
+        let mut normal = corners[0].normal * weights[0] + corners[1].normal * weights[1] + corners[2].normal * weights[2];
+        if normal.magnitude2() <= f32::EPSILON {
+            normal = (corners[1].position - corners[0].position).cross(corners[2].position - corners[0].position);
+        }
+        let normal_matrix = *model.get_transform().get_normal_matrix();
+
+        Some(RaycastHit {
+            model: model,
+            mesh_idx: mesh_idx,
+            primitive: hit.hit.primitive,
+            barycentrics: hit.hit.barycentrics,
+            distance: hit.hit.distance,
+            position: origin + direction * hit.hit.distance,
+            normal: (normal_matrix * normal.extend(0.0)).truncate().normalize()
+        })
+    }
+
+    /// Rebuilds the bvh of the scene after models were created or dropped, otherwise refits it around moved dynamic models
+    fn update_scene_bvh(&mut self) {
+        if self.scene_bvh_dirty {
+            self.scene_bvh.clear();
+            self.scene_bvh_instances.clear();
+            for model_idx in 0..self.static_models.len() + self.dynamic_models.len() {
+                let (model, model_resource) = self.get_render_model(model_idx);
+                let model_matrix = *model.get_transform().get_matrix(false);
+                let mesh_bvhs = self.mesh_bvhs.entry(model_resource.clone()).or_insert_with(|| {
+                    model_resource.as_ref().meshes.iter()
+                        .map(|mesh| match mesh.kind {
+                            MeshKind::Triangles => Some(Arc::new(MeshBvh::from_mesh(mesh))),
+                            _ => None
+                        })
+                        .collect()
+                });
+
+                for (mesh_idx, mesh_bvh) in mesh_bvhs.iter().enumerate() {
+                    if let Some(mesh_bvh) = mesh_bvh {
+                        self.scene_bvh.add_instance(mesh_bvh.clone(), &model_matrix);
+                        self.scene_bvh_instances.push((model_idx, mesh_idx));
+                    }
+                }
+            }
+            self.scene_bvh_dirty = false;
+        } else {
+            // Static models never move
+            for (instance, (model_idx, _)) in self.scene_bvh_instances.iter().enumerate() {
+                if let Some(dynamic_model) = model_idx.checked_sub(self.static_models.len()).map(|idx| &self.dynamic_models[idx]) {
+                    let model_matrix = *dynamic_model.properties.as_mut().transform.get_matrix(false);
+                    if model_matrix != *self.scene_bvh.get_matrix(instance) {
+                        self.scene_bvh.set_matrix(instance, &model_matrix);
+                    }
+                }
+            }
+        }
+
+        self.scene_bvh.update();
+    }
+
+    /// Handle and model of the static models followed by the dynamic ones
+    fn get_render_model(&self, model_idx: usize) -> (RenderModel, Resource<Model>) {
+        match model_idx.checked_sub(self.static_models.len()) {
+            None => {
+                let static_model = &self.static_models[model_idx];
+                (RenderModel::Static(static_model.properties.clone()), static_model.model_resource.clone())
+            },
+            Some(dynamic_idx) => {
+                let dynamic_model = &self.dynamic_models[dynamic_idx];
+                (RenderModel::Dynamic(dynamic_model.properties.clone()), dynamic_model.model_resource.clone())
+            }
+        }
+    }
+
+    fn store_texture(&mut self, material_texture: &MaterialTexture) {
+        if material_texture.is_empty() {
+            return;
+        }
+
+        if !self.textures.contains(&material_texture.texture) {
             let texture = VkTexture::new(
                 self.app.clone(),
-                texture_resource.clone()
+                material_texture.texture.clone()
             );
 
-            if self.samplers.get(&texture.mip_levels()).is_none() {
-                self.samplers.insert(
-                    texture.mip_levels(),
-                    VkSampler::new(
-                        self.app.as_ref().get_device(),
-                        &texture
-                    )
-                );
-            }
-
+            let memory_size = texture.get_memory_size();
             self.textures.insert(
-                texture_resource,
-                texture
+                &material_texture.texture,
+                texture,
+                memory_size,
+                self.frame_index,
+                self.residency_timer.elapsed()
             );
         }
-    }
 
-    pub fn create_dynamic_model(&mut self, model_resource: Resource<Model>) -> RcCell<DynamicRenderModelProperties> {
-        let properties = RcCell::new(DynamicRenderModelProperties {
-            transform: Transform::new()
-        });
+        if self.samplers.get(&material_texture.sampler).is_none() {
+            self.samplers.insert(
+                material_texture.sampler,
+                VkSampler::new(
+                    self.app.as_ref().get_device(),
+                    &material_texture.sampler
+                )
+            );
+        }
+    }
 
-        if self.models.get(&model_resource).is_none() {
+    fn store_model(&mut self, model_resource: &Resource<Model>) {
+        if !self.models.contains(model_resource) {
             let mut meshes = Vec::new();
             for mesh in model_resource.as_ref().meshes.iter() {
                 meshes.push(VkMesh::new(
                     &mut self.app.as_mut(),
-                    &mesh.vertices,
-                    &mesh.indices
+                    &self.mesh_arena,
+                    mesh,
+                    self.vertex_layout
                 ));
             }
-            self.models.insert(model_resource.clone(), meshes);
+
+            // Every distinct texture and sampler pair of the model is stored once
+            let same_texture = |a: &MaterialTexture, b: &MaterialTexture| a.texture == b.texture && a.sampler == b.sampler;
+            let mut textures: Vec<MaterialTexture> = Vec::new();
+            for material in &model_resource.as_ref().materials {
+                for material_texture in material.as_ref().textures() {
+                    if !material_texture.is_empty() && !textures.iter().any(|texture| same_texture(texture, material_texture)) {
+                        textures.push(material_texture.clone());
+                    }
+                }
+            }
+            let texture_index = |material_texture: &MaterialTexture| {
+                match material_texture.is_empty() {
+                    true => -1,
+                    false => textures.iter().position(|texture| same_texture(texture, material_texture)).unwrap() as i32
+                }
+            };
+
+            let mut materials: Vec<MaterialProperties> = model_resource.as_ref().materials.iter()
+                .map(|material| MaterialProperties::new(&material.as_ref(), texture_index))
+                .collect();
+            // Buffers can not be empty
+            if materials.is_empty() {
+                materials.push(MaterialProperties::new(&Material::default(), texture_index));
+            }
+            let materials = VkDataBuffer::new(
+                "Materials",
+                &mut self.app.as_mut(),
+                &materials,
+                vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+                vk::MemoryPropertyFlags::DEVICE_LOCAL,
+                false
+            );
+
+            let memory_size = meshes.iter().map(|vk_mesh| vk_mesh.get_memory_size()).sum::<vk::DeviceSize>()
+                + materials.get_buffer().get_size();
+            let resident_model = ResidentModel {
+                meshes: meshes,
+                materials: materials,
+                textures: textures
+            };
+            self.models.insert(model_resource, resident_model, memory_size, self.frame_index, self.residency_timer.elapsed());
         }
 
         for material in &model_resource.as_ref().materials {
-            self.store_texture(material.as_ref().base_color_texture.clone());
-            self.store_texture(material.as_ref().normal_texture.clone());
-            self.store_texture(material.as_ref().metallic_roughness_texture.clone());
-            self.store_texture(material.as_ref().occlusion_texture.clone());
-            self.store_texture(material.as_ref().emissive_texture.clone());
+            for material_texture in material.as_ref().textures() {
+                self.store_texture(material_texture);
+            }
         }
+    }
+
+    /// Creates a model that is placed once and never moves.
+    /// Its instances are only touched when the tlas is fully rebuilt.
+    pub fn create_static_model(&mut self, model_resource: Resource<Model>, transform: Transform) -> RcCell<StaticRenderModelProperties> {
+        let properties = RcCell::new(StaticRenderModelProperties {
+            transform: transform
+        });
+
+        self.store_model(&model_resource);
+        let mesh_count = model_resource.as_ref().meshes.len();
+
+        let static_render_model = StaticRenderModel {
+            lods: vec![0; mesh_count],
+            model_resource: model_resource,
+            properties: properties.clone()
+        };
+        self.static_models.push(static_render_model);
+        self.tlas_dirty = true;
+        self.scene_bvh_dirty = true;
+
+        properties
+    }
+
+    pub fn create_dynamic_model(&mut self, model_resource: Resource<Model>) -> RcCell<DynamicRenderModelProperties> {
+        let properties = RcCell::new(DynamicRenderModelProperties {
+            transform: Transform::new()
+        });
+
+        self.store_model(&model_resource);
+        let mesh_count = model_resource.as_ref().meshes.len();
 
         let dynamic_render_model = DynamicRenderModel {
+            lods: vec![0; mesh_count],
             model_resource: model_resource,
             properties: properties.clone()
         };
         self.dynamic_models.push(dynamic_render_model);
+        self.tlas_dirty = true;
+        self.scene_bvh_dirty = true;
 
         properties
     }
@@ -1,3 +1,5 @@
+use cgmath::{Vector2, Vector3};
+
 use crate::resources::{Model, Resource};
 use crate::common::{RcCell};
 
@@ -9,15 +11,71 @@ pub struct DynamicRenderModelProperties {
     pub transform: Transform
 }
 
+#[derive(Debug, Clone, Copy)]
+pub struct StaticRenderModelProperties {
+    pub(super) transform: Transform
+}
+
+impl StaticRenderModelProperties {
+    pub fn get_transform(&self) -> &Transform {
+        &self.transform
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct RenderCameraProperties {
     pub camera: Camera,
     pub main: bool
 }
 
+/// Handle of a model created by the renderer
+#[derive(Clone)]
+pub enum RenderModel {
+    Static(RcCell<StaticRenderModelProperties>),
+    Dynamic(RcCell<DynamicRenderModelProperties>)
+}
+
+impl RenderModel {
+    pub fn get_transform(&self) -> Transform {
+        match self {
+            RenderModel::Static(properties) => properties.as_ref().transform,
+            RenderModel::Dynamic(properties) => properties.as_ref().transform
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct RaycastHit {
+    pub model: RenderModel,
+    pub mesh_idx: usize,
+    /// Index of the triangle in the index buffer of the mesh
+    pub primitive: usize,
+    /// Weights of the second and third vertex of the triangle
+    pub barycentrics: Vector2<f32>,
+    pub distance: f32,
+    pub position: Vector3<f32>,
+    /// Interpolated vertex normal in world space
+    pub normal: Vector3<f32>
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RenderStats {
+    pub blas_count: usize,
+    pub blas_memory: u64,
+    pub blas_memory_saved: u64,
+    pub resident_mesh_count: usize,
+    pub resident_texture_count: usize,
+    /// Device memory of every mesh, blas and texture that is currently resident
+    pub resident_memory: u64,
+    /// Triangles of every model instance at its selected level of detail
+    pub triangle_count: u64
+}
+
 pub(super) struct DynamicRenderModel {
     pub(super) model_resource: Resource<Model>,
-    pub(super) properties: RcCell<DynamicRenderModelProperties>
+    pub(super) properties: RcCell<DynamicRenderModelProperties>,
+    /// Selected level of detail of every mesh
+    pub(super) lods: Vec<usize>
 }
 
 impl DynamicRenderModel {
@@ -26,6 +84,19 @@ impl DynamicRenderModel {
     }
 }
 
+pub(super) struct StaticRenderModel {
+    pub(super) model_resource: Resource<Model>,
+    pub(super) properties: RcCell<StaticRenderModelProperties>,
+    /// Selected level of detail of every mesh
+    pub(super) lods: Vec<usize>
+}
+
+impl StaticRenderModel {
+    pub(super) fn is_active(&self) -> bool {
+        self.properties.strong_count() > 1
+    }
+}
+
 pub(super) struct RenderCamera {
     pub(super) properties: RcCell<RenderCameraProperties>
 }
@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+
+use crate::resources::{Resource, WeakResource};
+
+struct Resident<T, G> {
+    resource: WeakResource<T>,
+    gpu: G,
+    memory_size: u64,
+    last_used_frame: u64,
+    last_used_time: f32
+}
+
+/// Gpu copies of cpu resources, keyed by the cpu resource without keeping it alive.
+/// Evicted entries are retired first and only dropped once every frame that could still be using them has completed.
+pub(super) struct ResidencyCache<T, G> {
+    residents: HashMap<*const T, Resident<T, G>>,
+    retired: Vec<(u64, G)>
+}
+
+impl<T, G> ResidencyCache<T, G> {
+    pub fn new() -> Self {
+        ResidencyCache {
+            residents: HashMap::new(),
+            retired: Vec::new()
+        }
+    }
+
+    fn key(resource: &Resource<T>) -> Option<*const T> {
+        match resource.is_empty() {
+            true => None,
+            false => Some(resource.as_ptr())
+        }
+    }
+
+    pub fn get(&self, resource: &Resource<T>) -> Option<&G> {
+        self.residents.get(&Self::key(resource)?).map(|resident| &resident.gpu)
+    }
+
+    pub fn get_mut(&mut self, resource: &Resource<T>) -> Option<&mut G> {
+        self.residents.get_mut(&Self::key(resource)?).map(|resident| &mut resident.gpu)
+    }
+
+    pub fn contains(&self, resource: &Resource<T>) -> bool {
+        self.get(resource).is_some()
+    }
+
+    pub fn insert(&mut self, resource: &Resource<T>, gpu: G, memory_size: u64, frame: u64, time: f32) {
+        let resident = Resident {
+            resource: resource.downgrade(),
+            gpu: gpu,
+            memory_size: memory_size,
+            last_used_frame: frame,
+            last_used_time: time
+        };
+
+        if let Some(previous) = self.residents.insert(resident.resource.as_ptr(), resident) {
+            self.retired.push((frame, previous.gpu));
+        }
+    }
+
+    /// Marks a resource as used by the frame that is about to be recorded
+    pub fn touch(&mut self, resource: &Resource<T>, frame: u64, time: f32) {
+        if let Some(resident) = Self::key(resource).and_then(|key| self.residents.get_mut(&key)) {
+            resident.last_used_frame = frame;
+            resident.last_used_time = time;
+        }
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &G> {
+        self.residents.values().map(|resident| &resident.gpu)
+    }
+
+    pub fn len(&self) -> usize {
+        self.residents.len()
+    }
+
+    pub fn memory_size(&self) -> u64 {
+        self.residents.values().map(|resident| resident.memory_size).sum()
+    }
+
+    /// Retires entries that are not used by `frame` and whose cpu resource is gone or that were unused for `grace_period` seconds.
+    /// Afterwards the least recently used entries are retired until `excess` bytes are freed. Returns the freed bytes.
+    pub fn evict(&mut self, frame: u64, time: f32, grace_period: f32, excess: u64) -> u64 {
+        let mut freed = 0;
+
+        let expired: Vec<*const T> = self.residents.iter()
+            .filter(|(_, resident)| resident.last_used_frame < frame)
+            .filter(|(_, resident)| !resident.resource.is_alive() || resident.last_used_time + grace_period < time)
+            .map(|(key, _)| *key)
+            .collect();
+        for key in expired {
+            freed += self.retire(key);
+        }
+
+        if freed < excess {
+            let mut candidates: Vec<(u64, *const T)> = self.residents.iter()
+                .filter(|(_, resident)| resident.last_used_frame < frame)
+                .map(|(key, resident)| (resident.last_used_frame, *key))
+                .collect();
+            candidates.sort_by_key(|(last_used_frame, _)| *last_used_frame);
+
+            for (_, key) in candidates {
+                if freed >= excess {
+                    break;
+                }
+                freed += self.retire(key);
+            }
+        }
+
+        freed
+    }
+
+    /// Retires every entry, for when all gpu copies have to be recreated
+    pub fn retire_all(&mut self, frame: u64) {
+        for (_, resident) in self.residents.drain() {
+            self.retired.push((frame, resident.gpu));
+        }
+    }
+
+    fn retire(&mut self, key: *const T) -> u64 {
+        match self.residents.remove(&key) {
+            Some(resident) => {
+                self.retired.push((resident.last_used_frame, resident.gpu));
+                resident.memory_size
+            },
+            None => 0
+        }
+    }
+
+    /// Drops retired entries whose last frame is at or before `completed_frame`
+    pub fn collect_garbage(&mut self, completed_frame: u64) {
+        self.retired.retain(|(last_used_frame, _)| *last_used_frame > completed_frame);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inserted_resources_are_found() {
+        let resource = Resource::new(0u32);
+        let mut cache = ResidencyCache::new();
+        cache.insert(&resource, "gpu", 16, 0, 0.0);
+
+        assert_eq!(cache.get(&resource), Some(&"gpu"));
+        assert!(cache.contains(&resource));
+        assert!(!cache.contains(&Resource::new(0u32)));
+
+        // Replacing an entry retires the previous copy instead of adding a second one
+        cache.insert(&resource, "gpu 2", 16, 1, 0.0);
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.get(&resource), Some(&"gpu 2"));
+    }
+
+    #[test]
+    fn touched_resources_are_kept() {
+        let resource = Resource::new(0u32);
+        let mut cache = ResidencyCache::new();
+        cache.insert(&resource, "gpu", 16, 0, 0.0);
+
+        cache.touch(&resource, 10, 10.0);
+        assert_eq!(cache.evict(11, 12.0, 5.0, 0), 0);
+        assert!(cache.contains(&resource));
+
+        // Unused for longer than the grace period
+        assert_eq!(cache.evict(11, 20.0, 5.0, 0), 16);
+        assert!(!cache.contains(&resource));
+    }
+
+    #[test]
+    fn dropped_resources_are_evicted() {
+        let resource = Resource::new(0u32);
+        let mut cache = ResidencyCache::new();
+        cache.insert(&resource, "gpu", 16, 0, 0.0);
+
+        drop(resource);
+        assert_eq!(cache.evict(1, 0.0, 5.0, 0), 16);
+        assert_eq!(cache.len(), 0);
+    }
+}
\ No newline at end of file
@@ -1,7 +1,7 @@
-use cgmath::{Vector3, Quaternion, Matrix4};
+use cgmath::{Vector3, Quaternion, Matrix, Matrix3, Matrix4, Euler, Rad, InnerSpace, Rotation, ElementWise, VectorSpace};
 use cgmath::prelude::SquareMatrix;
 
-use crate::common::quaternion_to_matrix;
+use crate::common::{quaternion_to_matrix, look_rotation, forward, right, up};
 
 #[derive(Debug, Clone, Copy)]
 pub struct Transform {
@@ -10,8 +10,10 @@ pub struct Transform {
     scale: Vector3<f32>,
 
     model_matrix: Matrix4<f32>,
+    model_matrix_inv: Matrix4<f32>,
     model_matrix_inv_trans: Matrix4<f32>,
-    model_matrix_dirty: bool
+    model_matrix_dirty: bool,
+    changed: bool
 }
 
 impl Transform {
@@ -21,11 +23,35 @@ impl Transform {
             rotation: Quaternion::new(1.0, 0.0, 0.0, 0.0),
             scale: Vector3::new(1.0, 1.0, 1.0),
             model_matrix: SquareMatrix::identity(),
+            model_matrix_inv: SquareMatrix::identity(),
             model_matrix_inv_trans: SquareMatrix::identity(),
-            model_matrix_dirty: true
+            model_matrix_dirty: true,
+            changed: true
         }
     }
 
+    /// Decomposes an affine matrix into translation, rotation and scale. Shear is lost and
+    /// a mirrored matrix ends up with a negative x scale
+    pub fn from_matrix(matrix: &Matrix4<f32>) -> Self {
+        let columns = [matrix.x.truncate(), matrix.y.truncate(), matrix.z.truncate()];
+
+        let mut scale = Vector3::new(columns[0].magnitude(), columns[1].magnitude(), columns[2].magnitude());
+        if columns[0].dot(columns[1].cross(columns[2])) < 0.0 {
+            scale.x = -scale.x;
+        }
+
+        let mut transform = Transform::new();
+        transform.set_translation(&matrix.w.truncate());
+        transform.set_scale(&scale);
+
+        if scale.x != 0.0 && scale.y != 0.0 && scale.z != 0.0 {
+            let rotation_matrix = Matrix3::from_cols(columns[0] / scale.x, columns[1] / scale.y, columns[2] / scale.z);
+            transform.set_rotation(&Quaternion::from(rotation_matrix).normalize());
+        }
+
+        transform
+    }
+
     pub fn get_translation(&self) -> &Vector3<f32> {
         &self.translation
     }
@@ -38,19 +64,43 @@ impl Transform {
         &self.scale
     }
 
+    pub fn get_euler(&self) -> Euler<Rad<f32>> {
+        Euler::from(self.rotation)
+    }
+
+    pub fn get_forward(&self) -> Vector3<f32> {
+        self.rotation.rotate_vector(forward())
+    }
+
+    pub fn get_right(&self) -> Vector3<f32> {
+        self.rotation.rotate_vector(right())
+    }
+
+    pub fn get_up(&self) -> Vector3<f32> {
+        self.rotation.rotate_vector(up())
+    }
+
     pub fn set_translation(&mut self, translation: &Vector3<f32>) {
         self.translation = translation.clone();
         self.model_matrix_dirty = true;
+        self.changed = true;
     }
 
     pub fn set_rotation(&mut self, rotation: &Quaternion<f32>) {
         self.rotation = rotation.clone();
         self.model_matrix_dirty = true;
+        self.changed = true;
     }
 
     pub fn set_scale(&mut self, scale: &Vector3<f32>) {
         self.scale = scale.clone();
         self.model_matrix_dirty = true;
+        self.changed = true;
+    }
+
+    pub fn set_euler<A: Into<Rad<f32>>>(&mut self, euler: Euler<A>) {
+        let euler = Euler::new(euler.x.into(), euler.y.into(), euler.z.into());
+        self.set_rotation(&Quaternion::from(euler));
     }
 
     pub fn translate(&mut self, translation: &Vector3<f32>) {
@@ -62,30 +112,169 @@ impl Transform {
     }
 
     pub fn scale(&mut self, scale: &Vector3<f32>) {
-        self.set_scale(&(self.scale + scale));
+        self.set_scale(&self.scale.mul_element_wise(*scale));
+    }
+
+    /// Rotates so `get_forward` points at `target`
+    pub fn look_at(&mut self, target: &Vector3<f32>, up: &Vector3<f32>) {
+        let rotation = look_rotation(&(target - self.translation), up);
+        self.set_rotation(&rotation);
+    }
+
+    /// Linear interpolation, the rotation is normalized after interpolating linearly which is cheaper than `slerp`
+    pub fn lerp(&self, other: &Transform, amount: f32) -> Transform {
+        let rotation = self.rotation.nlerp(Self::nearest_rotation(&self.rotation, &other.rotation), amount);
+        self.interpolate(other, rotation, amount)
+    }
+
+    /// Like `lerp`, but the rotation moves at a constant angular speed
+    pub fn slerp(&self, other: &Transform, amount: f32) -> Transform {
+        let rotation = self.rotation.slerp(Self::nearest_rotation(&self.rotation, &other.rotation), amount).normalize();
+        self.interpolate(other, rotation, amount)
+    }
+
+    fn interpolate(&self, other: &Transform, rotation: Quaternion<f32>, amount: f32) -> Transform {
+        let mut transform = Transform::new();
+        transform.set_translation(&self.translation.lerp(other.translation, amount));
+        transform.set_rotation(&rotation);
+        transform.set_scale(&self.scale.lerp(other.scale, amount));
+
+        transform
+    }
+
+    /// Both signs describe the same rotation, the one closer to `from` interpolates the short way around
+    fn nearest_rotation(from: &Quaternion<f32>, to: &Quaternion<f32>) -> Quaternion<f32> {
+        match from.dot(*to) < 0.0 {
+            true => -*to,
+            false => *to
+        }
+    }
+
+    /// Whether the transform was modified since the last call to `clear_changed`.
+    pub fn is_changed(&self) -> bool {
+        self.changed
+    }
+
+    pub fn clear_changed(&mut self) {
+        self.changed = false;
     }
 
+    /// The model matrix, or its inverse which is the view matrix of a camera with this transform
     pub fn get_matrix(&mut self, invert: bool) -> &Matrix4<f32> {
         if self.model_matrix_dirty {
-            self.recalculate_matrix(invert);
+            self.recalculate_matrix();
+        }
+
+        match invert {
+            true => &self.model_matrix_inv,
+            false => &self.model_matrix
+        }
+    }
+
+    pub fn get_inverse_matrix(&mut self) -> &Matrix4<f32> {
+        self.get_matrix(true)
+    }
+
+    /// Inverse transpose of the model matrix, transforms normals under non uniform scale
+    pub fn get_normal_matrix(&mut self) -> &Matrix4<f32> {
+        if self.model_matrix_dirty {
+            self.recalculate_matrix();
         }
 
-        &self.model_matrix
+        &self.model_matrix_inv_trans
     }
 
-    fn recalculate_matrix(&mut self, invert: bool) {
-        self.model_matrix = if invert {
-            Matrix4::from_translation(-self.translation)
-            * quaternion_to_matrix(&self.rotation)
-            * Matrix4::from_nonuniform_scale(self.scale.x, self.scale.y, self.scale.z)
+    fn recalculate_matrix(&mut self) {
+        let rotation = quaternion_to_matrix(&self.rotation);
+
+        self.model_matrix = Matrix4::from_translation(self.translation)
+            * rotation
+            * Matrix4::from_nonuniform_scale(self.scale.x, self.scale.y, self.scale.z);
+
+        // Inverting the parts in reverse order avoids a general inverse, a zero scale has none
+        self.model_matrix_inv = if self.scale.x != 0.0 && self.scale.y != 0.0 && self.scale.z != 0.0 {
+            Matrix4::from_nonuniform_scale(1.0 / self.scale.x, 1.0 / self.scale.y, 1.0 / self.scale.z)
+            * rotation.transpose()
+            * Matrix4::from_translation(-self.translation)
         } else {
-            Matrix4::from_translation(self.translation)
-            * quaternion_to_matrix(&self.rotation)
-            * Matrix4::from_nonuniform_scale(self.scale.x, self.scale.y, self.scale.z)
+            Matrix4::identity()
         };
-        self.model_matrix_inv_trans = self.model_matrix.clone()
-            .invert().unwrap();
-        self.model_matrix_inv_trans.transpose_self();
+        self.model_matrix_inv_trans = self.model_matrix_inv.transpose();
         self.model_matrix_dirty = false;
     }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{Deg, Vector4};
+
+    fn assert_matrix_eq(a: &Matrix4<f32>, b: &Matrix4<f32>) {
+        for (column_a, column_b) in [(a.x, b.x), (a.y, b.y), (a.z, b.z), (a.w, b.w)] {
+            assert!((column_a - column_b).magnitude() < 1e-5, "{:?} != {:?}", a, b);
+        }
+    }
+
+    fn test_transform() -> Transform {
+        let mut transform = Transform::new();
+        transform.set_translation(&Vector3::new(1.0, -2.0, 3.0));
+        transform.set_euler(Euler::new(Deg(30.0), Deg(45.0), Deg(-60.0)));
+        transform.set_scale(&Vector3::new(2.0, 0.5, 3.0));
+        transform
+    }
+
+    #[test]
+    fn inverse_undoes_the_model_matrix() {
+        let mut transform = test_transform();
+        let model_matrix = *transform.get_matrix(false);
+        assert_matrix_eq(&(transform.get_inverse_matrix() * model_matrix), &Matrix4::identity());
+        assert_matrix_eq(transform.get_inverse_matrix(), &model_matrix.invert().unwrap());
+    }
+
+    #[test]
+    fn look_at_points_forward_at_the_target() {
+        let mut transform = Transform::new();
+        transform.set_translation(&Vector3::new(1.0, 2.0, 3.0));
+        transform.look_at(&Vector3::new(4.0, 2.0, -1.0), &up());
+
+        assert!((transform.get_forward() - Vector3::new(0.6, 0.0, -0.8)).magnitude() < 1e-5);
+        assert!((transform.get_up() - up()).magnitude() < 1e-5);
+
+        // The view matrix puts the target straight ahead of the camera
+        let target = transform.get_inverse_matrix() * Vector4::new(4.0, 2.0, -1.0, 1.0);
+        assert!((target.truncate() - forward() * 5.0).magnitude() < 1e-5);
+    }
+
+    #[test]
+    fn from_matrix_round_trips() {
+        let mut transform = test_transform();
+        let model_matrix = *transform.get_matrix(false);
+
+        let mut decomposed = Transform::from_matrix(&model_matrix);
+        assert!((decomposed.get_translation() - transform.get_translation()).magnitude() < 1e-5);
+        assert!((decomposed.get_scale() - transform.get_scale()).magnitude() < 1e-5);
+        assert_matrix_eq(decomposed.get_matrix(false), &model_matrix);
+
+        // Mirroring is kept as a negative scale
+        let mirrored = model_matrix * Matrix4::from_nonuniform_scale(1.0, -1.0, 1.0);
+        assert_matrix_eq(Transform::from_matrix(&mirrored).get_matrix(false), &mirrored);
+    }
+
+    #[test]
+    fn slerp_takes_the_short_way_around() {
+        let mut from = Transform::new();
+        from.set_euler(Euler::new(Deg(0.0), Deg(170.0), Deg(0.0)));
+        let mut to = Transform::new();
+        to.set_euler(Euler::new(Deg(0.0), Deg(-170.0), Deg(0.0)));
+        to.set_translation(&Vector3::new(2.0, 0.0, 0.0));
+
+        let halfway = from.slerp(&to, 0.5);
+        assert!((halfway.get_translation() - Vector3::new(1.0, 0.0, 0.0)).magnitude() < 1e-5);
+        // Halfway between 170 and 190 degrees, not between 170 and -170
+        assert!((halfway.get_forward() - Vector3::new(0.0, 0.0, 1.0)).magnitude() < 1e-5);
+
+        assert!((from.slerp(&to, 0.0).get_forward() - from.get_forward()).magnitude() < 1e-5);
+        assert!((from.slerp(&to, 1.0).get_forward() - to.get_forward()).magnitude() < 1e-5);
+    }
 }
\ No newline at end of file
@@ -4,8 +4,6 @@ use crate::graphics::*;
 
 pub struct VkDataBuffer<T> {
     buffer: Arc<VkBuffer>,
-    count: u32,
-    stride: u32,
     dynamic: bool,
     data: *mut T
 }
@@ -19,8 +17,7 @@ impl<T> VkDataBuffer<T> {
         mem_properties: vk::MemoryPropertyFlags,
         dynamic: bool
     ) -> Self {
-        let stride = std::mem::size_of::<T>();
-        let size: u64 = (stride * data.len()) as u64;
+        let size: u64 = (std::mem::size_of::<T>() * data.len()) as u64;
 
         let buffer = if dynamic {
             let buffer = VkBuffer::new(
@@ -89,8 +86,6 @@ impl<T> VkDataBuffer<T> {
 
         VkDataBuffer {
             buffer: Arc::new(buffer),
-            count: data.len() as u32,
-            stride: stride as u32,
             dynamic: dynamic,
             data: data_ptr
         }
@@ -100,23 +95,13 @@ impl<T> VkDataBuffer<T> {
         self.buffer.clone()
     }
 
-    pub fn get_count(&self) -> u32 {
-        self.count
-    }
-
-    pub fn get_stride(&self) -> u32 {
-        self.stride
-    }
-
     pub fn set_data(&mut self, data: &Vec<T>) {
         assert!(self.dynamic, "Failed to set index data. (Not marked as dynamic)");
-        assert!(data.len() * std::mem::size_of::<T>() < self.buffer.get_size() as usize, "Failed to set index data. (Exceeds available memory)");
+        assert!(data.len() * std::mem::size_of::<T>() <= self.buffer.get_size() as usize, "Failed to set index data. (Exceeds available memory)");
 
         unsafe {
             self.data.copy_from_nonoverlapping(data.as_ptr(), data.len());
         }
-
-        self.count = data.len() as u32;
     }
 
     pub fn get_data_ptr(&self) -> *mut T {
@@ -7,6 +7,7 @@ pub struct VkImage {
     image: vk::Image,
     image_view: Option<vk::ImageView>,
     memory: vk::DeviceMemory,
+    memory_size: vk::DeviceSize,
     width: u32, height: u32,
     format: vk::Format,
     sample_count: vk::SampleCountFlags
@@ -84,6 +85,7 @@ impl VkImage {
             image: texture_image,
             image_view: None,
             memory: texture_image_memory,
+            memory_size: image_memory_requirement.size,
             width: width,
             height: height,
             format: format,
@@ -165,6 +167,10 @@ impl VkImage {
     pub fn sample_count(&self) -> vk::SampleCountFlags {
         self.sample_count
     }
+
+    pub fn get_memory_size(&self) -> vk::DeviceSize {
+        self.memory_size
+    }
 }
 
 impl Drop for VkImage {
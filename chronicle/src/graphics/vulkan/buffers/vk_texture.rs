@@ -1,11 +1,10 @@
 use ash::vk;
 
 use crate::graphics::*;
-use crate::resources::Texture;
+use crate::resources::{Texture, TextureFormat, ColorSpace};
 
 pub struct VkTexture {
-    image: VkImage,
-    mip_levels: u32
+    image: VkImage
 }
 
 impl VkTexture {
@@ -15,12 +14,26 @@ impl VkTexture {
     ) -> Self {
         let mut app = app.as_mut();
 
-        let (image_width, image_height, channel_count) = (texture_resource.as_ref().width, texture_resource.as_ref().height, texture_resource.as_ref().channel_count);
-        assert_eq!(channel_count, 4, "Failed to create new image.");
-        
-        let image_size = (std::mem::size_of::<u8>() as u32 * image_width * image_height * channel_count) as vk::DeviceSize;
-        let image_data = &texture_resource.as_ref().data;
-        let mip_levels = texture_resource.as_ref().mip_levels;
+        // Basis textures are transcoded to the best block format the device can sample
+        let texture_ref = texture_resource.as_ref();
+        let transcoded;
+        let texture = if texture_ref.format.needs_transcoding() {
+            transcoded = texture_ref.transcode(Self::transcode_target(app.get_physical_device(), texture_ref.color_space));
+            &transcoded
+        } else {
+            &*texture_ref
+        };
+
+        let format = Self::vk_format(texture.format, texture.color_space);
+        assert!(app.get_physical_device().supports_texture_format(format), "Failed to create new image. (Format {:?} is not supported)", format);
+        if !texture.format.is_compressed() {
+            assert_eq!(texture.channel_count, 4, "Failed to create new image.");
+        }
+
+        let (image_width, image_height) = (texture.width, texture.height);
+        let image_size = texture.data.len() as vk::DeviceSize;
+        let image_data = &texture.data;
+        let mip_levels = texture.mip_levels;
 
         let staging_buffer = VkBuffer::new(
             "Texture STAGING BUFFER".to_owned(),
@@ -42,7 +55,7 @@ impl VkTexture {
             app.get_device().clone(),
             image_width, image_height,
             mip_levels,
-            vk::Format::R8G8B8A8_UNORM,
+            format,
             vk::SampleCountFlags::TYPE_1,
             vk::ImageTiling::OPTIMAL,
             vk::ImageUsageFlags::TRANSFER_SRC | vk::ImageUsageFlags::TRANSFER_DST | vk::ImageUsageFlags::SAMPLED,
@@ -61,19 +74,102 @@ impl VkTexture {
                 vk::ImageLayout::TRANSFER_DST_OPTIMAL,
                 mip_levels
             );
-            cmd_buffer_ref.copy_buffer_to_image(&staging_buffer, &image);
-            cmd_buffer_ref.generate_mips(&image, mip_levels);
+
+            if texture.has_precomputed_mips() {
+                let mut regions = Vec::with_capacity(mip_levels as usize);
+                for level in 0..mip_levels {
+                    let (width, height) = texture.level_extent(level);
+                    regions.push(vk::BufferImageCopy {
+                        image_subresource: vk::ImageSubresourceLayers {
+                            aspect_mask: vk::ImageAspectFlags::COLOR,
+                            mip_level: level,
+                            base_array_layer: 0,
+                            layer_count: 1,
+                        },
+                        image_extent: vk::Extent3D {
+                            width: width,
+                            height: height,
+                            depth: 1,
+                        },
+                        buffer_offset: texture.mip_offsets[level as usize] as vk::DeviceSize,
+                        buffer_image_height: 0,
+                        buffer_row_length: 0,
+                        image_offset: vk::Offset3D { x: 0, y: 0, z: 0 },
+                    });
+                }
+
+                cmd_buffer_ref.copy_buffer_to_image_regions(&staging_buffer, &image, &regions);
+                cmd_buffer_ref.transition_image_layout(
+                    &image,
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                    mip_levels
+                );
+            } else {
+                assert!(!texture.format.is_compressed(), "Failed to create new image. (Can't generate mips for block compressed formats)");
+
+                cmd_buffer_ref.copy_buffer_to_image(&staging_buffer, &image);
+                cmd_buffer_ref.generate_mips(&image, mip_levels);
+            }
+
             cmd_buffer_ref.end();
         }
         cmd_queue.submit_cmd_buffer(cmd_buffer, None, None);
         app.get_device().wait_idle();
 
         VkTexture {
-            image: image,
-            mip_levels: mip_levels
+            image: image
         }
     }
 
+    fn vk_format(format: TextureFormat, color_space: ColorSpace) -> vk::Format {
+        if color_space == ColorSpace::Srgb {
+            match format {
+                TextureFormat::Rgba8 => return vk::Format::R8G8B8A8_SRGB,
+                TextureFormat::Bc1 => return vk::Format::BC1_RGBA_SRGB_BLOCK,
+                TextureFormat::Bc2 => return vk::Format::BC2_SRGB_BLOCK,
+                TextureFormat::Bc3 => return vk::Format::BC3_SRGB_BLOCK,
+                TextureFormat::Bc7 => return vk::Format::BC7_SRGB_BLOCK,
+                TextureFormat::Etc2Rgb8 => return vk::Format::ETC2_R8G8B8_SRGB_BLOCK,
+                TextureFormat::Etc2Rgba8 => return vk::Format::ETC2_R8G8B8A8_SRGB_BLOCK,
+                TextureFormat::Astc4x4 => return vk::Format::ASTC_4X4_SRGB_BLOCK,
+                _ => {} // No sRGB variant, sampled as linear
+            }
+        }
+
+        match format {
+            TextureFormat::Rgba8 => vk::Format::R8G8B8A8_UNORM,
+            TextureFormat::Bc1 => vk::Format::BC1_RGBA_UNORM_BLOCK,
+            TextureFormat::Bc2 => vk::Format::BC2_UNORM_BLOCK,
+            TextureFormat::Bc3 => vk::Format::BC3_UNORM_BLOCK,
+            TextureFormat::Bc4 => vk::Format::BC4_UNORM_BLOCK,
+            TextureFormat::Bc5 => vk::Format::BC5_UNORM_BLOCK,
+            TextureFormat::Bc6h => vk::Format::BC6H_UFLOAT_BLOCK,
+            TextureFormat::Bc7 => vk::Format::BC7_UNORM_BLOCK,
+            TextureFormat::Etc2Rgb8 => vk::Format::ETC2_R8G8B8_UNORM_BLOCK,
+            TextureFormat::Etc2Rgba8 => vk::Format::ETC2_R8G8B8A8_UNORM_BLOCK,
+            TextureFormat::Astc4x4 => vk::Format::ASTC_4X4_UNORM_BLOCK,
+            TextureFormat::Basis | TextureFormat::Uastc => panic!("Failed to get image format. (Basis textures must be transcoded first)")
+        }
+    }
+
+    fn transcode_target(physical_device: &VkPhysicalDevice, color_space: ColorSpace) -> TextureFormat {
+        let candidates = [
+            TextureFormat::Bc7,
+            TextureFormat::Astc4x4,
+            TextureFormat::Etc2Rgba8,
+            TextureFormat::Bc3
+        ];
+
+        for candidate in candidates {
+            if physical_device.supports_texture_format(Self::vk_format(candidate, color_space)) {
+                return candidate;
+            }
+        }
+
+        TextureFormat::Rgba8
+    }
+
     pub fn get_image(&self) -> &VkImage {
         &self.image
     }
@@ -82,7 +178,7 @@ impl VkTexture {
         self.image.get_image_view()
     }
 
-    pub fn mip_levels(&self) -> u32 {
-        self.mip_levels
+    pub fn get_memory_size(&self) -> vk::DeviceSize {
+        self.image.get_memory_size()
     }
 }
\ No newline at end of file
@@ -16,6 +16,8 @@ pub mod vk_render_pass;
 pub use vk_render_pass::*;
 pub mod vk_graphics_pipeline;
 pub use vk_graphics_pipeline::*;
+pub mod vk_compute_pipeline;
+pub use vk_compute_pipeline::*;
 pub mod vk_swapchain;
 pub use vk_swapchain::*;
 pub mod vk_cmd_pool;
@@ -33,6 +35,8 @@ pub mod vk_vertex;
 pub use vk_vertex::*;
 pub mod buffers;
 pub use buffers::*;
+pub mod vk_mesh_arena;
+pub use vk_mesh_arena::*;
 pub mod vk_mesh;
 pub use vk_mesh::*;
 pub mod descriptors;
@@ -1,5 +1,4 @@
 use ash::vk;
-use model::Vertex;
 
 use crate::graphics::*;
 
@@ -8,7 +7,10 @@ pub struct VkBlas {
     accel_info: ArcMutex<vk::AccelerationStructureBuildGeometryInfoKHR>,
     geometries: Vec<vk::AccelerationStructureGeometryKHR>,
     accel: Option<Arc<VkAccel>>,
-    dirty: bool
+    dirty: bool,
+
+    memory_size: vk::DeviceSize,
+    memory_saved: vk::DeviceSize
 }
 
 pub struct VkAccelBuildInfo {
@@ -16,7 +18,8 @@ pub struct VkAccelBuildInfo {
     pub size_info: vk::AccelerationStructureBuildSizesInfoKHR,
     range_info: Arc<Vec<vk::AccelerationStructureBuildRangeInfoKHR>>,
     pub accel: Option<Arc<VkAccel>>,
-    pub cleanup: Option<Arc<VkAccel>>
+    pub cleanup: Option<Arc<VkAccel>>,
+    pub compacted_size: Option<vk::DeviceSize>
 }
 
 impl VkAccelBuildInfo {
@@ -27,30 +30,31 @@ impl VkAccelBuildInfo {
 
 impl VkBlas {
     pub fn new(
-        vertex_buffer: &VkDataBuffer<Vertex>,
-        index_buffer: &VkDataBuffer<u32>,
+        vertex_address: vk::DeviceAddress,
+        vertex_count: u32,
+        vertex_stride: u32,
+        vertex_format: vk::Format,
+        index_address: vk::DeviceAddress,
+        index_count: u32,
         build_flags: vk::BuildAccelerationStructureFlagsKHR
     ) -> ArcMutex<Self> {
-        let vertex_buffer_address = vertex_buffer.get_buffer().get_device_address();
-        let index_buffer_address = index_buffer.get_buffer().get_device_address();
-
-        let primitive_count = index_buffer.get_count() / 3;
+        let primitive_count = index_count / 3;
 
         let geometries = vec![vk::AccelerationStructureGeometryKHR::builder()
             .geometry_type(vk::GeometryTypeKHR::TRIANGLES)
             .flags(vk::GeometryFlagsKHR::OPAQUE)
             .geometry(vk::AccelerationStructureGeometryDataKHR {
                 triangles: vk::AccelerationStructureGeometryTrianglesDataKHR::builder()
-                    .vertex_format(vk::Format::R32G32B32_SFLOAT)
+                    .vertex_format(vertex_format)
                     .vertex_data(vk::DeviceOrHostAddressConstKHR {
-                        device_address: vertex_buffer_address
+                        device_address: vertex_address
                     })
-                    .vertex_stride(vertex_buffer.get_stride() as u64)
+                    .vertex_stride(vertex_stride as u64)
                     .index_type(vk::IndexType::UINT32)
                     .index_data(vk::DeviceOrHostAddressConstKHR {
-                        device_address: index_buffer_address
+                        device_address: index_address
                     })
-                    .max_vertex(vertex_buffer.get_count())
+                    .max_vertex(vertex_count)
                     .build()
         }).build()];
 
@@ -69,10 +73,22 @@ impl VkBlas {
             accel_info: info,
             geometries: geometries,
             accel: None,
-            dirty: true
+            dirty: true,
+            memory_size: 0,
+            memory_saved: 0
         })
     }
 
+    /// Size of the acceleration structure in device memory, after compaction.
+    pub fn get_memory_size(&self) -> vk::DeviceSize {
+        self.memory_size
+    }
+
+    /// Bytes freed by compacting the acceleration structure.
+    pub fn get_memory_saved(&self) -> vk::DeviceSize {
+        self.memory_saved
+    }
+
     pub fn get_accel_ref(&self) -> vk::AccelerationStructureReferenceKHR {
         self.accel.as_ref().unwrap().get_accel_ref()
     }
@@ -111,7 +127,7 @@ impl VkBlas {
             };
 
             _total_size += build_sizes.acceleration_structure_size;
-            max_scratch_size += std::cmp::max(max_scratch_size, build_sizes.build_scratch_size);
+            max_scratch_size = std::cmp::max(max_scratch_size, build_sizes.build_scratch_size);
             if build_info.as_ref().flags.contains(vk::BuildAccelerationStructureFlagsKHR::ALLOW_COMPACTION) {
                 compaction_count += 1;
             }
@@ -121,7 +137,8 @@ impl VkBlas {
                 size_info: build_sizes,
                 range_info: blas.get_offset(),
                 accel: None,
-                cleanup: None
+                cleanup: None,
+                compacted_size: None
             });
         }
 
@@ -193,8 +210,13 @@ impl VkBlas {
 
                         cmd_buffer_ref.end();
                     }
-                    cmd_queue.submit_cmd_buffer(cmd_buffer, None, None);
-                    app.get_device().wait_idle();
+                    let fence = cmd_queue.submit_cmd_buffer(cmd_buffer, None, None);
+                    fence.wait();
+
+                    // The copies are done, so the original (non compacted) blases can be freed
+                    for i in &indices {
+                        build_infos[*i].cleanup = None;
+                    }
                 }
 
                 batch_size = 0;
@@ -206,6 +228,10 @@ impl VkBlas {
             let mut blas = blases[i].as_mut();
             blas.accel = build_info.accel.clone();
             blas.dirty = false;
+
+            let original_size = build_info.size_info.acceleration_structure_size;
+            blas.memory_size = build_info.compacted_size.unwrap_or(original_size);
+            blas.memory_saved = original_size - blas.memory_size;
         }
     }
 
@@ -26,6 +26,14 @@ impl VkBlasInstance {
         }
     }
     
+    pub fn set_transform(&mut self, transform_matrix: Matrix4<f32>) {
+        self.instance.transform = mat4_to_khr_transform_matrix(transform_matrix);
+    }
+
+    pub fn set_blas(&mut self, blas: ArcMutex<VkBlas>) {
+        self.instance.acceleration_structure_reference = blas.as_ref().get_accel_ref();
+    }
+
     pub fn get_instance(&self) -> &vk::AccelerationStructureInstanceKHR {
         &self.instance
     }
@@ -4,12 +4,22 @@ use crate::graphics::*;
 
 pub struct VkTlas {
     accel: Option<ArcMutex<VkAccel>>,
+    instances_buffer: Option<VkBuffer>,
+    instances_ptr: *mut VkBlasInstance,
+    instance_capacity: usize,
+    instance_count: usize,
+    build_flags: vk::BuildAccelerationStructureFlagsKHR
 }
 
 impl VkTlas {
     pub fn new() -> ArcMutex<Self> {
         let tlas = ArcMutex::new(VkTlas {
-            accel: None
+            accel: None,
+            instances_buffer: None,
+            instances_ptr: std::ptr::null_mut(),
+            instance_capacity: 0,
+            instance_count: 0,
+            build_flags: vk::BuildAccelerationStructureFlagsKHR::empty()
         });
 
         tlas
@@ -19,53 +29,75 @@ impl VkTlas {
         self.accel.as_ref().unwrap().as_ref().get_accel()
     }
 
+    /// Fully rebuilds the tlas, only required when instances were added or removed.
     pub fn rebuild(&mut self,
         app: &mut VkApp,
         instances: &Vec<VkBlasInstance>,
         build_flags: vk::BuildAccelerationStructureFlagsKHR
     ) {
-        let build_flags = build_flags | vk::BuildAccelerationStructureFlagsKHR::ALLOW_UPDATE;
-        let instances_size = std::mem::size_of::<VkBlasInstance>() * instances.len();
-
-        let staging_buffer = VkBuffer::new(
-            "Tlas STAGING BUFFER".to_owned(),
-            app.get_device().clone(),
-            app.get_allocator(),
-            instances_size as u64,
-            vk::BufferUsageFlags::TRANSFER_SRC,
-            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
-            None
-        );
+        self.build_flags = build_flags | vk::BuildAccelerationStructureFlagsKHR::ALLOW_UPDATE;
+
+        if instances.len() > self.instance_capacity || self.instances_buffer.is_none() {
+            app.get_device().wait_idle();
+
+            self.instance_capacity = std::cmp::max(instances.len(), 1).next_power_of_two();
+            let instances_buffer = VkBuffer::new(
+                "Tlas instances BUFFER".to_owned(),
+                app.get_device(),
+                app.get_allocator(),
+                (std::mem::size_of::<VkBlasInstance>() * self.instance_capacity) as u64,
+                vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS | vk::BufferUsageFlags::ACCELERATION_STRUCTURE_BUILD_INPUT_READ_ONLY_KHR,
+                vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+                Some(16)
+            );
+            self.instances_ptr = instances_buffer.map() as *mut VkBlasInstance;
+            self.instances_buffer = Some(instances_buffer);
+        }
 
         unsafe {
-            let data_ptr = staging_buffer.map() as *mut VkBlasInstance;
-            data_ptr.copy_from_nonoverlapping(instances.as_ptr(), instances.len());
-            staging_buffer.unmap();
+            self.instances_ptr.copy_from_nonoverlapping(instances.as_ptr(), instances.len());
         }
+        self.instance_count = instances.len();
 
-        let instances_buffer = VkBuffer::new(
-            "Tlas instances BUFFER".to_owned(),
-            app.get_device(),
-            app.get_allocator(),
-            instances_size as u64,
-            vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS | vk::BufferUsageFlags::ACCELERATION_STRUCTURE_BUILD_INPUT_READ_ONLY_KHR | vk::BufferUsageFlags::TRANSFER_DST,
-            vk::MemoryPropertyFlags::DEVICE_LOCAL,
-            None
-        );
-        let buffer_address = instances_buffer.get_device_address();
-
-        {
-            let cmd_queue = app.get_cmd_queue();
-            let mut cmd_queue = cmd_queue.as_mut();
-            let cmd_buffer = cmd_queue.get_cmd_buffer(); {
-                let cmd_buffer_ref = cmd_buffer.as_ref();
-                cmd_buffer_ref.begin(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
-                cmd_buffer_ref.copy_buffers(&staging_buffer, &instances_buffer);
-                cmd_buffer_ref.end();
-            }
-            cmd_queue.submit_cmd_buffer(cmd_buffer, None, None);
-            app.get_device().wait_idle();
+        self.build(app, false);
+    }
+
+    /// Patches the transform of a single instance in the persistent instance buffer.
+    /// The change only becomes visible after calling `refit`.
+    pub fn set_instance_transform(&mut self, index: usize, transform_matrix: Matrix4<f32>) {
+        assert!(index < self.instance_count, "Failed to set instance transform. (Index out of bounds)");
+
+        unsafe {
+            (*self.instances_ptr.add(index)).set_transform(transform_matrix);
         }
+    }
+
+    /// Points a single instance at another blas, e.g. a different level of detail of the same mesh.
+    /// The change only becomes visible after calling `refit`.
+    pub fn set_instance_blas(&mut self, index: usize, blas: ArcMutex<VkBlas>) {
+        assert!(index < self.instance_count, "Failed to set instance blas. (Index out of bounds)");
+
+        unsafe {
+            (*self.instances_ptr.add(index)).set_blas(blas);
+        }
+    }
+
+    /// Updates the tlas in place using the current contents of the instance buffer.
+    pub fn refit(&mut self, app: &mut VkApp) {
+        assert!(self.accel.is_some(), "Failed to refit tlas. (Not built yet)");
+
+        self.build(app, true);
+    }
+
+    fn build(&mut self, app: &mut VkApp, update: bool) {
+        let buffer_address = self.instances_buffer.as_ref().unwrap().get_device_address();
+
+        // Keep the previous accel alive until the new one is done building
+        let _previous_accel = if !update {
+            self.accel.take()
+        } else {
+            None
+        };
 
         let cmd_queue = app.get_cmd_queue();
         let mut cmd_queue = cmd_queue.as_mut();
@@ -73,21 +105,21 @@ impl VkTlas {
             let mut cmd_buffer_ref = cmd_buffer.as_mut();
             cmd_buffer_ref.begin(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
 
-            if !self.accel.is_some() {
+            if !update {
                 self.accel = Some(cmd_buffer_ref.create_tlas(
                     None,
-                    instances.len() as u32,
+                    self.instance_count as u32,
                     buffer_address,
-                    build_flags,
+                    self.build_flags,
                     false,
                     app.get_physical_device().get_accel_properties()
                 ).unwrap());
             } else {
                 cmd_buffer_ref.create_tlas(
                     Some(self.accel.as_ref().unwrap().clone()),
-                    instances.len() as u32,
+                    self.instance_count as u32,
                     buffer_address,
-                    build_flags,
+                    self.build_flags,
                     true,
                     app.get_physical_device().get_accel_properties()
                 );
@@ -98,4 +130,4 @@ impl VkTlas {
         cmd_queue.submit_cmd_buffer(cmd_buffer, None, None);
         app.get_device().wait_idle();
     }
-}
\ No newline at end of file
+}
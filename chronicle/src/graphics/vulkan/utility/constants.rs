@@ -16,8 +16,9 @@ pub const VALIDATION: ValidationInfo = ValidationInfo {
 // "VK_KHR_A"
 // "VK_KHR_B" (required for VK_KHR_A)
 // "VK_KHR_C" (required for VK_KHR_B)
-pub const DEVICE_EXTENSIONS: [&'static str; 9] = [
+pub const DEVICE_EXTENSIONS: [&'static str; 10] = [
     "VK_KHR_swapchain",
+    "VK_KHR_draw_indirect_count",
 
     "VK_KHR_device_group",
     "VK_KHR_buffer_device_address",
@@ -32,8 +33,9 @@ pub const DEVICE_EXTENSIONS: [&'static str; 9] = [
     "VK_KHR_shader_float_controls"
 ];
 
-pub const ENABLE_EXTENSION_NAMES: [*const std::ffi::c_char; 9] = [
+pub const ENABLE_EXTENSION_NAMES: [*const std::ffi::c_char; 10] = [
     ash::extensions::khr::Swapchain::name().as_ptr(),
+    ash::extensions::khr::DrawIndirectCount::name().as_ptr(),
     ash::extensions::khr::DeviceGroup::name().as_ptr(),
     ash::extensions::khr::BufferDeviceAddress::name().as_ptr(),
     ash::extensions::khr::AccelerationStructure::name().as_ptr(),
@@ -44,4 +46,7 @@ pub const ENABLE_EXTENSION_NAMES: [*const std::ffi::c_char; 9] = [
     ash::vk::KhrShaderFloatControlsFn::name().as_ptr(),
 ];
 
-pub const MAX_FRAMES_IN_FLIGHT: usize = 3;
\ No newline at end of file
+pub const MAX_FRAMES_IN_FLIGHT: usize = 3;
+
+/// Size of the ray tracing texture array, the textures of all resident materials are laid out in it back to back
+pub const MAX_RT_TEXTURES: usize = 1024;
\ No newline at end of file
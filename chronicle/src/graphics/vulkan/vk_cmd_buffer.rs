@@ -18,7 +18,9 @@ pub struct VkCmdBuffer {
     desc_layouts: HashMap<u32, Arc<VkDescriptorSetLayout>>,
 
     graphics_pipeline: Option<Arc<VkGraphicsPipeline>>,
+    compute_pipeline: Option<Arc<VkComputePipeline>>,
     rt_pipeline: Option<Arc<VkRTPipeline>>,
+    bind_point: vk::PipelineBindPoint,
 
     tracked_buffers: Vec<Arc<VkBuffer>>,
     tracked_desc_sets: Vec<Arc<VkDescriptorSet>>,
@@ -54,7 +56,9 @@ impl VkCmdBuffer {
             desc_sets: HashMap::new(),
             desc_layouts: HashMap::new(),
             graphics_pipeline: None,
+            compute_pipeline: None,
             rt_pipeline: None,
+            bind_point: vk::PipelineBindPoint::GRAPHICS,
             tracked_buffers: Vec::new(),
             tracked_desc_sets: Vec::new()
         }
@@ -67,6 +71,8 @@ impl VkCmdBuffer {
     pub fn reset(&mut self) {
         unsafe {
             self.graphics_pipeline = None;
+            self.compute_pipeline = None;
+            self.bind_point = vk::PipelineBindPoint::GRAPHICS;
             self.desc_layouts.clear();
             self.tracked_buffers.clear();
             self.tracked_desc_sets.clear();
@@ -146,7 +152,14 @@ impl VkCmdBuffer {
         }
     }
 
-    pub fn begin_render_pass(&self, render_pass: &VkRenderPass, swapchain: &VkSwapchain) {
+    pub fn set_depth_compare_op(&self, compare_op: vk::CompareOp) {
+        unsafe {
+            self.device.get_device()
+                .cmd_set_depth_compare_op(self.cmd_buffer, compare_op);
+        }
+    }
+
+    pub fn begin_render_pass(&self, render_pass: &VkRenderPass, swapchain: &VkSwapchain, clear_depth: f32) {
         let clear_values = [
             vk::ClearValue {
                 color: vk::ClearColorValue {
@@ -155,7 +168,7 @@ impl VkCmdBuffer {
             },
             vk::ClearValue {
                 depth_stencil: vk::ClearDepthStencilValue {
-                    depth: 1.0,
+                    depth: clear_depth,
                     stencil: 0,
                 },
             }
@@ -192,6 +205,7 @@ impl VkCmdBuffer {
 
     pub fn bind_graphics_pipeline(&mut self, pipeline: Arc<VkGraphicsPipeline>) {
         self.graphics_pipeline = Some(pipeline.clone());
+        self.bind_point = vk::PipelineBindPoint::GRAPHICS;
 
         unsafe {
             self.device.get_device()
@@ -203,8 +217,23 @@ impl VkCmdBuffer {
         }
     }
 
+    pub fn bind_compute_pipeline(&mut self, pipeline: Arc<VkComputePipeline>) {
+        self.compute_pipeline = Some(pipeline.clone());
+        self.bind_point = vk::PipelineBindPoint::COMPUTE;
+
+        unsafe {
+            self.device.get_device()
+                .cmd_bind_pipeline(
+                    self.cmd_buffer,
+                    vk::PipelineBindPoint::COMPUTE,
+                    pipeline.get_pipeline(),
+                );
+        }
+    }
+
     pub fn bind_rt_pipeline(&mut self, pipeline: Arc<VkRTPipeline>) {
         self.rt_pipeline = Some(pipeline.clone());
+        self.bind_point = vk::PipelineBindPoint::RAY_TRACING_KHR;
 
         unsafe {
             self.device.get_device()
@@ -244,6 +273,29 @@ impl VkCmdBuffer {
         self.tracked_buffers.push(index_buffer.get_buffer());
     }
 
+    pub fn bind_mesh_arena(&mut self, arena: &VkMeshArena) {
+        unsafe {
+            self.device.get_device()
+                .cmd_bind_vertex_buffers(
+                    self.cmd_buffer,
+                    0,
+                    &[arena.get_vertex_buffer().get_buffer()],
+                    &[0_u64]
+                );
+
+            self.device.get_device()
+                .cmd_bind_index_buffer(
+                    self.cmd_buffer,
+                    arena.get_index_buffer().get_buffer(),
+                    0,
+                    vk::IndexType::UINT32
+                );
+        }
+
+        self.tracked_buffers.push(arena.get_vertex_buffer());
+        self.tracked_buffers.push(arena.get_index_buffer());
+    }
+
     pub fn draw(&self, vertex_count: u32, instance_count: u32, first_vertex: u32, first_instance: u32) {
         unsafe {
             self.device.get_device()
@@ -271,6 +323,60 @@ impl VkCmdBuffer {
         }
     }
 
+    pub fn draw_indexed_indirect_count(&mut self,
+        buffer: Arc<VkBuffer>,
+        offset: vk::DeviceSize,
+        count_buffer: Arc<VkBuffer>,
+        count_offset: vk::DeviceSize,
+        max_draw_count: u32,
+        stride: u32
+    ) {
+        unsafe {
+            self.device.draw_indirect_count_loader()
+                .cmd_draw_indexed_indirect_count(
+                    self.cmd_buffer,
+                    buffer.get_buffer(),
+                    offset,
+                    count_buffer.get_buffer(),
+                    count_offset,
+                    max_draw_count,
+                    stride
+                );
+        }
+
+        self.tracked_buffers.push(buffer);
+        self.tracked_buffers.push(count_buffer);
+    }
+
+    pub fn dispatch(&self, group_count_x: u32, group_count_y: u32, group_count_z: u32) {
+        assert!(self.compute_pipeline.is_some(), "Failed to dispatch. (No VkComputePipeline bound)");
+
+        unsafe {
+            self.device.get_device()
+                .cmd_dispatch(
+                    self.cmd_buffer,
+                    group_count_x,
+                    group_count_y,
+                    group_count_z
+                );
+        }
+    }
+
+    pub fn fill_buffer(&mut self, buffer: Arc<VkBuffer>, data: u32) {
+        unsafe {
+            self.device.get_device()
+                .cmd_fill_buffer(
+                    self.cmd_buffer,
+                    buffer.get_buffer(),
+                    0,
+                    vk::WHOLE_SIZE,
+                    data
+                );
+        }
+
+        self.tracked_buffers.push(buffer);
+    }
+
     pub fn trace_rays(&self, width: u32, height: u32) {
         let rt_pipeline = self.rt_pipeline.as_ref()
             .expect("Failed to trace rays. (No VkRTPipeline bound)");
@@ -310,6 +416,18 @@ impl VkCmdBuffer {
         }
     }
 
+    pub fn copy_buffer_regions(&self, src_buffer: &VkBuffer, dst_buffer: &VkBuffer, regions: &[vk::BufferCopy]) {
+        unsafe {
+            self.device.get_device()
+                .cmd_copy_buffer(
+                    self.cmd_buffer,
+                    src_buffer.get_buffer(),
+                    dst_buffer.get_buffer(),
+                    regions
+                );
+        }
+    }
+
     pub fn copy_buffer_to_image(&self, src_buffer: &VkBuffer, dst_image: &VkImage) {
         let buffer_image_regions = [vk::BufferImageCopy {
             image_subresource: vk::ImageSubresourceLayers {
@@ -341,6 +459,19 @@ impl VkCmdBuffer {
         }
     }
 
+    pub fn copy_buffer_to_image_regions(&self, src_buffer: &VkBuffer, dst_image: &VkImage, regions: &[vk::BufferImageCopy]) {
+        unsafe {
+            self.device.get_device()
+                .cmd_copy_buffer_to_image(
+                    self.cmd_buffer,
+                    src_buffer.get_buffer(),
+                    dst_image.get_image(),
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    regions,
+                );
+        }
+    }
+
     pub fn transition_image_layout(&self,
         image: &VkImage,
         old_layout: vk::ImageLayout,
@@ -605,6 +736,42 @@ impl VkCmdBuffer {
         self.tracked_buffers.push(uniform_buffer.as_ref().track_buffer());
     }
 
+    pub fn set_desc_storage_buffer(&mut self,
+        set: u32,
+        binding: u32,
+        buffer: Arc<VkBuffer>
+    ) {
+        let desc_set = self.get_desc_set(set);
+
+        let descriptor_buffer_info = [vk::DescriptorBufferInfo {
+            buffer: buffer.get_buffer(),
+            offset: 0,
+            range: vk::WHOLE_SIZE,
+        }];
+
+        let descriptor_write_sets = [
+            vk::WriteDescriptorSet {
+                s_type: vk::StructureType::WRITE_DESCRIPTOR_SET,
+                p_next: std::ptr::null(),
+                dst_set: desc_set.get_desc_set(),
+                dst_binding: binding,
+                dst_array_element: 0,
+                descriptor_count: 1,
+                descriptor_type: vk::DescriptorType::STORAGE_BUFFER,
+                p_image_info: std::ptr::null(),
+                p_buffer_info: descriptor_buffer_info.as_ptr(),
+                p_texel_buffer_view: std::ptr::null(),
+            }
+        ];
+
+        unsafe {
+            self.device.get_device()
+                .update_descriptor_sets(&descriptor_write_sets, &[]);
+        }
+
+        self.tracked_buffers.push(buffer);
+    }
+
     pub fn set_desc_texture(&mut self,
         set: u32,
         binding: u32,
@@ -723,8 +890,8 @@ impl VkCmdBuffer {
             self.device.get_device()
                 .cmd_bind_descriptor_sets(
                     self.cmd_buffer,
-                    vk::PipelineBindPoint::GRAPHICS,
-                    self.graphics_pipeline.as_ref().unwrap().get_layout(),
+                    self.bind_point,
+                    self.get_bound_layout(),
                     0,
                     &desc_set_ptrs,
                     &[]
@@ -737,9 +904,19 @@ impl VkCmdBuffer {
         self.desc_sets.clear();
     }
 
+    fn get_bound_layout(&self) -> vk::PipelineLayout {
+        match self.bind_point {
+            vk::PipelineBindPoint::COMPUTE => self.compute_pipeline.as_ref()
+                .expect("Failed to get pipeline layout. (No pipeline bound)").get_layout(),
+            vk::PipelineBindPoint::RAY_TRACING_KHR => self.rt_pipeline.as_ref()
+                .expect("Failed to get pipeline layout. (No pipeline bound)").get_layout(),
+            _ => self.graphics_pipeline.as_ref()
+                .expect("Failed to get pipeline layout. (No pipeline bound)").get_layout()
+        }
+    }
+
     pub fn push_constant<T: Sized>(&self, constant: &T, stage_flags: vk::ShaderStageFlags) {
-        let pipeline = self.graphics_pipeline.as_ref()
-                                        .expect("Failed to push constant. (No pipeline bound)").as_ref();
+        let layout = self.get_bound_layout();
 
         unsafe {
             let bytes = core::slice::from_raw_parts(
@@ -750,7 +927,7 @@ impl VkCmdBuffer {
             self.device.get_device()
                 .cmd_push_constants(
                     self.cmd_buffer,
-                    pipeline.get_layout(),
+                    layout,
                     stage_flags,
                     0,
                     bytes
@@ -793,7 +970,7 @@ impl VkCmdBuffer {
             query_pool.reset();
         }
 
-        for i in indices {
+        for (query_idx, i) in indices.iter().enumerate() {
             let build_info = &mut build_infos[*i];
 
             let mut create_info = vk::AccelerationStructureCreateInfoKHR::builder()
@@ -836,7 +1013,7 @@ impl VkCmdBuffer {
                             &[build_info.build_info.as_ref().dst_acceleration_structure],
                             vk::QueryType::ACCELERATION_STRUCTURE_COMPACTED_SIZE_KHR,
                             query_pool.get_query_pool(),
-                            0
+                            query_idx as u32
                         );
                 }
             }
@@ -848,18 +1025,16 @@ impl VkCmdBuffer {
         indices: &Vec<usize>,
         query_pool: Arc<VkQueryPool>
     ) {
-        let mut query_count = 0;
-        let compact_sizes = query_pool.query_results::<vk::DeviceSize>(indices.len());
+        let compact_sizes = query_pool.query_results::<vk::DeviceSize>(0, indices.len());
         
-        for i in indices {
+        for (query_idx, i) in indices.iter().enumerate() {
             let build_info = &mut build_infos[*i];
 
             build_info.cleanup = build_info.accel.clone();
-            build_info.size_info.acceleration_structure_size = compact_sizes[query_count];
-            query_count += 1;
+            build_info.compacted_size = Some(compact_sizes[query_idx]);
 
             let mut create_info = vk::AccelerationStructureCreateInfoKHR::builder()
-                .size(build_info.size_info.acceleration_structure_size)
+                .size(compact_sizes[query_idx])
                 .ty(vk::AccelerationStructureTypeKHR::BOTTOM_LEVEL)
                 .build();
             build_info.accel = Some(Arc::new(VkAccel::new(
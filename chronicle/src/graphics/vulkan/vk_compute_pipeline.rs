@@ -0,0 +1,99 @@
+use std::ptr;
+
+use crate::graphics::*;
+
+pub struct VkComputePipeline {
+    device: Arc<VkLogicalDevice>,
+    pipeline_layout: vk::PipelineLayout,
+    pipeline: vk::Pipeline
+}
+
+impl VkComputePipeline {
+    pub fn new(
+        device: Arc<VkLogicalDevice>,
+        desc_layouts: &Vec<&VkDescriptorSetLayout>,
+        push_constants: &Vec<vk::PushConstantRange>,
+        shader: String
+    ) -> Arc<Self> {
+        let main_function_name = std::ffi::CString::new("main").unwrap();
+
+        let shader_module = VkShaderModule::new(device.clone(), shader);
+        assert_eq!(*shader_module.get_stage_flags(), vk::ShaderStageFlags::COMPUTE, "Failed to create compute pipeline. (Not a compute shader)");
+
+        let shader_stage = vk::PipelineShaderStageCreateInfo {
+            s_type: vk::StructureType::PIPELINE_SHADER_STAGE_CREATE_INFO,
+            p_next: ptr::null(),
+            flags: vk::PipelineShaderStageCreateFlags::empty(),
+            module: *shader_module.get_module(),
+            p_name: main_function_name.as_ptr(),
+            p_specialization_info: ptr::null(),
+            stage: vk::ShaderStageFlags::COMPUTE
+        };
+
+        let mut set_layouts = Vec::new();
+        for desc_layout in desc_layouts {
+            set_layouts.push(desc_layout.get_desc_layout());
+        }
+
+        let pipeline_layout_create_info = vk::PipelineLayoutCreateInfo {
+            s_type: vk::StructureType::PIPELINE_LAYOUT_CREATE_INFO,
+            p_next: ptr::null(),
+            flags: vk::PipelineLayoutCreateFlags::empty(),
+            set_layout_count: set_layouts.len() as u32,
+            p_set_layouts: set_layouts.as_ptr(),
+            push_constant_range_count: push_constants.len() as u32,
+            p_push_constant_ranges: push_constants.as_ptr(),
+        };
+
+        let pipeline_layout = unsafe {
+            device.get_device()
+                .create_pipeline_layout(&pipeline_layout_create_info, None)
+                .expect("Failed to create pipeline layout.")
+        };
+
+        let compute_pipeline_create_infos = [vk::ComputePipelineCreateInfo {
+            s_type: vk::StructureType::COMPUTE_PIPELINE_CREATE_INFO,
+            p_next: ptr::null(),
+            flags: vk::PipelineCreateFlags::empty(),
+            stage: shader_stage,
+            layout: pipeline_layout,
+            base_pipeline_handle: vk::Pipeline::null(),
+            base_pipeline_index: -1,
+        }];
+
+        let pipeline = unsafe {
+            device.get_device()
+                .create_compute_pipelines(
+                    vk::PipelineCache::null(),
+                    &compute_pipeline_create_infos,
+                    None,
+                )
+                .expect("Failed to create Compute Pipeline.")
+        };
+
+        Arc::new(VkComputePipeline {
+            device: device,
+            pipeline_layout: pipeline_layout,
+            pipeline: pipeline[0]
+        })
+    }
+
+    pub fn get_pipeline(&self) -> vk::Pipeline {
+        self.pipeline
+    }
+
+    pub fn get_layout(&self) -> vk::PipelineLayout {
+        self.pipeline_layout
+    }
+}
+
+impl Drop for VkComputePipeline {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.get_device()
+                .destroy_pipeline(self.pipeline, None);
+            self.device.get_device()
+                .destroy_pipeline_layout(self.pipeline_layout, None);
+        }
+    }
+}
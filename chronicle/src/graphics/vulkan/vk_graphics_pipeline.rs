@@ -16,6 +16,7 @@ impl VkGraphicsPipeline {
         desc_layouts: &Vec<&VkDescriptorSetLayout>,
         push_constants: &Vec<vk::PushConstantRange>,
         shaders: &Vec<String>,
+        topology: vk::PrimitiveTopology,
         cull_mode: vk::CullModeFlags,
         depth_test_enable: vk::Bool32
     ) -> Arc<Self> {
@@ -54,7 +55,7 @@ impl VkGraphicsPipeline {
             flags: vk::PipelineInputAssemblyStateCreateFlags::empty(),
             p_next: ptr::null(),
             primitive_restart_enable: vk::FALSE,
-            topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+            topology: topology,
         };
 
         let viewports = [vk::Viewport {
@@ -166,7 +167,11 @@ impl VkGraphicsPipeline {
             blend_constants: [0.0, 0.0, 0.0, 0.0],
         };
 
-        let dynamic_state = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+        // The depth compare op flips with reversed depth projections, so it is left to the command buffer
+        let mut dynamic_state = vec![vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+        if depth_test_enable == vk::TRUE {
+            dynamic_state.push(vk::DynamicState::DEPTH_COMPARE_OP);
+        }
         let dynamic_state_info = vk::PipelineDynamicStateCreateInfo {
             s_type: vk::StructureType::PIPELINE_DYNAMIC_STATE_CREATE_INFO,
             p_next: ptr::null(),
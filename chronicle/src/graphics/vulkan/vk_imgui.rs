@@ -5,7 +5,7 @@ use cgmath::Vector4;
 use memoffset::offset_of;
 
 use crate::graphics::*;
-use crate::resources::Texture;
+use crate::resources::{Texture, TextureFormat, ColorSpace, TextureSampler};
 
 pub struct VkImGui {
     context: imgui::Context,
@@ -51,6 +51,10 @@ impl VkImGui {
         self.context.io_mut().add_mouse_pos_event([x, y]);
     }
 
+    pub fn mouse_wheel_event(&mut self, x: f32, y: f32) {
+        self.context.io_mut().add_mouse_wheel_event([x, y]);
+    }
+
     pub fn new_frame(&mut self) -> &mut imgui::Ui {
         if !self.rendered {
             unsafe { imgui::sys::igEndFrame(); }
@@ -138,10 +142,13 @@ impl Renderer {
             width: atlas_texture.width,
             height: atlas_texture.height,
             channel_count: 4,
-            mip_levels: 1 // Mip mapping???
+            mip_levels: 1, // Mip mapping???
+            format: TextureFormat::Rgba8,
+            color_space: ColorSpace::Linear,
+            mip_offsets: Vec::new()
         });
         let texture = VkTexture::new(app.clone(), texture);
-        let sampler = VkSampler::new(device.clone(), &texture);
+        let sampler = VkSampler::new(device.clone(), &TextureSampler::default());
 
         Renderer {
             device: device,
@@ -185,6 +192,7 @@ impl Renderer {
             &vec![&descriptor_layout],
             &push_constants,
             &vec![String::from("imgui.vert"), String::from("imgui.frag")],
+            vk::PrimitiveTopology::TRIANGLE_LIST,
             vk::CullModeFlags::NONE,
             vk::FALSE
         );
@@ -270,7 +278,7 @@ impl Renderer {
                     width: fb_width as u32,
                     height: fb_height as u32
                 });
-                cmd_buffer.begin_render_pass(&render_pass, &swapchain);
+                cmd_buffer.begin_render_pass(&render_pass, &swapchain, 1.0);
 
                 cmd_buffer.bind_graphics_pipeline(self.pipeline.clone());
                 cmd_buffer.set_desc_layout(0, self.desc_layout.clone());
@@ -25,7 +25,8 @@ pub struct VkLogicalDevice {
     debug_utils_loader: ash::extensions::ext::DebugUtils,
     raytracing_loader: ash::extensions::khr::RayTracingPipeline,
     accel_loader: ash::extensions::khr::AccelerationStructure,
-    buffer_device_address_loader: ash::extensions::khr::BufferDeviceAddress
+    buffer_device_address_loader: ash::extensions::khr::BufferDeviceAddress,
+    draw_indirect_count_loader: ash::extensions::khr::DrawIndirectCount
 }
 
 impl VkLogicalDevice {
@@ -56,6 +57,10 @@ impl VkLogicalDevice {
         let physical_device_features = vk::PhysicalDeviceFeatures {
             sampler_anisotropy: vk::TRUE,
             shader_int64: vk::TRUE,
+            multi_draw_indirect: vk::TRUE,
+            texture_compression_bc: physical_device.get_features().texture_compression_bc,
+            texture_compression_etc2: physical_device.get_features().texture_compression_etc2,
+            texture_compression_astc_ldr: physical_device.get_features().texture_compression_astc_ldr,
             ..Default::default()
         };
 
@@ -130,6 +135,7 @@ impl VkLogicalDevice {
         let accel_loader = ash::extensions::khr::AccelerationStructure::new(instance.get_instance(), &device);
         let buffer_device_address_loader = ash::extensions::khr::BufferDeviceAddress::new(instance.get_instance(), &device);
         let debug_utils_loader = ash::extensions::ext::DebugUtils::new(instance.get_entry(), instance.get_instance());
+        let draw_indirect_count_loader = ash::extensions::khr::DrawIndirectCount::new(instance.get_instance(), &device);
 
         Arc::new(VkLogicalDevice {
             device: device,
@@ -137,7 +143,8 @@ impl VkLogicalDevice {
             debug_utils_loader: debug_utils_loader,
             raytracing_loader: raytracing_loader,
             accel_loader: accel_loader,
-            buffer_device_address_loader: buffer_device_address_loader
+            buffer_device_address_loader: buffer_device_address_loader,
+            draw_indirect_count_loader: draw_indirect_count_loader
         })
     }
 
@@ -180,6 +187,10 @@ impl VkLogicalDevice {
     pub fn buffer_device_address_loader(&self) -> &ash::extensions::khr::BufferDeviceAddress {
         &self.buffer_device_address_loader
     }
+
+    pub fn draw_indirect_count_loader(&self) -> &ash::extensions::khr::DrawIndirectCount {
+        &self.draw_indirect_count_loader
+    }
 }
 
 impl Drop for VkLogicalDevice {
@@ -1,60 +1,138 @@
 use crate::graphics::*;
-use crate::resources::Vertex;
+use crate::resources::{Mesh, CompactVertex, VertexLayout, MeshKind};
+
+/// Index range of one level of detail, all levels share the vertices of the mesh
+pub struct VkMeshLod {
+    pub first_index: u32,
+    pub index_count: u32,
+    blas: Option<ArcMutex<VkBlas>>
+}
 
 pub struct VkMesh {
-    vertex_buffer: VkDataBuffer<Vertex>,
-    index_buffer: VkDataBuffer<u32>,
-    blas: ArcMutex<VkBlas>
+    arena: ArcMutex<VkMeshArena>,
+    allocation: VkMeshAllocation,
+    lods: Vec<VkMeshLod>
 }
 
 impl VkMesh {
     pub fn new(
         app: &mut VkApp,
-        vertices: &Vec<Vertex>,
-        indices: &Vec<u32>
+        arena: &ArcMutex<VkMeshArena>,
+        mesh: &Mesh,
+        vertex_layout: VertexLayout
     ) -> Self {
-        let usage_flags = vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS
-                                            | vk::BufferUsageFlags::ACCELERATION_STRUCTURE_BUILD_INPUT_READ_ONLY_KHR
-                                            | vk::BufferUsageFlags::STORAGE_BUFFER;
-
-        let vertex_buffer = VkDataBuffer::new(
-            "Mesh Vertices",
-            app,
-            &vertices,
-            vk::BufferUsageFlags::VERTEX_BUFFER | usage_flags,
-            vk::MemoryPropertyFlags::DEVICE_LOCAL,
-            false
-        );
-        let index_buffer = VkDataBuffer::new(
-            "Mesh Indices",
-            app,
-            &indices,
-            vk::BufferUsageFlags::INDEX_BUFFER | usage_flags,
-            vk::MemoryPropertyFlags::DEVICE_LOCAL,
-            false
-        );
+        // The indices of every level of detail are stored back to back in a single allocation
+        let mut indices = mesh.indices.clone();
+        let mut lod_ranges = vec![(0, mesh.indices.len() as u32)];
+        for lod in &mesh.lods {
+            lod_ranges.push((indices.len() as u32, lod.indices.len() as u32));
+            indices.extend_from_slice(&lod.indices);
+        }
 
-        let blas = VkBlas::new(
-            &vertex_buffer,
-            &index_buffer,
-            vk::BuildAccelerationStructureFlagsKHR::PREFER_FAST_BUILD | vk::BuildAccelerationStructureFlagsKHR::ALLOW_COMPACTION
-        );
-        VkBlas::build(app, &vec![blas.clone()], vk::AccelerationStructureBuildTypeKHR::DEVICE);
+        let allocation = match vertex_layout {
+            VertexLayout::Full => arena.as_mut().allocate(app, &mesh.vertices, &indices),
+            VertexLayout::Compact => {
+                let compact_vertices: Vec<CompactVertex> = mesh.vertices.iter().map(CompactVertex::from).collect();
+                arena.as_mut().allocate(app, &compact_vertices, &indices)
+            }
+        };
+
+        // The arena hands out an empty allocation when the vertices or indices are missing, there is nothing to draw
+        if allocation.index_count == 0 {
+            lod_ranges.iter_mut().for_each(|range| *range = (0, 0));
+        }
+
+        let mut lods = Vec::with_capacity(lod_ranges.len());
+        for (offset, index_count) in lod_ranges {
+            // Only triangles can be ray traced
+            let blas = if mesh.kind == MeshKind::Triangles && index_count > 0 {
+                let arena = arena.as_ref();
+                Some(VkBlas::new(
+                    arena.get_vertex_address(&allocation),
+                    allocation.vertex_count,
+                    arena.get_vertex_stride(),
+                    // Both vertex layouts start with full precision positions
+                    vk::Format::R32G32B32_SFLOAT,
+                    arena.get_index_address(&allocation) + offset as u64 * 4,
+                    index_count,
+                    vk::BuildAccelerationStructureFlagsKHR::PREFER_FAST_BUILD | vk::BuildAccelerationStructureFlagsKHR::ALLOW_COMPACTION
+                ))
+            } else {
+                None
+            };
+
+            lods.push(VkMeshLod {
+                first_index: allocation.first_index + offset,
+                index_count: index_count,
+                blas: blas
+            });
+        }
+
+        let blases: Vec<ArcMutex<VkBlas>> = lods.iter().filter_map(|lod| lod.blas.as_ref().map(|blas| blas.clone())).collect();
+        if !blases.is_empty() {
+            VkBlas::build(app, &blases, vk::AccelerationStructureBuildTypeKHR::DEVICE);
+        }
 
         VkMesh {
-            vertex_buffer: vertex_buffer,
-            index_buffer: index_buffer,
-            blas: blas
+            arena: arena.clone(),
+            allocation: allocation,
+            lods: lods
         }
     }
 
-    pub fn draw_cmds(&self, cmd_buffer: &mut VkCmdBuffer) {
-        cmd_buffer.bind_vertex_buffer(&self.vertex_buffer);
-        cmd_buffer.bind_index_buffer(&self.index_buffer);
-        cmd_buffer.draw_indexed(self.index_buffer.get_count(), 1, 0, 0, 0);
+    pub fn topology(kind: MeshKind) -> vk::PrimitiveTopology {
+        match kind {
+            MeshKind::Triangles => vk::PrimitiveTopology::TRIANGLE_LIST,
+            MeshKind::Lines => vk::PrimitiveTopology::LINE_LIST,
+            MeshKind::LineStrip => vk::PrimitiveTopology::LINE_STRIP,
+            MeshKind::Points => vk::PrimitiveTopology::POINT_LIST
+        }
     }
 
-    pub fn get_blas(&self) -> ArcMutex<VkBlas> {
-        self.blas.clone()
+    pub fn draw_cmds(&self, cmd_buffer: &mut VkCmdBuffer, lod: usize) {
+        let lod = &self.lods[lod];
+
+        cmd_buffer.bind_mesh_arena(&self.arena.as_ref());
+        cmd_buffer.draw_indexed(
+            lod.index_count,
+            1,
+            lod.first_index,
+            self.allocation.vertex_offset,
+            0
+        );
     }
-}
\ No newline at end of file
+
+    pub fn get_allocation(&self) -> &VkMeshAllocation {
+        &self.allocation
+    }
+
+    /// Level 0 is the full resolution mesh
+    pub fn get_lod(&self, lod: usize) -> &VkMeshLod {
+        &self.lods[lod]
+    }
+
+    /// None for line and point meshes
+    pub fn get_blas(&self, lod: usize) -> Option<ArcMutex<VkBlas>> {
+        self.lods[lod].blas.as_ref().map(|blas| blas.clone())
+    }
+
+    /// The blases of every level of detail
+    pub fn get_blases(&self) -> Vec<ArcMutex<VkBlas>> {
+        self.lods.iter().filter_map(|lod| lod.blas.as_ref().map(|blas| blas.clone())).collect()
+    }
+
+    /// Device memory of the vertices and indices in the arena, plus the blases
+    pub fn get_memory_size(&self) -> vk::DeviceSize {
+        let vertex_size = self.allocation.vertex_count as vk::DeviceSize * self.arena.as_ref().get_vertex_stride() as vk::DeviceSize;
+        let index_size = self.allocation.index_count as vk::DeviceSize * std::mem::size_of::<u32>() as vk::DeviceSize;
+        let blas_size: vk::DeviceSize = self.get_blases().iter().map(|blas| blas.as_ref().get_memory_size()).sum();
+
+        vertex_size + index_size + blas_size
+    }
+}
+
+impl Drop for VkMesh {
+    fn drop(&mut self) {
+        self.arena.as_mut().release(&self.allocation);
+    }
+}
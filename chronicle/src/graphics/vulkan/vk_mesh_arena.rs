@@ -0,0 +1,246 @@
+use ash::vk;
+
+use crate::graphics::*;
+
+const INITIAL_VERTEX_CAPACITY: u32 = 256 * 1024;
+const INITIAL_INDEX_CAPACITY: u32 = 1024 * 1024;
+
+#[derive(Debug, Clone, Copy)]
+pub struct VkMeshAllocation {
+    pub vertex_offset: u32,
+    pub vertex_count: u32,
+    pub first_index: u32,
+    pub index_count: u32
+}
+
+struct VkArenaRanges {
+    capacity: u32,
+    free: Vec<(u32, u32)> // (offset, count), sorted by offset
+}
+
+impl VkArenaRanges {
+    fn new(capacity: u32) -> Self {
+        VkArenaRanges {
+            capacity: capacity,
+            free: vec![(0, capacity)]
+        }
+    }
+
+    fn allocate(&mut self, count: u32) -> Option<u32> {
+        for i in 0..self.free.len() {
+            let (offset, free_count) = self.free[i];
+            if free_count >= count {
+                if free_count == count {
+                    self.free.remove(i);
+                } else {
+                    self.free[i] = (offset + count, free_count - count);
+                }
+                return Some(offset);
+            }
+        }
+
+        None
+    }
+
+    fn release(&mut self, offset: u32, count: u32) {
+        if count == 0 {
+            return;
+        }
+
+        let i = self.free.partition_point(|range| range.0 < offset);
+        self.free.insert(i, (offset, count));
+
+        // Merge with the next and previous ranges
+        if i + 1 < self.free.len() && self.free[i].0 + self.free[i].1 == self.free[i + 1].0 {
+            self.free[i].1 += self.free[i + 1].1;
+            self.free.remove(i + 1);
+        }
+        if i > 0 && self.free[i - 1].0 + self.free[i - 1].1 == self.free[i].0 {
+            self.free[i - 1].1 += self.free[i].1;
+            self.free.remove(i);
+        }
+    }
+
+    fn grow(&mut self, new_capacity: u32) {
+        let old_capacity = self.capacity;
+        self.capacity = new_capacity;
+        self.release(old_capacity, new_capacity - old_capacity);
+    }
+}
+
+pub struct VkMeshArena {
+    vertex_buffer: Arc<VkBuffer>,
+    index_buffer: Arc<VkBuffer>,
+    vertex_stride: u32,
+
+    vertex_ranges: VkArenaRanges,
+    index_ranges: VkArenaRanges
+}
+
+impl VkMeshArena {
+    pub fn new(app: &mut VkApp, vertex_stride: u32) -> ArcMutex<Self> {
+        ArcMutex::new(VkMeshArena {
+            vertex_buffer: Self::create_buffer(app, "Arena Vertices", INITIAL_VERTEX_CAPACITY as u64 * vertex_stride as u64, vk::BufferUsageFlags::VERTEX_BUFFER),
+            index_buffer: Self::create_buffer(app, "Arena Indices", INITIAL_INDEX_CAPACITY as u64 * 4, vk::BufferUsageFlags::INDEX_BUFFER),
+            vertex_stride: vertex_stride,
+            vertex_ranges: VkArenaRanges::new(INITIAL_VERTEX_CAPACITY),
+            index_ranges: VkArenaRanges::new(INITIAL_INDEX_CAPACITY)
+        })
+    }
+
+    fn create_buffer(app: &mut VkApp, name: &str, size: vk::DeviceSize, usage: vk::BufferUsageFlags) -> Arc<VkBuffer> {
+        Arc::new(VkBuffer::new(
+            format!("{name} BUFFER"),
+            app.get_device(),
+            app.get_allocator(),
+            size,
+            usage
+                | vk::BufferUsageFlags::TRANSFER_SRC
+                | vk::BufferUsageFlags::TRANSFER_DST
+                | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS
+                | vk::BufferUsageFlags::ACCELERATION_STRUCTURE_BUILD_INPUT_READ_ONLY_KHR
+                | vk::BufferUsageFlags::STORAGE_BUFFER,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            None
+        ))
+    }
+
+    pub fn allocate<V>(&mut self, app: &mut VkApp, vertices: &Vec<V>, indices: &Vec<u32>) -> VkMeshAllocation {
+        assert_eq!(std::mem::size_of::<V>() as u32, self.vertex_stride, "Failed to allocate mesh. (Vertex stride mismatch)");
+
+        let vertex_count = vertices.len() as u32;
+        let index_count = indices.len() as u32;
+
+        // Vulkan does not allow empty buffers or copies, a mesh without vertices or indices takes no space
+        if vertex_count == 0 || index_count == 0 {
+            return VkMeshAllocation {
+                vertex_offset: 0,
+                vertex_count: 0,
+                first_index: 0,
+                index_count: 0
+            };
+        }
+
+        let vertex_offset = match self.vertex_ranges.allocate(vertex_count) {
+            Some(offset) => offset,
+            None => {
+                self.grow_vertices(app, vertex_count);
+                self.vertex_ranges.allocate(vertex_count).unwrap()
+            }
+        };
+        let first_index = match self.index_ranges.allocate(index_count) {
+            Some(offset) => offset,
+            None => {
+                self.grow_indices(app, index_count);
+                self.index_ranges.allocate(index_count).unwrap()
+            }
+        };
+
+        let vertex_size = vertex_count as vk::DeviceSize * self.vertex_stride as vk::DeviceSize;
+        let index_size = index_count as vk::DeviceSize * 4;
+
+        let staging_buffer = VkBuffer::new(
+            "Arena STAGING BUFFER".to_owned(),
+            app.get_device(),
+            app.get_allocator(),
+            vertex_size + index_size,
+            vk::BufferUsageFlags::TRANSFER_SRC,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+            None
+        );
+
+        unsafe {
+            let data_ptr = staging_buffer.map() as *mut u8;
+            (data_ptr as *mut V).copy_from_nonoverlapping(vertices.as_ptr(), vertices.len());
+            (data_ptr.add(vertex_size as usize) as *mut u32).copy_from_nonoverlapping(indices.as_ptr(), indices.len());
+            staging_buffer.unmap();
+        }
+
+        let cmd_queue = app.get_cmd_queue();
+        let mut cmd_queue = cmd_queue.as_mut();
+        let cmd_buffer = cmd_queue.get_cmd_buffer(); {
+            let cmd_buffer_ref = cmd_buffer.as_ref();
+            cmd_buffer_ref.begin(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+            cmd_buffer_ref.copy_buffer_regions(&staging_buffer, &self.vertex_buffer, &[vk::BufferCopy {
+                src_offset: 0,
+                dst_offset: vertex_offset as vk::DeviceSize * self.vertex_stride as vk::DeviceSize,
+                size: vertex_size
+            }]);
+            cmd_buffer_ref.copy_buffer_regions(&staging_buffer, &self.index_buffer, &[vk::BufferCopy {
+                src_offset: vertex_size,
+                dst_offset: first_index as vk::DeviceSize * 4,
+                size: index_size
+            }]);
+            cmd_buffer_ref.end();
+        }
+        cmd_queue.submit_cmd_buffer(cmd_buffer, None, None);
+        app.get_device().wait_idle();
+
+        VkMeshAllocation {
+            vertex_offset: vertex_offset,
+            vertex_count: vertex_count,
+            first_index: first_index,
+            index_count: index_count
+        }
+    }
+
+    pub fn release(&mut self, allocation: &VkMeshAllocation) {
+        self.vertex_ranges.release(allocation.vertex_offset, allocation.vertex_count);
+        self.index_ranges.release(allocation.first_index, allocation.index_count);
+    }
+
+    fn grow_vertices(&mut self, app: &mut VkApp, required: u32) {
+        let new_capacity = (self.vertex_ranges.capacity + required).next_power_of_two();
+        let buffer = Self::create_buffer(app, "Arena Vertices", new_capacity as u64 * self.vertex_stride as u64, vk::BufferUsageFlags::VERTEX_BUFFER);
+        Self::copy_into(app, &self.vertex_buffer, &buffer);
+
+        self.vertex_buffer = buffer;
+        self.vertex_ranges.grow(new_capacity);
+    }
+
+    fn grow_indices(&mut self, app: &mut VkApp, required: u32) {
+        let new_capacity = (self.index_ranges.capacity + required).next_power_of_two();
+        let buffer = Self::create_buffer(app, "Arena Indices", new_capacity as u64 * 4, vk::BufferUsageFlags::INDEX_BUFFER);
+        Self::copy_into(app, &self.index_buffer, &buffer);
+
+        self.index_buffer = buffer;
+        self.index_ranges.grow(new_capacity);
+    }
+
+    fn copy_into(app: &mut VkApp, src_buffer: &VkBuffer, dst_buffer: &VkBuffer) {
+        let cmd_queue = app.get_cmd_queue();
+        let mut cmd_queue = cmd_queue.as_mut();
+        let cmd_buffer = cmd_queue.get_cmd_buffer(); {
+            let cmd_buffer_ref = cmd_buffer.as_ref();
+            cmd_buffer_ref.begin(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+            cmd_buffer_ref.copy_buffer_regions(src_buffer, dst_buffer, &[vk::BufferCopy {
+                src_offset: 0,
+                dst_offset: 0,
+                size: src_buffer.get_size()
+            }]);
+            cmd_buffer_ref.end();
+        }
+        cmd_queue.submit_cmd_buffer(cmd_buffer, None, None);
+        app.get_device().wait_idle();
+    }
+
+    pub fn get_vertex_buffer(&self) -> Arc<VkBuffer> {
+        self.vertex_buffer.clone()
+    }
+
+    pub fn get_index_buffer(&self) -> Arc<VkBuffer> {
+        self.index_buffer.clone()
+    }
+
+    pub fn get_vertex_stride(&self) -> u32 {
+        self.vertex_stride
+    }
+
+    pub fn get_vertex_address(&self, allocation: &VkMeshAllocation) -> vk::DeviceAddress {
+        self.vertex_buffer.get_device_address() + allocation.vertex_offset as u64 * self.vertex_stride as u64
+    }
+
+    pub fn get_index_address(&self, allocation: &VkMeshAllocation) -> vk::DeviceAddress {
+        self.index_buffer.get_device_address() + allocation.first_index as u64 * 4
+    }
+}
@@ -3,10 +3,27 @@ use ash::vk;
 use crate::graphics::*;
 use utility::constants::DEVICE_EXTENSIONS;
 
+const TEXTURE_FORMAT_CANDIDATES: [vk::Format; 24] = [
+    vk::Format::R8G8B8A8_UNORM, vk::Format::R8G8B8A8_SRGB,
+    vk::Format::BC1_RGBA_UNORM_BLOCK, vk::Format::BC1_RGBA_SRGB_BLOCK,
+    vk::Format::BC2_UNORM_BLOCK, vk::Format::BC2_SRGB_BLOCK,
+    vk::Format::BC3_UNORM_BLOCK, vk::Format::BC3_SRGB_BLOCK,
+    vk::Format::BC4_UNORM_BLOCK, vk::Format::BC5_UNORM_BLOCK,
+    vk::Format::BC6H_UFLOAT_BLOCK, vk::Format::BC6H_SFLOAT_BLOCK,
+    vk::Format::BC7_UNORM_BLOCK, vk::Format::BC7_SRGB_BLOCK,
+    vk::Format::ETC2_R8G8B8_UNORM_BLOCK, vk::Format::ETC2_R8G8B8_SRGB_BLOCK,
+    vk::Format::ETC2_R8G8B8A8_UNORM_BLOCK, vk::Format::ETC2_R8G8B8A8_SRGB_BLOCK,
+    vk::Format::ASTC_4X4_UNORM_BLOCK, vk::Format::ASTC_4X4_SRGB_BLOCK,
+    vk::Format::EAC_R11_UNORM_BLOCK, vk::Format::EAC_R11G11_UNORM_BLOCK,
+    vk::Format::R16G16B16A16_SFLOAT, vk::Format::R32G32B32A32_SFLOAT
+];
+
 pub struct VkPhysicalDevice {
     device: vk::PhysicalDevice,
     mem_properties: vk::PhysicalDeviceMemoryProperties,
     max_sample_count: vk::SampleCountFlags,
+    features: vk::PhysicalDeviceFeatures,
+    texture_formats: Vec<vk::Format>,
 
     raytracing_pipeline_props: vk::PhysicalDeviceRayTracingPipelinePropertiesKHR,
     accel_props: vk::PhysicalDeviceAccelerationStructurePropertiesKHR
@@ -35,10 +52,29 @@ impl VkPhysicalDevice {
             device
         );
 
+        let features = unsafe {
+            instance.get_instance()
+                .get_physical_device_features(device)
+        };
+
+        let mut texture_formats = Vec::new();
+        for format in TEXTURE_FORMAT_CANDIDATES {
+            let format_properties = unsafe {
+                instance.get_instance()
+                    .get_physical_device_format_properties(device, format)
+            };
+
+            if format_properties.optimal_tiling_features.contains(vk::FormatFeatureFlags::SAMPLED_IMAGE | vk::FormatFeatureFlags::TRANSFER_DST) {
+                texture_formats.push(format);
+            }
+        }
+
         VkPhysicalDevice {
             device,
             mem_properties,
             max_sample_count,
+            features,
+            texture_formats,
             raytracing_pipeline_props,
             accel_props
         }
@@ -101,7 +137,8 @@ impl VkPhysicalDevice {
         return is_queue_family_supported
             && is_device_extension_supported
             && is_swapchain_supported
-            && device_features.sampler_anisotropy > 0;
+            && device_features.sampler_anisotropy > 0
+            && device_features.multi_draw_indirect > 0;
     }
 
     fn check_device_extension_support(
@@ -254,6 +291,15 @@ impl VkPhysicalDevice {
         self.max_sample_count
     }
 
+    pub fn get_features(&self) -> &vk::PhysicalDeviceFeatures {
+        &self.features
+    }
+
+    /// Whether textures of this format can be uploaded and sampled with optimal tiling.
+    pub fn supports_texture_format(&self, format: vk::Format) -> bool {
+        self.texture_formats.contains(&format)
+    }
+
     pub fn get_raytracing_properties(&self) -> &vk::PhysicalDeviceRayTracingPipelinePropertiesKHR {
         &self.raytracing_pipeline_props
     }
@@ -39,16 +39,24 @@ impl VkQueryPool {
         self.count
     }
 
-    pub fn query_results<T: Default + Clone>(&self, count: usize) -> Vec<T> {
+    pub fn query_results<T: Default + Clone>(&self, first_query: u32, count: usize) -> Vec<T> {
+        assert!(first_query as usize + count <= self.count as usize, "Failed to get query results. (Out of bounds)");
+
+        let flags = if std::mem::size_of::<T>() == 8 {
+            vk::QueryResultFlags::WAIT | vk::QueryResultFlags::TYPE_64
+        } else {
+            vk::QueryResultFlags::WAIT
+        };
+
         let mut results = vec![T::default(); count];
         unsafe {
             self.device.get_device()
                 .get_query_pool_results(
                     self.query_pool,
-                    0,
-                    self.count,
+                    first_query,
+                    count as u32,
                     results.as_mut_slice(),
-                    vk::QueryResultFlags::WAIT
+                    flags
                 )
                 .expect("Failed to get query results.");
         }
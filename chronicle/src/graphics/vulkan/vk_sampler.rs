@@ -1,6 +1,7 @@
 use ash::vk;
 
 use crate::graphics::*;
+use crate::resources::{TextureSampler, TextureWrap, TextureFilter};
 
 pub struct VkSampler {
     device: Arc<VkLogicalDevice>,
@@ -8,23 +9,42 @@ pub struct VkSampler {
 }
 
 impl VkSampler {
-    pub fn new(device: Arc<VkLogicalDevice>, texture: &VkTexture) -> Self {
+    pub fn new(device: Arc<VkLogicalDevice>, desc: &TextureSampler) -> Self {
+        let filter = |filter: TextureFilter| match filter {
+            TextureFilter::Nearest => vk::Filter::NEAREST,
+            TextureFilter::Linear => vk::Filter::LINEAR
+        };
+        let address_mode = |wrap: TextureWrap| match wrap {
+            TextureWrap::Repeat => vk::SamplerAddressMode::REPEAT,
+            TextureWrap::MirroredRepeat => vk::SamplerAddressMode::MIRRORED_REPEAT,
+            TextureWrap::ClampToEdge => vk::SamplerAddressMode::CLAMP_TO_EDGE
+        };
+
+        let (mipmap_mode, max_lod) = match desc.mipmap_mode {
+            Some(TextureFilter::Nearest) => (vk::SamplerMipmapMode::NEAREST, vk::LOD_CLAMP_NONE),
+            Some(TextureFilter::Linear) => (vk::SamplerMipmapMode::LINEAR, vk::LOD_CLAMP_NONE),
+            None => (vk::SamplerMipmapMode::NEAREST, 0.0)
+        };
+
+        // Anisotropy would blur nearest filtered (pixel art) textures
+        let anisotropy_enable = desc.min_filter == TextureFilter::Linear && desc.mipmap_mode.is_some();
+
         let sampler_create_info = vk::SamplerCreateInfo {
             s_type: vk::StructureType::SAMPLER_CREATE_INFO,
             p_next: std::ptr::null(),
             flags: vk::SamplerCreateFlags::empty(),
-            mag_filter: vk::Filter::LINEAR,
-            min_filter: vk::Filter::LINEAR,
-            address_mode_u: vk::SamplerAddressMode::REPEAT,
-            address_mode_v: vk::SamplerAddressMode::REPEAT,
+            mag_filter: filter(desc.mag_filter),
+            min_filter: filter(desc.min_filter),
+            address_mode_u: address_mode(desc.wrap_s),
+            address_mode_v: address_mode(desc.wrap_t),
             address_mode_w: vk::SamplerAddressMode::REPEAT,
-            anisotropy_enable: vk::TRUE,
+            anisotropy_enable: if anisotropy_enable { vk::TRUE } else { vk::FALSE },
             max_anisotropy: 16.0,
             compare_enable: vk::FALSE,
             compare_op: vk::CompareOp::ALWAYS,
-            mipmap_mode: vk::SamplerMipmapMode::LINEAR,
+            mipmap_mode: mipmap_mode,
             min_lod: 0.0,
-            max_lod: texture.mip_levels() as f32,
+            max_lod: max_lod,
             mip_lod_bias: 0.0,
             border_color: vk::BorderColor::INT_OPAQUE_BLACK,
             unnormalized_coordinates: vk::FALSE,
@@ -10,7 +10,7 @@ pub struct VkShaderModule {
 impl VkShaderModule {
     pub fn new(device: Arc<VkLogicalDevice>, name: String) -> Self {
         let shader_code = app().resources()
-            .get_binary_blob(format!("assets/builtin/shaders/bin/{name}.spv"));
+            .get_binary_blob(format!("builtin://shaders/bin/{name}.spv"));
 
         let create_info = vk::ShaderModuleCreateInfo {
             s_type: vk::StructureType::SHADER_MODULE_CREATE_INFO,
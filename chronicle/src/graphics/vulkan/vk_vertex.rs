@@ -1,9 +1,10 @@
 use ash::vk;
 use memoffset::offset_of;
 
-use crate::resources::Vertex;
+use crate::resources::{Vertex, CompactVertex};
 
 pub struct VkVertex;
+pub struct VkCompactVertex;
 
 pub trait VkVertexDescs {
     fn get_binding_desc() -> Vec<vk::VertexInputBindingDescription>;
@@ -59,4 +60,56 @@ impl VkVertexDescs for VkVertex {
             }
         ].to_vec()
     }
+}
+
+/// Same locations as `VkVertex`, the vertex input unpacks the quantised attributes to floats so shaders work with either layout
+impl VkVertexDescs for VkCompactVertex {
+    fn get_binding_desc() -> Vec<vk::VertexInputBindingDescription> {
+        [vk::VertexInputBindingDescription {
+            binding: 0,
+            stride: std::mem::size_of::<CompactVertex>() as u32,
+            input_rate: vk::VertexInputRate::VERTEX,
+        }].to_vec()
+    }
+
+    fn get_attribute_desc() -> Vec<vk::VertexInputAttributeDescription> {
+        [
+            vk::VertexInputAttributeDescription {
+                location: 0,
+                binding: 0,
+                format: vk::Format::R32G32B32_SFLOAT,
+                offset: offset_of!(CompactVertex, position) as u32,
+            },
+            vk::VertexInputAttributeDescription {
+                binding: 0,
+                location: 1,
+                format: vk::Format::R8G8B8A8_SNORM,
+                offset: offset_of!(CompactVertex, normal) as u32,
+            },
+            vk::VertexInputAttributeDescription {
+                binding: 0,
+                location: 2,
+                format: vk::Format::R8G8B8A8_SNORM,
+                offset: offset_of!(CompactVertex, tangent) as u32,
+            },
+            vk::VertexInputAttributeDescription {
+                binding: 0,
+                location: 3,
+                format: vk::Format::R16G16_SFLOAT,
+                offset: offset_of!(CompactVertex, tex_coord) as u32,
+            },
+            vk::VertexInputAttributeDescription {
+                binding: 0,
+                location: 4,
+                format: vk::Format::R16G16_SFLOAT,
+                offset: offset_of!(CompactVertex, tex_coord_1) as u32,
+            },
+            vk::VertexInputAttributeDescription {
+                binding: 0,
+                location: 5,
+                format: vk::Format::R8G8B8A8_UNORM,
+                offset: offset_of!(CompactVertex, color) as u32,
+            }
+        ].to_vec()
+    }
 }
\ No newline at end of file
@@ -0,0 +1,122 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use super::Binding;
+
+/// Named actions and axes with the inputs bound to them.
+/// An action is held while any of its bindings has a positive value, an axis is the sum of its bindings
+#[derive(Debug, Clone, Default)]
+pub struct ActionMap {
+    bindings: BTreeMap<String, Vec<Binding>>
+}
+
+impl ActionMap {
+    pub fn new() -> Self {
+        ActionMap {
+            bindings: BTreeMap::new()
+        }
+    }
+
+    /// Adds a binding to an action, next to the ones it already has
+    pub fn bind(&mut self, name: &str, binding: Binding) {
+        self.bindings.entry(String::from(name))
+            .or_default()
+            .push(binding);
+    }
+
+    /// Replaces every binding of an action
+    pub fn rebind(&mut self, name: &str, bindings: Vec<Binding>) {
+        self.bindings.insert(String::from(name), bindings);
+    }
+
+    pub fn unbind(&mut self, name: &str) {
+        self.bindings.remove(name);
+    }
+
+    pub fn get_bindings(&self, name: &str) -> &[Binding] {
+        self.bindings.get(name).map(|bindings| bindings.as_slice()).unwrap_or(&[])
+    }
+
+    pub fn get_names(&self) -> Vec<&String> {
+        self.bindings.keys().collect()
+    }
+
+    /// One `name = binding` per line, lines starting with `#` are comments
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut action_map = ActionMap::new();
+
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (name, binding) = line.split_once('=')
+                .ok_or(format!("Line {}: missing '='", i + 1))?;
+            let binding = Binding::parse(binding)
+                .map_err(|error| format!("Line {}: {}", i + 1, error))?;
+            action_map.bind(name.trim(), binding);
+        }
+
+        Ok(action_map)
+    }
+
+    pub fn to_config(&self) -> String {
+        let mut text = String::new();
+        for (name, bindings) in self.bindings.iter() {
+            for binding in bindings {
+                text += &format!("{} = {}\n", name, binding.to_config());
+            }
+        }
+
+        text
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path.as_ref())
+            .map_err(|error| format!("Failed to read input config \"{:?}\" ({})", path.as_ref(), error))?;
+        Self::parse(&text)
+            .map_err(|error| format!("Failed to parse input config \"{:?}\" ({})", path.as_ref(), error))
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) {
+        std::fs::write(path.as_ref(), self.to_config())
+            .expect(&format!("Failed to write input config \"{:?}\"", path.as_ref()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::{InputSource, VirtualKeyCode, MouseButton};
+
+    #[test]
+    fn action_maps_round_trip_through_config() {
+        let text = "
+            # Movement
+            move_x = key:D
+            move_x = key:A scale=-1
+
+            save = key:S modifiers=ctrl
+            pick = mouse:Left
+        ";
+        let action_map = ActionMap::parse(text).unwrap();
+        assert_eq!(action_map.get_names(), vec!["move_x", "pick", "save"]);
+        assert_eq!(action_map.get_bindings("move_x"), &[
+            Binding::key(VirtualKeyCode::D),
+            Binding::key(VirtualKeyCode::A).with_scale(-1.0)
+        ]);
+        assert_eq!(action_map.get_bindings("pick")[0].source, InputSource::MouseButton(MouseButton::Left));
+        assert!(action_map.get_bindings("missing").is_empty());
+
+        let config = action_map.to_config();
+        assert_eq!(config, "move_x = key:D\nmove_x = key:A scale=-1\npick = mouse:Left\nsave = key:S modifiers=ctrl\n");
+        assert_eq!(ActionMap::parse(&config).unwrap().to_config(), config);
+    }
+
+    #[test]
+    fn parse_errors_name_the_line() {
+        assert_eq!(ActionMap::parse("pick = mouse:Left\njump key:Space").unwrap_err(), "Line 2: missing '='");
+        assert!(ActionMap::parse("jump = key:Nope").unwrap_err().starts_with("Line 1: "));
+    }
+}
\ No newline at end of file
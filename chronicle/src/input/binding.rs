@@ -0,0 +1,338 @@
+use winit::event::{VirtualKeyCode, MouseButton, ModifiersState};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MouseAxis {
+    /// Horizontal movement this frame, positive to the right
+    X,
+    /// Vertical movement this frame, positive downwards
+    Y,
+    /// Scrolled lines this frame, positive when scrolling away from the user
+    Wheel
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GamepadButton {
+    South,
+    East,
+    North,
+    West,
+    LeftBumper,
+    RightBumper,
+    LeftTrigger,
+    RightTrigger,
+    Select,
+    Start,
+    Mode,
+    LeftStick,
+    RightStick,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight
+}
+
+impl GamepadButton {
+    pub const ALL: [GamepadButton; 17] = [
+        GamepadButton::South, GamepadButton::East, GamepadButton::North, GamepadButton::West,
+        GamepadButton::LeftBumper, GamepadButton::RightBumper, GamepadButton::LeftTrigger, GamepadButton::RightTrigger,
+        GamepadButton::Select, GamepadButton::Start, GamepadButton::Mode,
+        GamepadButton::LeftStick, GamepadButton::RightStick,
+        GamepadButton::DPadUp, GamepadButton::DPadDown, GamepadButton::DPadLeft, GamepadButton::DPadRight
+    ];
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GamepadAxis {
+    /// -1 to 1, positive to the right
+    LeftStickX,
+    /// -1 to 1, positive upwards
+    LeftStickY,
+    RightStickX,
+    RightStickY,
+    /// 0 to 1
+    LeftTrigger,
+    RightTrigger
+}
+
+impl GamepadAxis {
+    pub const ALL: [GamepadAxis; 6] = [
+        GamepadAxis::LeftStickX, GamepadAxis::LeftStickY,
+        GamepadAxis::RightStickX, GamepadAxis::RightStickY,
+        GamepadAxis::LeftTrigger, GamepadAxis::RightTrigger
+    ];
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum InputSource {
+    Key(VirtualKeyCode),
+    MouseButton(MouseButton),
+    MouseAxis(MouseAxis),
+    GamepadButton(GamepadButton),
+    GamepadAxis(GamepadAxis)
+}
+
+impl InputSource {
+    /// Written as `key:W`, `mouse:Left`, `mouse_axis:X`, `gamepad:South` or `gamepad_axis:LeftStickX`
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let (kind, name) = text.split_once(':')
+            .ok_or(format!("Missing ':' in input \"{}\"", text))?;
+
+        let source = match kind {
+            "key" => key_from_name(name).map(InputSource::Key),
+            "mouse" => mouse_button_from_name(name).map(InputSource::MouseButton),
+            "mouse_axis" => [MouseAxis::X, MouseAxis::Y, MouseAxis::Wheel].into_iter()
+                .find(|axis| format!("{:?}", axis) == name)
+                .map(InputSource::MouseAxis),
+            "gamepad" => GamepadButton::ALL.into_iter()
+                .find(|button| format!("{:?}", button) == name)
+                .map(InputSource::GamepadButton),
+            "gamepad_axis" => GamepadAxis::ALL.into_iter()
+                .find(|axis| format!("{:?}", axis) == name)
+                .map(InputSource::GamepadAxis),
+            _ => return Err(format!("Unknown input kind \"{}\"", kind))
+        };
+
+        source.ok_or(format!("Unknown input \"{}\"", text))
+    }
+
+    pub fn to_config(&self) -> String {
+        match self {
+            InputSource::Key(key_code) => format!("key:{:?}", key_code),
+            InputSource::MouseButton(MouseButton::Other(i)) => format!("mouse:Other{}", i),
+            InputSource::MouseButton(button) => format!("mouse:{:?}", button),
+            InputSource::MouseAxis(axis) => format!("mouse_axis:{:?}", axis),
+            InputSource::GamepadButton(button) => format!("gamepad:{:?}", button),
+            InputSource::GamepadAxis(axis) => format!("gamepad_axis:{:?}", axis)
+        }
+    }
+}
+
+/// Ties an input to an action or axis
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Binding {
+    pub source: InputSource,
+    /// Modifier keys that have to be held as well, like control for control+s
+    pub modifiers: ModifiersState,
+    /// Analog values closer to zero than this are ignored
+    pub dead_zone: f32,
+    /// Multiplies the value, a negative scale makes a key push an axis the other way
+    pub scale: f32
+}
+
+impl Binding {
+    pub fn new(source: InputSource) -> Self {
+        Binding {
+            source: source,
+            modifiers: ModifiersState::empty(),
+            dead_zone: 0.0,
+            scale: 1.0
+        }
+    }
+
+    pub fn key(key_code: VirtualKeyCode) -> Self {
+        Self::new(InputSource::Key(key_code))
+    }
+
+    pub fn mouse_button(button: MouseButton) -> Self {
+        Self::new(InputSource::MouseButton(button))
+    }
+
+    pub fn mouse_axis(axis: MouseAxis) -> Self {
+        Self::new(InputSource::MouseAxis(axis))
+    }
+
+    pub fn gamepad_button(button: GamepadButton) -> Self {
+        Self::new(InputSource::GamepadButton(button))
+    }
+
+    pub fn gamepad_axis(axis: GamepadAxis) -> Self {
+        Self::new(InputSource::GamepadAxis(axis))
+    }
+
+    pub fn with_modifiers(mut self, modifiers: ModifiersState) -> Self {
+        self.modifiers = modifiers;
+        self
+    }
+
+    pub fn with_dead_zone(mut self, dead_zone: f32) -> Self {
+        self.dead_zone = dead_zone;
+        self
+    }
+
+    pub fn with_scale(mut self, scale: f32) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    /// Applies the dead zone and scale to a raw value of the source.
+    /// Gamepad axes are rescaled so they still start at 0 right past the dead zone and end at 1
+    pub fn apply(&self, value: f32) -> f32 {
+        if value.abs() <= self.dead_zone {
+            return 0.0;
+        }
+
+        let value = match self.source {
+            InputSource::GamepadAxis(_) if self.dead_zone < 1.0 => {
+                value.signum() * (value.abs() - self.dead_zone) / (1.0 - self.dead_zone)
+            },
+            _ => value
+        };
+
+        value * self.scale
+    }
+
+    /// Written as the source followed by optional `modifiers=ctrl+shift`, `dead_zone=0.2` and `scale=-1`
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut tokens = text.split_whitespace();
+        let source = tokens.next().ok_or(String::from("Missing input"))?;
+        let mut binding = Binding::new(InputSource::parse(source)?);
+
+        for token in tokens {
+            let (option, value) = token.split_once('=')
+                .ok_or(format!("Missing '=' in option \"{}\"", token))?;
+
+            match option {
+                "modifiers" => {
+                    for modifier in value.split('+') {
+                        binding.modifiers |= match modifier {
+                            "shift" => ModifiersState::SHIFT,
+                            "ctrl" => ModifiersState::CTRL,
+                            "alt" => ModifiersState::ALT,
+                            "logo" => ModifiersState::LOGO,
+                            _ => return Err(format!("Unknown modifier \"{}\"", modifier))
+                        };
+                    }
+                },
+                "dead_zone" => binding.dead_zone = value.parse()
+                    .map_err(|_| format!("Invalid dead zone \"{}\"", value))?,
+                "scale" => binding.scale = value.parse()
+                    .map_err(|_| format!("Invalid scale \"{}\"", value))?,
+                _ => return Err(format!("Unknown option \"{}\"", option))
+            }
+        }
+
+        Ok(binding)
+    }
+
+    pub fn to_config(&self) -> String {
+        let mut text = self.source.to_config();
+
+        if !self.modifiers.is_empty() {
+            let modifiers: Vec<&str> = [
+                (ModifiersState::SHIFT, "shift"),
+                (ModifiersState::CTRL, "ctrl"),
+                (ModifiersState::ALT, "alt"),
+                (ModifiersState::LOGO, "logo")
+            ].into_iter()
+                .filter(|(modifier, _)| self.modifiers.contains(*modifier))
+                .map(|(_, name)| name)
+                .collect();
+            text += &format!(" modifiers={}", modifiers.join("+"));
+        }
+        if self.dead_zone != 0.0 {
+            text += &format!(" dead_zone={}", self.dead_zone);
+        }
+        if self.scale != 1.0 {
+            text += &format!(" scale={}", self.scale);
+        }
+
+        text
+    }
+}
+
+/// Every key code winit knows, in declaration order
+pub(super) const KEY_CODES: [VirtualKeyCode; 163] = {
+    use VirtualKeyCode::*;
+    [
+        Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9, Key0, A, B, C, D, E, F, G, H, I, J, K, L, M, N,
+        O, P, Q, R, S, T, U, V, W, X, Y, Z, Escape, F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12, F13,
+        F14, F15, F16, F17, F18, F19, F20, F21, F22, F23, F24, Snapshot, Scroll, Pause, Insert, Home, Delete,
+        End, PageDown, PageUp, Left, Up, Right, Down, Back, Return, Space, Compose, Caret, Numlock, Numpad0,
+        Numpad1, Numpad2, Numpad3, Numpad4, Numpad5, Numpad6, Numpad7, Numpad8, Numpad9, NumpadAdd,
+        NumpadDivide, NumpadDecimal, NumpadComma, NumpadEnter, NumpadEquals, NumpadMultiply, NumpadSubtract,
+        AbntC1, AbntC2, Apostrophe, Apps, Asterisk, At, Ax, Backslash, Calculator, Capital, Colon, Comma,
+        Convert, Equals, Grave, Kana, Kanji, LAlt, LBracket, LControl, LShift, LWin, Mail, MediaSelect,
+        MediaStop, Minus, Mute, MyComputer, NavigateForward, NavigateBackward, NextTrack, NoConvert, OEM102,
+        Period, PlayPause, Plus, Power, PrevTrack, RAlt, RBracket, RControl, RShift, RWin, Semicolon, Slash,
+        Sleep, Stop, Sysrq, Tab, Underline, Unlabeled, VolumeDown, VolumeUp, Wake, WebBack, WebFavorites,
+        WebForward, WebHome, WebRefresh, WebSearch, WebStop, Yen, Copy, Paste, Cut
+    ]
+};
+
+fn key_from_name(name: &str) -> Option<VirtualKeyCode> {
+    KEY_CODES.into_iter().find(|key_code| format!("{:?}", key_code) == name)
+}
+
+fn mouse_button_from_name(name: &str) -> Option<MouseButton> {
+    match name {
+        "Left" => Some(MouseButton::Left),
+        "Right" => Some(MouseButton::Right),
+        "Middle" => Some(MouseButton::Middle),
+        // Buttons that the input state has no room for could never be pressed
+        _ => name.strip_prefix("Other")
+            .and_then(|i| i.parse().ok())
+            .map(MouseButton::Other)
+            .filter(|button| super::Input::mb_to_idx(*button).is_some())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bindings_round_trip_through_config() {
+        let configs = [
+            "key:W",
+            "key:S modifiers=shift+ctrl",
+            "mouse:Left",
+            "mouse:Other4 modifiers=alt",
+            "mouse_axis:Wheel scale=-2",
+            "gamepad:DPadUp",
+            "gamepad_axis:LeftStickX dead_zone=0.2 scale=-1"
+        ];
+        for config in configs {
+            let binding = Binding::parse(config).unwrap();
+            assert_eq!(binding.to_config(), config);
+            assert_eq!(Binding::parse(&binding.to_config()).unwrap(), binding);
+        }
+
+        for key_code in KEY_CODES {
+            let source = InputSource::Key(key_code);
+            assert_eq!(InputSource::parse(&source.to_config()), Ok(source));
+        }
+    }
+
+    #[test]
+    fn invalid_bindings_are_errors() {
+        assert!(Binding::parse("").is_err());
+        assert!(Binding::parse("W").is_err());
+        assert!(Binding::parse("key:Nope").is_err());
+        assert!(Binding::parse("joystick:W").is_err());
+        assert!(Binding::parse("key:W modifiers=hyper").is_err());
+        assert!(Binding::parse("key:W scale").is_err());
+        assert!(Binding::parse("key:W dead_zone=much").is_err());
+        assert!(Binding::parse("mouse:Other28").is_ok());
+        assert!(Binding::parse("mouse:Other29").is_err());
+        assert!(Binding::parse("mouse:Other40").is_err());
+        assert!(Binding::parse("mouse:Other70000").is_err());
+    }
+
+    #[test]
+    fn gamepad_axes_are_rescaled_past_the_dead_zone() {
+        let binding = Binding::gamepad_axis(GamepadAxis::LeftStickX).with_dead_zone(0.2);
+        assert_eq!(binding.apply(0.1), 0.0);
+        assert_eq!(binding.apply(-0.2), 0.0);
+        assert!((binding.apply(0.6) - 0.5).abs() < 1e-6);
+        assert!((binding.apply(-0.6) + 0.5).abs() < 1e-6);
+        assert!((binding.apply(1.0) - 1.0).abs() < 1e-6);
+
+        let binding = binding.with_scale(-2.0);
+        assert!((binding.apply(1.0) + 2.0).abs() < 1e-6);
+
+        // Mouse movement has no upper end, only the dead zone is cut off
+        let binding = Binding::mouse_axis(MouseAxis::X).with_dead_zone(0.5);
+        assert_eq!(binding.apply(0.4), 0.0);
+        assert_eq!(binding.apply(3.0), 3.0);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,493 @@
+use std::collections::HashMap;
+use cgmath::{Vector2, InnerSpace};
+use gilrs::ff::{EffectBuilder, Effect, BaseEffect, BaseEffectType, Replay, Repeat, Ticks};
+
+use super::{GamepadButton, GamepadAxis};
+use crate::common::RcCell;
+
+const BUTTON_COUNT: usize = GamepadButton::ALL.len();
+const AXIS_COUNT: usize = GamepadAxis::ALL.len();
+const DEFAULT_STICK_DEAD_ZONE: f32 = 0.15;
+const DEFAULT_TRIGGER_DEAD_ZONE: f32 = 0.05;
+
+/// State of a gamepad that has been connected at some point, it keeps its id when it reconnects
+pub struct Gamepad {
+    name: String,
+    connected: bool,
+    connected_prev: bool,
+    supports_rumble: bool,
+    buttons: [bool; BUTTON_COUNT],
+    buttons_prev: [bool; BUTTON_COUNT],
+    /// Values as reported, before the dead zones
+    axes: [f32; AXIS_COUNT],
+
+    /// Radial dead zone of both sticks, stick values are rescaled to start at 0 right past it
+    pub stick_dead_zone: f32,
+    pub trigger_dead_zone: f32
+}
+
+impl Gamepad {
+    pub(super) fn new() -> Self {
+        Gamepad {
+            name: String::new(),
+            connected: false,
+            connected_prev: false,
+            supports_rumble: false,
+            buttons: [false; BUTTON_COUNT],
+            buttons_prev: [false; BUTTON_COUNT],
+            axes: [0.0; AXIS_COUNT],
+            stick_dead_zone: DEFAULT_STICK_DEAD_ZONE,
+            trigger_dead_zone: DEFAULT_TRIGGER_DEAD_ZONE
+        }
+    }
+
+    pub(super) fn update(&mut self) {
+        self.connected_prev = self.connected;
+        self.buttons_prev = self.buttons;
+    }
+
+    pub(super) fn handle_event(&mut self, event: &GamepadEvent) {
+        match event {
+            GamepadEvent::Connected { name, supports_rumble, .. } => {
+                self.name = name.clone();
+                self.supports_rumble = *supports_rumble;
+                self.connected = true;
+            },
+            GamepadEvent::Disconnected { .. } => {
+                self.connected = false;
+                self.buttons = [false; BUTTON_COUNT];
+                self.axes = [0.0; AXIS_COUNT];
+            },
+            GamepadEvent::Button { button, pressed, .. } => self.buttons[*button as usize] = *pressed,
+            GamepadEvent::Axis { axis, value, .. } => self.axes[*axis as usize] = *value
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connected
+    }
+
+    pub fn just_connected(&self) -> bool {
+        self.connected && !self.connected_prev
+    }
+
+    pub fn just_disconnected(&self) -> bool {
+        !self.connected && self.connected_prev
+    }
+
+    pub fn supports_rumble(&self) -> bool {
+        self.supports_rumble
+    }
+
+    pub fn button(&self, button: GamepadButton) -> bool {
+        self.buttons[button as usize]
+    }
+
+    pub fn button_down(&self, button: GamepadButton) -> bool {
+        self.buttons[button as usize] && !self.buttons_prev[button as usize]
+    }
+
+    pub fn button_up(&self, button: GamepadButton) -> bool {
+        !self.buttons[button as usize] && self.buttons_prev[button as usize]
+    }
+
+    /// Value of an axis after the dead zones
+    pub fn axis(&self, axis: GamepadAxis) -> f32 {
+        match axis {
+            GamepadAxis::LeftStickX => self.left_stick().x,
+            GamepadAxis::LeftStickY => self.left_stick().y,
+            GamepadAxis::RightStickX => self.right_stick().x,
+            GamepadAxis::RightStickY => self.right_stick().y,
+            GamepadAxis::LeftTrigger | GamepadAxis::RightTrigger => {
+                let value = self.raw_axis(axis);
+                match value > self.trigger_dead_zone {
+                    true => (value - self.trigger_dead_zone) / (1.0 - self.trigger_dead_zone).max(f32::EPSILON),
+                    false => 0.0
+                }
+            }
+        }
+    }
+
+    /// Value of an axis as the gamepad reported it
+    pub fn raw_axis(&self, axis: GamepadAxis) -> f32 {
+        self.axes[axis as usize]
+    }
+
+    pub fn left_stick(&self) -> Vector2<f32> {
+        self.stick(GamepadAxis::LeftStickX, GamepadAxis::LeftStickY)
+    }
+
+    pub fn right_stick(&self) -> Vector2<f32> {
+        self.stick(GamepadAxis::RightStickX, GamepadAxis::RightStickY)
+    }
+
+    /// The dead zone is applied to the length of the stick rather than each axis, so diagonals are not snapped to the axes
+    fn stick(&self, x: GamepadAxis, y: GamepadAxis) -> Vector2<f32> {
+        let stick = Vector2::new(self.raw_axis(x), self.raw_axis(y));
+        let magnitude = stick.magnitude();
+        if magnitude <= self.stick_dead_zone {
+            return Vector2::new(0.0, 0.0);
+        }
+
+        let rescaled = (magnitude - self.stick_dead_zone) / (1.0 - self.stick_dead_zone).max(f32::EPSILON);
+        stick / magnitude * rescaled.min(1.0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GamepadEvent {
+    Connected { id: usize, name: String, supports_rumble: bool },
+    Disconnected { id: usize },
+    Button { id: usize, button: GamepadButton, pressed: bool },
+    /// Sticks range from -1 to 1 and are positive to the right and upwards, triggers range from 0 to 1
+    Axis { id: usize, axis: GamepadAxis, value: f32 }
+}
+
+impl GamepadEvent {
+    pub fn get_id(&self) -> usize {
+        match self {
+            GamepadEvent::Connected { id, .. }
+            | GamepadEvent::Disconnected { id }
+            | GamepadEvent::Button { id, .. }
+            | GamepadEvent::Axis { id, .. } => *id
+        }
+    }
+}
+
+/// Where gamepad events come from
+pub trait GamepadBackend {
+    /// Events since the last poll, in the order they happened
+    fn poll(&mut self) -> Vec<GamepadEvent>;
+    /// Vibrates a gamepad with motor strengths from 0 to 1 for `duration` seconds, zero strengths stop it.
+    /// Returns whether the gamepad can rumble
+    fn rumble(&mut self, id: usize, strong: f32, weak: f32, duration: f32) -> bool;
+}
+
+/// Gamepads of the system, evdev on linux
+pub struct GilrsBackend {
+    gilrs: gilrs::Gilrs,
+    /// Playing rumble of every gamepad, dropping an effect stops it
+    rumble_effects: HashMap<usize, Effect>,
+    /// Gamepads that were already connected when the backend was created
+    initial_events: Vec<GamepadEvent>
+}
+
+impl GilrsBackend {
+    /// None when gamepads are not supported on this platform or can not be accessed
+    pub fn new() -> Option<Self> {
+        let gilrs = gilrs::Gilrs::new().ok()?;
+
+        let initial_events = gilrs.gamepads()
+            .map(|(gamepad_id, gamepad)| GamepadEvent::Connected {
+                id: usize::from(gamepad_id),
+                name: String::from(gamepad.name()),
+                supports_rumble: gamepad.is_ff_supported()
+            })
+            .collect();
+
+        Some(GilrsBackend {
+            gilrs: gilrs,
+            rumble_effects: HashMap::new(),
+            initial_events: initial_events
+        })
+    }
+}
+
+impl GamepadBackend for GilrsBackend {
+    fn poll(&mut self) -> Vec<GamepadEvent> {
+        let mut events = std::mem::take(&mut self.initial_events);
+
+        while let Some(gilrs::Event { id: gamepad_id, event, .. }) = self.gilrs.next_event() {
+            let id = usize::from(gamepad_id);
+            match event {
+                gilrs::EventType::Connected => {
+                    let gamepad = self.gilrs.gamepad(gamepad_id);
+                    events.push(GamepadEvent::Connected {
+                        id: id,
+                        name: String::from(gamepad.name()),
+                        supports_rumble: gamepad.is_ff_supported()
+                    });
+                },
+                gilrs::EventType::Disconnected => {
+                    self.rumble_effects.remove(&id);
+                    events.push(GamepadEvent::Disconnected { id: id });
+                },
+                gilrs::EventType::ButtonPressed(button, _) | gilrs::EventType::ButtonReleased(button, _) => {
+                    if let Some(button) = gilrs_to_button(button) {
+                        events.push(GamepadEvent::Button {
+                            id: id,
+                            button: button,
+                            pressed: matches!(event, gilrs::EventType::ButtonPressed(..))
+                        });
+                    }
+                },
+                // Analog triggers report as buttons with a value
+                gilrs::EventType::ButtonChanged(gilrs::Button::LeftTrigger2, value, _) => {
+                    events.push(GamepadEvent::Axis { id: id, axis: GamepadAxis::LeftTrigger, value: value });
+                },
+                gilrs::EventType::ButtonChanged(gilrs::Button::RightTrigger2, value, _) => {
+                    events.push(GamepadEvent::Axis { id: id, axis: GamepadAxis::RightTrigger, value: value });
+                },
+                gilrs::EventType::AxisChanged(axis, value, _) => {
+                    if let Some(axis) = gilrs_to_axis(axis) {
+                        events.push(GamepadEvent::Axis { id: id, axis: axis, value: value });
+                    }
+                },
+                _ => {}
+            }
+        }
+
+        events
+    }
+
+    fn rumble(&mut self, id: usize, strong: f32, weak: f32, duration: f32) -> bool {
+        let gamepad_id = match self.gilrs.gamepads().find(|(gamepad_id, _)| usize::from(*gamepad_id) == id) {
+            Some((gamepad_id, gamepad)) if gamepad.is_ff_supported() => gamepad_id,
+            _ => return false
+        };
+
+        self.rumble_effects.remove(&id);
+        if strong <= 0.0 && weak <= 0.0 {
+            return true;
+        }
+
+        let ticks = Ticks::from_ms((duration * 1000.0) as u32);
+        let scheduling = Replay {
+            play_for: ticks,
+            ..Default::default()
+        };
+        let magnitude = |strength: f32| (strength.clamp(0.0, 1.0) * u16::MAX as f32) as u16;
+
+        let effect = EffectBuilder::new()
+            .add_effect(BaseEffect {
+                kind: BaseEffectType::Strong { magnitude: magnitude(strong) },
+                scheduling: scheduling,
+                ..Default::default()
+            })
+            .add_effect(BaseEffect {
+                kind: BaseEffectType::Weak { magnitude: magnitude(weak) },
+                scheduling: scheduling,
+                ..Default::default()
+            })
+            .gamepads(&[gamepad_id])
+            .repeat(Repeat::For(ticks))
+            .finish(&mut self.gilrs);
+
+        match effect.and_then(|effect| effect.play().map(|_| effect)) {
+            Ok(effect) => {
+                self.rumble_effects.insert(id, effect);
+                true
+            },
+            Err(_) => false
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rumble {
+    pub strong: f32,
+    pub weak: f32,
+    pub duration: f32
+}
+
+#[derive(Default)]
+struct VirtualGamepadState {
+    events: Vec<GamepadEvent>,
+    rumbles: HashMap<usize, Rumble>
+}
+
+/// Gamepads driven from code, so tests and replays do not need hardware.
+/// Clones share their state, keep one to drive the gamepads after handing another to `Input::set_gamepad_backend`
+pub struct VirtualGamepad {
+    state: RcCell<VirtualGamepadState>
+}
+
+impl Clone for VirtualGamepad {
+    fn clone(&self) -> Self {
+        VirtualGamepad {
+            state: self.state.clone()
+        }
+    }
+}
+
+impl Default for VirtualGamepad {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VirtualGamepad {
+    pub fn new() -> Self {
+        VirtualGamepad {
+            state: RcCell::new(VirtualGamepadState::default())
+        }
+    }
+
+    pub fn connect(&self, id: usize, name: &str) {
+        self.state.as_mut().events.push(GamepadEvent::Connected {
+            id: id,
+            name: String::from(name),
+            supports_rumble: true
+        });
+    }
+
+    pub fn disconnect(&self, id: usize) {
+        self.state.as_mut().events.push(GamepadEvent::Disconnected { id: id });
+    }
+
+    pub fn set_button(&self, id: usize, button: GamepadButton, pressed: bool) {
+        self.state.as_mut().events.push(GamepadEvent::Button { id: id, button: button, pressed: pressed });
+    }
+
+    pub fn set_axis(&self, id: usize, axis: GamepadAxis, value: f32) {
+        self.state.as_mut().events.push(GamepadEvent::Axis { id: id, axis: axis, value: value });
+    }
+
+    /// The last rumble requested for a gamepad
+    pub fn get_rumble(&self, id: usize) -> Option<Rumble> {
+        self.state.as_ref().rumbles.get(&id).copied()
+    }
+}
+
+impl GamepadBackend for VirtualGamepad {
+    fn poll(&mut self) -> Vec<GamepadEvent> {
+        std::mem::take(&mut self.state.as_mut().events)
+    }
+
+    fn rumble(&mut self, id: usize, strong: f32, weak: f32, duration: f32) -> bool {
+        self.state.as_mut().rumbles.insert(id, Rumble {
+            strong: strong,
+            weak: weak,
+            duration: duration
+        });
+        true
+    }
+}
+
+fn gilrs_to_button(button: gilrs::Button) -> Option<GamepadButton> {
+    match button {
+        gilrs::Button::South => Some(GamepadButton::South),
+        gilrs::Button::East => Some(GamepadButton::East),
+        gilrs::Button::North => Some(GamepadButton::North),
+        gilrs::Button::West => Some(GamepadButton::West),
+        gilrs::Button::LeftTrigger => Some(GamepadButton::LeftBumper),
+        gilrs::Button::RightTrigger => Some(GamepadButton::RightBumper),
+        gilrs::Button::LeftTrigger2 => Some(GamepadButton::LeftTrigger),
+        gilrs::Button::RightTrigger2 => Some(GamepadButton::RightTrigger),
+        gilrs::Button::Select => Some(GamepadButton::Select),
+        gilrs::Button::Start => Some(GamepadButton::Start),
+        gilrs::Button::Mode => Some(GamepadButton::Mode),
+        gilrs::Button::LeftThumb => Some(GamepadButton::LeftStick),
+        gilrs::Button::RightThumb => Some(GamepadButton::RightStick),
+        gilrs::Button::DPadUp => Some(GamepadButton::DPadUp),
+        gilrs::Button::DPadDown => Some(GamepadButton::DPadDown),
+        gilrs::Button::DPadLeft => Some(GamepadButton::DPadLeft),
+        gilrs::Button::DPadRight => Some(GamepadButton::DPadRight),
+        _ => None
+    }
+}
+
+fn gilrs_to_axis(axis: gilrs::Axis) -> Option<GamepadAxis> {
+    match axis {
+        gilrs::Axis::LeftStickX => Some(GamepadAxis::LeftStickX),
+        gilrs::Axis::LeftStickY => Some(GamepadAxis::LeftStickY),
+        gilrs::Axis::RightStickX => Some(GamepadAxis::RightStickX),
+        gilrs::Axis::RightStickY => Some(GamepadAxis::RightStickY),
+        _ => None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::Input;
+
+    fn virtual_input() -> (Box<Input>, VirtualGamepad) {
+        let mut input = Input::init();
+        let gamepad = VirtualGamepad::new();
+        input.set_gamepad_backend(Box::new(gamepad.clone()));
+        (input, gamepad)
+    }
+
+    #[test]
+    fn connect_and_disconnect() {
+        let (mut input, gamepad) = virtual_input();
+        gamepad.connect(3, "Test pad");
+        input.update();
+        assert_eq!(input.get_connected_gamepads(), vec![3]);
+        assert!(input.get_gamepad(3).unwrap().just_connected());
+        assert_eq!(input.get_gamepad(3).unwrap().get_name(), "Test pad");
+
+        input.update();
+        assert!(!input.get_gamepad(3).unwrap().just_connected());
+
+        gamepad.set_button(3, GamepadButton::South, true);
+        gamepad.disconnect(3);
+        input.update();
+        assert!(input.get_connected_gamepads().is_empty());
+        assert!(input.get_gamepad(3).unwrap().just_disconnected());
+        assert!(!input.gamepad_button(GamepadButton::South));
+    }
+
+    #[test]
+    fn button_states() {
+        let (mut input, gamepad) = virtual_input();
+        gamepad.connect(0, "Test pad");
+        gamepad.set_button(0, GamepadButton::South, true);
+        input.update();
+        assert!(input.gamepad_button(GamepadButton::South));
+        assert!(input.gamepad_button_down(GamepadButton::South));
+
+        input.update();
+        assert!(input.gamepad_button(GamepadButton::South));
+        assert!(!input.gamepad_button_down(GamepadButton::South));
+
+        gamepad.set_button(0, GamepadButton::South, false);
+        input.update();
+        assert!(!input.gamepad_button(GamepadButton::South));
+        assert!(input.gamepad_button_up(GamepadButton::South));
+        assert_eq!(input.pressed_source(), None);
+    }
+
+    #[test]
+    fn sticks_and_triggers_have_dead_zones() {
+        let (mut input, gamepad) = virtual_input();
+        gamepad.connect(0, "Test pad");
+        gamepad.set_axis(0, GamepadAxis::LeftStickX, 0.1);
+        gamepad.set_axis(0, GamepadAxis::LeftStickY, 0.1);
+        gamepad.set_axis(0, GamepadAxis::RightStickX, -1.0);
+        gamepad.set_axis(0, GamepadAxis::LeftTrigger, 0.03);
+        gamepad.set_axis(0, GamepadAxis::RightTrigger, 1.0);
+        input.update();
+
+        let pad = input.get_gamepad(0).unwrap();
+        assert_eq!(pad.left_stick(), Vector2::new(0.0, 0.0));
+        assert!((pad.raw_axis(GamepadAxis::LeftStickX) - 0.1).abs() < 1e-6);
+        assert!((input.gamepad_axis(GamepadAxis::RightStickX) + 1.0).abs() < 1e-6);
+        assert_eq!(input.gamepad_axis(GamepadAxis::LeftTrigger), 0.0);
+        assert!((input.gamepad_axis(GamepadAxis::RightTrigger) - 1.0).abs() < 1e-6);
+
+        // A diagonal past the dead zone keeps its direction
+        input.get_gamepad_mut(0).unwrap().stick_dead_zone = 0.5;
+        gamepad.set_axis(0, GamepadAxis::LeftStickX, 0.6);
+        gamepad.set_axis(0, GamepadAxis::LeftStickY, 0.8);
+        input.update();
+        let stick = input.get_gamepad(0).unwrap().left_stick();
+        assert!((stick - Vector2::new(0.6, 0.8)).magnitude() < 1e-6);
+    }
+
+    #[test]
+    fn rumble_reaches_connected_gamepads() {
+        let (mut input, gamepad) = virtual_input();
+        assert!(!input.rumble(0, 1.0, 0.5, 0.2));
+
+        gamepad.connect(0, "Test pad");
+        input.update();
+        assert!(input.rumble(0, 1.0, 0.5, 0.2));
+        assert_eq!(gamepad.get_rumble(0), Some(Rumble { strong: 1.0, weak: 0.5, duration: 0.2 }));
+    }
+}
\ No newline at end of file
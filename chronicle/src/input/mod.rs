@@ -1,10 +1,20 @@
-pub use winit::event::{MouseButton, VirtualKeyCode, ElementState, KeyboardInput, WindowEvent};
+pub use winit::event::{MouseButton, MouseScrollDelta, VirtualKeyCode, ElementState, KeyboardInput, WindowEvent, ModifiersState};
+use std::collections::{HashSet, BTreeMap};
 use cgmath::Vector2;
 
+pub mod binding;
+pub use binding::*;
+pub mod action_map;
+pub use action_map::*;
+pub mod gamepad;
+pub use gamepad::*;
+
 use crate::app;
 
 const MAX_KEYS: usize = 512;
 const MAX_BUTTONS: usize = 32;
+/// Touchpads report scrolling in pixels, this converts them to mouse wheel lines
+const PIXELS_PER_SCROLL_LINE: f32 = 20.0;
 
 #[derive(Copy, Clone, Debug)]
 pub enum CursorMode {
@@ -19,7 +29,15 @@ pub struct Input {
     buttons_prev: [bool; MAX_BUTTONS],
     mouse_pos: Vector2<i32>,
     mouse_delta: Vector2<f32>,
-    cursor_mode: CursorMode
+    mouse_scroll: f32,
+    /// Every gamepad seen since the backend was set, disconnected ones keep their id and settings
+    gamepads: BTreeMap<usize, Gamepad>,
+    gamepad_backend: Option<Box<dyn GamepadBackend>>,
+    cursor_mode: CursorMode,
+
+    action_map: ActionMap,
+    /// Actions that were held during the previous frame
+    actions_prev: HashSet<String>
 }
 
 impl Input {
@@ -31,14 +49,40 @@ impl Input {
             buttons_prev: [false; MAX_BUTTONS],
             mouse_pos: Vector2::new(0, 0),
             mouse_delta: Vector2::new(0.0, 0.0),
-            cursor_mode: CursorMode::FREE
+            mouse_scroll: 0.0,
+            gamepads: BTreeMap::new(),
+            gamepad_backend: GilrsBackend::new()
+                .map(|backend| Box::new(backend) as Box<dyn GamepadBackend>),
+            cursor_mode: CursorMode::FREE,
+
+            action_map: ActionMap::new(),
+            actions_prev: HashSet::new()
         })
     }
 
     pub(crate) fn update(&mut self) {
+        // Evaluated before the previous state is overwritten, mouse axes only have a value until then
+        self.actions_prev = self.action_map.get_names().into_iter()
+            .filter(|name| self.action(name))
+            .cloned()
+            .collect();
+
         self.keys_prev = self.keys.clone();
         self.buttons_prev = self.buttons.clone();
         self.mouse_delta = Vector2::new(0.0, 0.0);
+        self.mouse_scroll = 0.0;
+
+        for gamepad in self.gamepads.values_mut() {
+            gamepad.update();
+        }
+        // Polled here so the events show up during the next frame
+        if let Some(backend) = self.gamepad_backend.as_mut() {
+            for event in backend.poll() {
+                self.gamepads.entry(event.get_id())
+                    .or_insert_with(Gamepad::new)
+                    .handle_event(&event);
+            }
+        }
     }
 
     pub fn key(&self, key_code: VirtualKeyCode) -> bool {
@@ -49,12 +93,83 @@ impl Input {
         self.keys[key_code as usize] && !self.keys_prev[key_code as usize]
     }
 
+    pub fn key_up(&self, key_code: VirtualKeyCode) -> bool {
+        !self.keys[key_code as usize] && self.keys_prev[key_code as usize]
+    }
+
+    /// Modifier keys that are held, on either side of the keyboard
+    pub fn modifiers(&self) -> ModifiersState {
+        let mut modifiers = ModifiersState::empty();
+        modifiers.set(ModifiersState::SHIFT, self.key(VirtualKeyCode::LShift) || self.key(VirtualKeyCode::RShift));
+        modifiers.set(ModifiersState::CTRL, self.key(VirtualKeyCode::LControl) || self.key(VirtualKeyCode::RControl));
+        modifiers.set(ModifiersState::ALT, self.key(VirtualKeyCode::LAlt) || self.key(VirtualKeyCode::RAlt));
+        modifiers.set(ModifiersState::LOGO, self.key(VirtualKeyCode::LWin) || self.key(VirtualKeyCode::RWin));
+        modifiers
+    }
+
     pub fn mouse_button(&self, button: MouseButton) -> bool {
-        self.buttons[Self::mb_to_idx(button)]
+        Self::mb_to_idx(button).map(|idx| self.buttons[idx]).unwrap_or(false)
     }
 
     pub fn mouse_button_down(&self, button: MouseButton) -> bool {
-        self.buttons[Self::mb_to_idx(button)] && !self.buttons_prev[Self::mb_to_idx(button)]
+        Self::mb_to_idx(button).map(|idx| self.buttons[idx] && !self.buttons_prev[idx]).unwrap_or(false)
+    }
+
+    pub fn mouse_button_up(&self, button: MouseButton) -> bool {
+        Self::mb_to_idx(button).map(|idx| !self.buttons[idx] && self.buttons_prev[idx]).unwrap_or(false)
+    }
+
+    /// Whether the button is held on any gamepad
+    pub fn gamepad_button(&self, button: GamepadButton) -> bool {
+        self.gamepads.values().any(|gamepad| gamepad.button(button))
+    }
+
+    pub fn gamepad_button_down(&self, button: GamepadButton) -> bool {
+        self.gamepads.values().any(|gamepad| gamepad.button_down(button))
+    }
+
+    pub fn gamepad_button_up(&self, button: GamepadButton) -> bool {
+        self.gamepads.values().any(|gamepad| gamepad.button_up(button))
+    }
+
+    /// The axis of the gamepad that pushes it furthest, after the dead zones of each gamepad
+    pub fn gamepad_axis(&self, axis: GamepadAxis) -> f32 {
+        self.gamepads.values()
+            .map(|gamepad| gamepad.axis(axis))
+            .fold(0.0, |furthest, value| if value.abs() > furthest.abs() { value } else { furthest })
+    }
+
+    pub fn get_gamepad(&self, id: usize) -> Option<&Gamepad> {
+        self.gamepads.get(&id)
+    }
+
+    /// For changing the dead zones of a gamepad
+    pub fn get_gamepad_mut(&mut self, id: usize) -> Option<&mut Gamepad> {
+        self.gamepads.get_mut(&id)
+    }
+
+    /// Ids of the gamepads that are connected, in increasing order
+    pub fn get_connected_gamepads(&self) -> Vec<usize> {
+        self.gamepads.iter()
+            .filter(|(_, gamepad)| gamepad.is_connected())
+            .map(|(id, _)| *id)
+            .collect()
+    }
+
+    /// Vibrates a gamepad with motor strengths from 0 to 1 for `duration` seconds, zero strengths stop it.
+    /// Returns false when the gamepad is not connected or can not rumble
+    pub fn rumble(&mut self, id: usize, strong: f32, weak: f32, duration: f32) -> bool {
+        let connected = self.gamepads.get(&id).map(|gamepad| gamepad.is_connected()).unwrap_or(false);
+        match self.gamepad_backend.as_mut() {
+            Some(backend) if connected => backend.rumble(id, strong, weak, duration),
+            _ => false
+        }
+    }
+
+    /// Replaces where gamepad events come from, like a `VirtualGamepad`. The current gamepads are forgotten
+    pub fn set_gamepad_backend(&mut self, backend: Box<dyn GamepadBackend>) {
+        self.gamepads.clear();
+        self.gamepad_backend = Some(backend);
     }
 
     pub fn mouse_pos(&self) -> Vector2<i32> {
@@ -65,6 +180,99 @@ impl Input {
         self.mouse_delta
     }
 
+    /// Scrolled lines this frame, positive when scrolling away from the user
+    pub fn mouse_scroll(&self) -> f32 {
+        self.mouse_scroll
+    }
+
+    /// Raw value of an input, 0 or 1 for keys and buttons
+    pub fn source_value(&self, source: InputSource) -> f32 {
+        match source {
+            InputSource::Key(key_code) => self.key(key_code) as u8 as f32,
+            InputSource::MouseButton(button) => self.mouse_button(button) as u8 as f32,
+            InputSource::MouseAxis(MouseAxis::X) => self.mouse_delta.x,
+            InputSource::MouseAxis(MouseAxis::Y) => self.mouse_delta.y,
+            InputSource::MouseAxis(MouseAxis::Wheel) => self.mouse_scroll,
+            InputSource::GamepadButton(button) => self.gamepad_button(button) as u8 as f32,
+            InputSource::GamepadAxis(axis) => self.gamepad_axis(axis)
+        }
+    }
+
+    /// Value of an input after the dead zone and scale of the binding, 0 while its modifiers are not held.
+    /// The most specific binding of an input wins, plain S is 0 while control+s is held and bound as well
+    pub fn binding_value(&self, binding: &Binding) -> f32 {
+        let modifiers = self.modifiers();
+        if !modifiers.contains(binding.modifiers) {
+            return 0.0;
+        }
+
+        let overridden = self.action_map.get_names().into_iter()
+            .flat_map(|name| self.action_map.get_bindings(name))
+            .any(|other| {
+                other.source == binding.source && other.modifiers != binding.modifiers
+                    && other.modifiers.contains(binding.modifiers) && modifiers.contains(other.modifiers)
+            });
+        if overridden {
+            return 0.0;
+        }
+
+        binding.apply(self.source_value(binding.source))
+    }
+
+    /// Whether the action is held
+    pub fn action(&self, name: &str) -> bool {
+        self.action_map.get_bindings(name).iter()
+            .any(|binding| self.binding_value(binding) > 0.0)
+    }
+
+    /// Whether the action started being held this frame
+    pub fn action_down(&self, name: &str) -> bool {
+        self.action(name) && !self.actions_prev.contains(name)
+    }
+
+    /// Whether the action stopped being held this frame
+    pub fn action_up(&self, name: &str) -> bool {
+        !self.action(name) && self.actions_prev.contains(name)
+    }
+
+    /// Sum of the values of every binding of the axis
+    pub fn axis(&self, name: &str) -> f32 {
+        self.action_map.get_bindings(name).iter()
+            .map(|binding| self.binding_value(binding))
+            .sum()
+    }
+
+    /// The first key or button pressed this frame, for waiting on the input to rebind an action to
+    pub fn pressed_source(&self) -> Option<InputSource> {
+        if let Some(key_code) = binding::KEY_CODES.into_iter().find(|key_code| self.key_down(*key_code)) {
+            return Some(InputSource::Key(key_code));
+        }
+
+        let button = [MouseButton::Left, MouseButton::Right, MouseButton::Middle].into_iter()
+            .chain((0..(MAX_BUTTONS - 3) as u16).map(MouseButton::Other))
+            .find(|button| self.mouse_button_down(*button));
+        if let Some(button) = button {
+            return Some(InputSource::MouseButton(button));
+        }
+
+        GamepadButton::ALL.into_iter()
+            .find(|button| self.gamepad_button_down(*button))
+            .map(InputSource::GamepadButton)
+    }
+
+    pub fn get_action_map(&self) -> &ActionMap {
+        &self.action_map
+    }
+
+    /// Bindings can be changed at any time, an action that stops being held reports `action_up` on the next frame
+    pub fn get_action_map_mut(&mut self) -> &mut ActionMap {
+        &mut self.action_map
+    }
+
+    pub fn set_action_map(&mut self, action_map: ActionMap) {
+        self.action_map = action_map;
+    }
+
     pub fn get_cursor_mode(&self) -> CursorMode {
         self.cursor_mode
     }
@@ -95,12 +303,19 @@ impl Input {
     }
 
     pub(crate) fn set_mouse_button(&mut self, button: MouseButton, value: bool) {
-        self.keys[Self::mb_to_idx(button)] = value;
+        self.set_mouse_button_state(button, value);
 
         let imgui = app().graphics().imgui();
         imgui.mouse_button_event(winit_to_imgui_mouse_button(button), value);
     }
 
+    /// Without forwarding to imgui, which needs a running app. Buttons past `MAX_BUTTONS` are ignored
+    fn set_mouse_button_state(&mut self, button: MouseButton, value: bool) {
+        if let Some(idx) = Self::mb_to_idx(button) {
+            self.buttons[idx] = value;
+        }
+    }
+
     pub(crate) fn set_mouse_pos(&mut self, mouse_pos: Vector2<i32>) {
         self.mouse_pos = mouse_pos;
 
@@ -108,16 +323,34 @@ impl Input {
         imgui.mouse_pos_event(mouse_pos.x as f32, mouse_pos.y as f32);
     }
 
+    /// Several motion events can arrive within a frame, they are summed until the next update
     pub(crate) fn set_mouse_delta(&mut self, mouse_delta: Vector2<f32>) {
-        self.mouse_delta = mouse_delta;
+        self.mouse_delta += mouse_delta;
+    }
+
+    pub(crate) fn set_mouse_scroll(&mut self, delta: MouseScrollDelta) {
+        let lines = match delta {
+            MouseScrollDelta::LineDelta(x, y) => Vector2::new(x, y),
+            MouseScrollDelta::PixelDelta(position) => Vector2::new(position.x as f32, position.y as f32) / PIXELS_PER_SCROLL_LINE
+        };
+        self.mouse_scroll += lines.y;
+
+        let imgui = app().graphics().imgui();
+        imgui.mouse_wheel_event(lines.x, lines.y);
     }
 
-    fn mb_to_idx(button: MouseButton) -> usize {
-        match button {
+    /// None for buttons that don't fit into `MAX_BUTTONS`
+    fn mb_to_idx(button: MouseButton) -> Option<usize> {
+        let idx = match button {
             MouseButton::Right => 0,
             MouseButton::Middle => 1,
             MouseButton::Left => 2,
             MouseButton::Other(i) => 3 + i as usize
+        };
+
+        match idx < MAX_BUTTONS {
+            true => Some(idx),
+            false => None
         }
     }
 }
@@ -134,4 +367,90 @@ fn winit_to_imgui_mouse_button(button: MouseButton) -> imgui::MouseButton {
             }
         }
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input_with_actions(config: &str) -> Box<Input> {
+        let mut input = Input::init();
+        input.set_action_map(ActionMap::parse(config).unwrap());
+        input
+    }
+
+    #[test]
+    fn mouse_buttons_do_not_press_keys() {
+        let mut input = Input::init();
+        input.set_mouse_button_state(MouseButton::Left, true);
+        assert!(input.mouse_button(MouseButton::Left));
+        assert!(input.mouse_button_down(MouseButton::Left));
+        assert!(!input.mouse_button(MouseButton::Right));
+        assert!(!KEY_CODES.into_iter().any(|key_code| input.key(key_code)));
+        assert_eq!(input.pressed_source(), Some(InputSource::MouseButton(MouseButton::Left)));
+
+        input.update();
+        assert!(input.mouse_button(MouseButton::Left));
+        assert!(!input.mouse_button_down(MouseButton::Left));
+
+        input.set_mouse_button_state(MouseButton::Left, false);
+        assert!(input.mouse_button_up(MouseButton::Left));
+    }
+
+    #[test]
+    fn out_of_range_mouse_buttons_are_ignored() {
+        let mut input = Input::init();
+        input.set_mouse_button_state(MouseButton::Other(28), true);
+        assert!(input.mouse_button(MouseButton::Other(28)));
+
+        input.set_mouse_button_state(MouseButton::Other(29), true);
+        input.set_mouse_button_state(MouseButton::Other(u16::MAX), true);
+        assert!(!input.mouse_button(MouseButton::Other(29)));
+        assert!(!input.mouse_button_down(MouseButton::Other(u16::MAX)));
+        assert!(!input.mouse_button_up(MouseButton::Other(u16::MAX)));
+        assert_eq!(input.source_value(InputSource::MouseButton(MouseButton::Other(40))), 0.0);
+    }
+
+    #[test]
+    fn actions_go_down_and_up() {
+        let mut input = input_with_actions("jump = key:Space\njump = mouse:Other2");
+        assert!(!input.action("jump"));
+
+        input.set_key(VirtualKeyCode::Space, true);
+        assert!(input.action("jump"));
+        assert!(input.action_down("jump"));
+        assert!(!input.action_up("jump"));
+
+        // A second binding of a held action does not press it again
+        input.update();
+        input.set_mouse_button_state(MouseButton::Other(2), true);
+        assert!(input.action("jump"));
+        assert!(!input.action_down("jump"));
+
+        input.update();
+        input.set_key(VirtualKeyCode::Space, false);
+        input.set_mouse_button_state(MouseButton::Other(2), false);
+        assert!(!input.action("jump"));
+        assert!(input.action_up("jump"));
+
+        input.update();
+        assert!(!input.action_up("jump"));
+    }
+
+    #[test]
+    fn the_most_specific_binding_wins() {
+        let mut input = input_with_actions("save = key:S modifiers=ctrl\nback = key:S\nmove_x = key:D\nmove_x = key:A scale=-1");
+
+        input.set_key(VirtualKeyCode::S, true);
+        assert!(input.action("back"));
+        assert!(!input.action("save"));
+
+        input.set_key(VirtualKeyCode::RControl, true);
+        assert!(input.action("save"));
+        assert!(!input.action("back"));
+
+        // Bindings without a more specific counterpart still work while modifiers are held
+        input.set_key(VirtualKeyCode::A, true);
+        assert_eq!(input.axis("move_x"), -1.0);
+    }
 }
\ No newline at end of file
@@ -18,6 +18,7 @@ pub use window::Window;
 pub mod graphics;
 pub mod resources;
 pub mod input;
+pub mod bvh;
 
 use common::Timer;
 
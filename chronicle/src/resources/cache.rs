@@ -0,0 +1,626 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use cgmath::{Vector4, Vector3, Vector2};
+
+use crate::resources::*;
+
+const MODEL_MAGIC: &[u8; 4] = b"CHRM";
+const TEXTURE_MAGIC: &[u8; 4] = b"CHRT";
+/// Bump whenever the layout of a cooked file or of `Vertex`, or the imported data changes, outdated files are then cooked again
+const VERSION: u32 = 3;
+
+pub const MODEL_EXTENSION: &str = "chrmodel";
+pub const TEXTURE_EXTENSION: &str = "chrtex";
+
+struct CookWriter {
+    bytes: Vec<u8>
+}
+
+impl CookWriter {
+    fn new(magic: &[u8; 4]) -> Self {
+        let mut writer = CookWriter {
+            bytes: Vec::new()
+        };
+        writer.bytes.extend_from_slice(magic);
+        writer.u32(VERSION);
+        writer
+    }
+
+    fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn f32(&mut self, value: f32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn vec2(&mut self, value: Vector2<f32>) {
+        self.f32(value.x);
+        self.f32(value.y);
+    }
+
+    fn vec3(&mut self, value: Vector3<f32>) {
+        self.f32(value.x);
+        self.f32(value.y);
+        self.f32(value.z);
+    }
+
+    fn vec4(&mut self, value: Vector4<f32>) {
+        self.f32(value.x);
+        self.f32(value.y);
+        self.f32(value.z);
+        self.f32(value.w);
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.u64(bytes.len() as u64);
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    fn string(&mut self, value: &str) {
+        self.bytes(value.as_bytes());
+    }
+
+    /// Writes the raw memory of a slice, only used for plain `#[repr(C)]` data
+    fn slice<T>(&mut self, values: &[T]) {
+        self.u64(values.len() as u64);
+        unsafe {
+            self.bytes.extend_from_slice(std::slice::from_raw_parts(
+                values.as_ptr() as *const u8,
+                values.len() * std::mem::size_of::<T>()
+            ));
+        }
+    }
+}
+
+/// Every read returns None past the end of the file or on invalid data, a broken cooked file is then treated like a missing one
+struct CookReader<'a> {
+    bytes: &'a [u8],
+    offset: usize
+}
+
+impl<'a> CookReader<'a> {
+    fn new(bytes: &'a [u8], magic: &[u8; 4]) -> Option<Self> {
+        match has_valid_header(bytes, magic) {
+            true => Some(CookReader {
+                bytes: bytes,
+                offset: 8
+            }),
+            false => None
+        }
+    }
+
+    fn take(&mut self, size: usize) -> Option<&'a [u8]> {
+        let bytes = self.bytes.get(self.offset..self.offset.checked_add(size)?)?;
+        self.offset += size;
+        Some(bytes)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        Some(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn f32(&mut self) -> Option<f32> {
+        Some(f32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn vec2(&mut self) -> Option<Vector2<f32>> {
+        Some(Vector2::new(self.f32()?, self.f32()?))
+    }
+
+    fn vec3(&mut self) -> Option<Vector3<f32>> {
+        Some(Vector3::new(self.f32()?, self.f32()?, self.f32()?))
+    }
+
+    fn vec4(&mut self) -> Option<Vector4<f32>> {
+        Some(Vector4::new(self.f32()?, self.f32()?, self.f32()?, self.f32()?))
+    }
+
+    fn bytes(&mut self) -> Option<&'a [u8]> {
+        let len = usize::try_from(self.u64()?).ok()?;
+        self.take(len)
+    }
+
+    fn string(&mut self) -> Option<String> {
+        String::from_utf8(self.bytes()?.to_vec()).ok()
+    }
+
+    fn slice<T: Clone>(&mut self) -> Option<Vec<T>> {
+        let len = usize::try_from(self.u64()?).ok()?;
+        let bytes = self.take(len.checked_mul(std::mem::size_of::<T>())?)?;
+
+        let mut values = Vec::<T>::with_capacity(len);
+        unsafe {
+            (values.as_mut_ptr() as *mut u8).copy_from_nonoverlapping(bytes.as_ptr(), bytes.len());
+            values.set_len(len);
+        }
+        Some(values)
+    }
+}
+
+fn has_valid_header(bytes: &[u8], magic: &[u8; 4]) -> bool {
+    bytes.len() >= 8 && &bytes[0..4] == magic && u32::from_le_bytes(bytes[4..8].try_into().unwrap()) == VERSION
+}
+
+/// The path of the cooked file belonging to a source asset, DamagedHelmet.gltf becomes DamagedHelmet.chrmodel
+pub fn cooked_path(asset_path: &String, extension: &str) -> String {
+    Path::new(asset_path).with_extension(extension).into_os_string().into_string().unwrap()
+}
+
+/// A cooked file is only used when it is at least as new as its source, outdated engine versions are caught by the header.
+/// Only the source file itself is compared, touching a .bin or texture next to a .gltf does not invalidate the cooked model.
+pub fn is_cooked_fresh(vfs: &Vfs, asset_path: &String, cooked_path: &String) -> bool {
+    match (vfs.modified(asset_path), vfs.modified(cooked_path)) {
+        (Some(source), Some(cooked)) => cooked >= source,
+        _ => false
+    }
+}
+
+fn texture_format_to_u8(format: TextureFormat) -> u8 {
+    match format {
+        TextureFormat::Rgba8 => 0,
+        TextureFormat::Bc1 => 1,
+        TextureFormat::Bc2 => 2,
+        TextureFormat::Bc3 => 3,
+        TextureFormat::Bc4 => 4,
+        TextureFormat::Bc5 => 5,
+        TextureFormat::Bc6h => 6,
+        TextureFormat::Bc7 => 7,
+        TextureFormat::Etc2Rgb8 => 8,
+        TextureFormat::Etc2Rgba8 => 9,
+        TextureFormat::Astc4x4 => 10,
+        TextureFormat::Basis => 11,
+        TextureFormat::Uastc => 12
+    }
+}
+
+fn texture_format_from_u8(value: u8) -> Option<TextureFormat> {
+    match value {
+        0 => Some(TextureFormat::Rgba8),
+        1 => Some(TextureFormat::Bc1),
+        2 => Some(TextureFormat::Bc2),
+        3 => Some(TextureFormat::Bc3),
+        4 => Some(TextureFormat::Bc4),
+        5 => Some(TextureFormat::Bc5),
+        6 => Some(TextureFormat::Bc6h),
+        7 => Some(TextureFormat::Bc7),
+        8 => Some(TextureFormat::Etc2Rgb8),
+        9 => Some(TextureFormat::Etc2Rgba8),
+        10 => Some(TextureFormat::Astc4x4),
+        11 => Some(TextureFormat::Basis),
+        12 => Some(TextureFormat::Uastc),
+        _ => None
+    }
+}
+
+fn color_space_from_u8(value: u8) -> Option<ColorSpace> {
+    match value {
+        0 => Some(ColorSpace::Linear),
+        1 => Some(ColorSpace::Srgb),
+        _ => None
+    }
+}
+
+fn color_space_to_u8(color_space: ColorSpace) -> u8 {
+    match color_space {
+        ColorSpace::Linear => 0,
+        ColorSpace::Srgb => 1
+    }
+}
+
+fn wrap_to_u8(wrap: TextureWrap) -> u8 {
+    match wrap {
+        TextureWrap::Repeat => 0,
+        TextureWrap::MirroredRepeat => 1,
+        TextureWrap::ClampToEdge => 2
+    }
+}
+
+fn wrap_from_u8(value: u8) -> Option<TextureWrap> {
+    match value {
+        0 => Some(TextureWrap::Repeat),
+        1 => Some(TextureWrap::MirroredRepeat),
+        2 => Some(TextureWrap::ClampToEdge),
+        _ => None
+    }
+}
+
+/// 0 is reserved for a missing mipmap filter
+fn filter_to_u8(filter: Option<TextureFilter>) -> u8 {
+    match filter {
+        None => 0,
+        Some(TextureFilter::Nearest) => 1,
+        Some(TextureFilter::Linear) => 2
+    }
+}
+
+fn filter_from_u8(value: u8) -> Option<Option<TextureFilter>> {
+    match value {
+        0 => Some(None),
+        1 => Some(Some(TextureFilter::Nearest)),
+        2 => Some(Some(TextureFilter::Linear)),
+        _ => None
+    }
+}
+
+fn mesh_kind_to_u8(kind: MeshKind) -> u8 {
+    match kind {
+        MeshKind::Triangles => 0,
+        MeshKind::Lines => 1,
+        MeshKind::LineStrip => 2,
+        MeshKind::Points => 3
+    }
+}
+
+fn mesh_kind_from_u8(value: u8) -> Option<MeshKind> {
+    match value {
+        0 => Some(MeshKind::Triangles),
+        1 => Some(MeshKind::Lines),
+        2 => Some(MeshKind::LineStrip),
+        3 => Some(MeshKind::Points),
+        _ => None
+    }
+}
+
+impl Texture {
+    /// Serializes the texture into a .chrtex file, Rgba8 textures get their full mip chain precomputed.
+    pub fn to_cooked(&self) -> Vec<u8> {
+        let mipmapped;
+        let texture = if self.format == TextureFormat::Rgba8 && !self.has_precomputed_mips() {
+            mipmapped = self.generate_mips();
+            &mipmapped
+        } else {
+            self
+        };
+
+        let mut writer = CookWriter::new(TEXTURE_MAGIC);
+        writer.u32(texture.width);
+        writer.u32(texture.height);
+        writer.u32(texture.channel_count);
+        writer.u32(texture.mip_levels);
+        writer.u8(texture_format_to_u8(texture.format));
+        writer.u8(color_space_to_u8(texture.color_space));
+        writer.u32(texture.mip_offsets.len() as u32);
+        for offset in &texture.mip_offsets {
+            writer.u64(*offset as u64);
+        }
+        writer.bytes(&texture.data);
+        writer.bytes
+    }
+
+    pub(super) fn from_cooked(bytes: &[u8], asset_path: &String) -> Texture {
+        Self::try_from_cooked(bytes)
+            .expect(&format!("Failed to read cooked file at \"{:?}\" (Invalid data or outdated version)", asset_path))
+    }
+
+    /// Returns None if the file is broken or was cooked by another version of the engine
+    fn try_from_cooked(bytes: &[u8]) -> Option<Texture> {
+        let mut reader = CookReader::new(bytes, TEXTURE_MAGIC)?;
+
+        let width = reader.u32()?;
+        let height = reader.u32()?;
+        let channel_count = reader.u32()?;
+        let mip_levels = reader.u32()?;
+        let format = texture_format_from_u8(reader.u8()?)?;
+        let color_space = color_space_from_u8(reader.u8()?)?;
+        let mip_offsets = (0..reader.u32()?)
+            .map(|_| reader.u64().map(|offset| offset as usize))
+            .collect::<Option<Vec<usize>>>()?;
+        let data = reader.bytes()?.to_vec();
+
+        Some(Texture {
+            data: data,
+            width: width,
+            height: height,
+            channel_count: channel_count,
+            mip_levels: mip_levels,
+            format: format,
+            color_space: color_space,
+            mip_offsets: mip_offsets
+        })
+    }
+}
+
+impl Model {
+    /// Writes the model to `cooked_path` as a .chrmodel file.
+    /// Every unique texture is written next to it as <name>.<index>.chrtex and referenced by file name.
+    pub fn cook<P: AsRef<Path>>(&self, cooked_path: P) {
+        let cooked_path = cooked_path.as_ref();
+        let directory = cooked_path.parent().unwrap_or_else(|| Path::new("./"));
+        let stem = cooked_path.file_stem().and_then(|stem| stem.to_str()).unwrap_or("model");
+
+        let mut texture_indices: HashMap<Resource<Texture>, u32> = HashMap::new();
+        let mut texture_names = Vec::new();
+        for material in &self.materials {
+            for material_texture in material.as_ref().textures() {
+                if material_texture.is_empty() || texture_indices.contains_key(&material_texture.texture) {
+                    continue;
+                }
+
+                let name = format!("{}.{}.{}", stem, texture_names.len(), TEXTURE_EXTENSION);
+                let texture = material_texture.texture.as_ref();
+                std::fs::write(directory.join(&name), texture.to_cooked())
+                    .expect(&format!("Failed to write cooked texture \"{}\"", name));
+
+                texture_indices.insert(material_texture.texture.clone(), texture_names.len() as u32);
+                texture_names.push((name, texture.color_space));
+            }
+        }
+
+        let mut writer = CookWriter::new(MODEL_MAGIC);
+
+        writer.u32(texture_names.len() as u32);
+        for (name, color_space) in &texture_names {
+            writer.string(name);
+            writer.u8(color_space_to_u8(*color_space));
+        }
+
+        writer.u32(self.materials.len() as u32);
+        for material in &self.materials {
+            let material = material.as_ref();
+            writer.string(&material.name);
+            writer.u64(material.index.map(|index| index as u64 + 1).unwrap_or(0));
+            writer.vec4(material.base_color_factor);
+            writer.f32(material.normal_scale);
+            writer.f32(material.metallic_factor);
+            writer.f32(material.roughness_factor);
+            writer.f32(material.occlusion_strength);
+            writer.vec3(material.emissive_factor);
+            writer.f32(material.emissive_strength);
+            writer.f32(material.clearcoat_factor);
+            writer.f32(material.clearcoat_roughness_factor);
+            writer.f32(material.clearcoat_normal_scale);
+            writer.f32(material.transmission_factor);
+            writer.f32(material.ior);
+            writer.vec3(material.sheen_color_factor);
+            writer.f32(material.sheen_roughness_factor);
+            writer.f32(material.specular_factor);
+            writer.vec3(material.specular_color_factor);
+
+            for material_texture in material.textures() {
+                // 0 marks an empty slot
+                let index = match material_texture.is_empty() {
+                    true => 0,
+                    false => texture_indices[&material_texture.texture] + 1
+                };
+                writer.u32(index);
+
+                let sampler = &material_texture.sampler;
+                writer.u8(wrap_to_u8(sampler.wrap_s));
+                writer.u8(wrap_to_u8(sampler.wrap_t));
+                writer.u8(filter_to_u8(Some(sampler.min_filter)));
+                writer.u8(filter_to_u8(Some(sampler.mag_filter)));
+                writer.u8(filter_to_u8(sampler.mipmap_mode));
+                writer.u32(material_texture.tex_coord);
+                writer.vec2(material_texture.transform.offset);
+                writer.f32(material_texture.transform.rotation);
+                writer.vec2(material_texture.transform.scale);
+            }
+        }
+
+        writer.u32(self.meshes.len() as u32);
+        for mesh in &self.meshes {
+            writer.slice(&mesh.vertices);
+            writer.slice(&mesh.indices);
+            writer.u32(mesh.lods.len() as u32);
+            for lod in &mesh.lods {
+                writer.slice(&lod.indices);
+                writer.f32(lod.error);
+            }
+            writer.vec3(mesh.min);
+            writer.vec3(mesh.max);
+            writer.u64(mesh.material_idx as u64);
+            writer.u8(mesh_kind_to_u8(mesh.kind));
+        }
+
+        std::fs::write(cooked_path, writer.bytes)
+            .expect(&format!("Failed to write cooked model \"{:?}\"", cooked_path));
+    }
+}
+
+impl Resources {
+    /// Returns None if the model or one of its textures is missing, broken or was cooked by another version of the engine
+    pub(super) fn try_load_cooked_model(&mut self, asset_path: &String) -> Option<Model> {
+        let bytes = self.vfs.read(asset_path)?;
+        let mut reader = CookReader::new(&bytes, MODEL_MAGIC)?;
+
+        let directory = Path::new(asset_path).parent().unwrap_or_else(|| Path::new("./"));
+
+        let mut textures = Vec::new();
+        for _ in 0..reader.u32()? {
+            let name = reader.string()?;
+            let color_space = color_space_from_u8(reader.u8()?)?;
+
+            let path = directory.join(name).into_os_string().into_string().ok()?;
+            textures.push(self.try_get_cooked_texture(path, color_space)?);
+        }
+
+        let mut materials = Vec::new();
+        for _ in 0..reader.u32()? {
+            let mut material = Material::default();
+            material.name = reader.string()?;
+            material.index = match reader.u64()? {
+                0 => None,
+                index => Some(index as usize - 1)
+            };
+            material.base_color_factor = reader.vec4()?;
+            material.normal_scale = reader.f32()?;
+            material.metallic_factor = reader.f32()?;
+            material.roughness_factor = reader.f32()?;
+            material.occlusion_strength = reader.f32()?;
+            material.emissive_factor = reader.vec3()?;
+            material.emissive_strength = reader.f32()?;
+            material.clearcoat_factor = reader.f32()?;
+            material.clearcoat_roughness_factor = reader.f32()?;
+            material.clearcoat_normal_scale = reader.f32()?;
+            material.transmission_factor = reader.f32()?;
+            material.ior = reader.f32()?;
+            material.sheen_color_factor = reader.vec3()?;
+            material.sheen_roughness_factor = reader.f32()?;
+            material.specular_factor = reader.f32()?;
+            material.specular_color_factor = reader.vec3()?;
+
+            for material_texture in material.textures_mut() {
+                material_texture.texture = match reader.u32()? {
+                    0 => Resource::empty(),
+                    index => textures.get(index as usize - 1).cloned()?
+                };
+
+                material_texture.sampler = TextureSampler {
+                    wrap_s: wrap_from_u8(reader.u8()?)?,
+                    wrap_t: wrap_from_u8(reader.u8()?)?,
+                    min_filter: filter_from_u8(reader.u8()?)?.unwrap_or(TextureFilter::Linear),
+                    mag_filter: filter_from_u8(reader.u8()?)?.unwrap_or(TextureFilter::Linear),
+                    mipmap_mode: filter_from_u8(reader.u8()?)?
+                };
+                material_texture.tex_coord = reader.u32()?;
+                material_texture.transform = TextureTransform {
+                    offset: reader.vec2()?,
+                    rotation: reader.f32()?,
+                    scale: reader.vec2()?
+                };
+            }
+
+            materials.push(Resource::new(material));
+        }
+
+        let mut meshes = Vec::new();
+        for _ in 0..reader.u32()? {
+            let vertices = reader.slice()?;
+            let indices = reader.slice()?;
+
+            let mut lods = Vec::new();
+            for _ in 0..reader.u32()? {
+                lods.push(MeshLod {
+                    indices: reader.slice()?,
+                    error: reader.f32()?
+                });
+            }
+
+            meshes.push(Mesh {
+                vertices: vertices,
+                indices: indices,
+                lods: lods,
+                min: reader.vec3()?,
+                max: reader.vec3()?,
+                material_idx: reader.u64()? as usize,
+                kind: mesh_kind_from_u8(reader.u8()?)?
+            });
+        }
+
+        Some(Model {
+            meshes: meshes,
+            materials: materials
+        })
+    }
+
+    /// Loads a texture of a cooked model like `get_texture`, but returns None instead of panicking on a broken file
+    fn try_get_cooked_texture(&mut self, asset_path: String, color_space: ColorSpace) -> Option<Resource<Texture>> {
+        let key = match color_space {
+            ColorSpace::Srgb => format!("{} (sRGB)", asset_path),
+            ColorSpace::Linear => asset_path.clone()
+        };
+
+        match self.image_manager.get(&key) {
+            Some(resource) => Some(resource),
+            None => {
+                let mut texture = Texture::try_from_cooked(&self.vfs.read(&asset_path)?)?;
+                texture.color_space = color_space;
+
+                let resource = Resource::new(texture);
+
+                self.image_manager.insert(resource.clone(), key);
+                Some(resource)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Cooks a model with a single textured triangle into its own folder in the temp directory
+    fn cook_test_model(name: &str) -> (std::path::PathBuf, String) {
+        let directory = std::env::temp_dir().join(format!("chronicle_{}_{}", name, std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+
+        let mut material = Material::default();
+        material.base_color_texture.texture = Resource::new(Texture {
+            data: vec![255; 4],
+            width: 1,
+            height: 1,
+            channel_count: 4,
+            mip_levels: 1,
+            format: TextureFormat::Rgba8,
+            color_space: ColorSpace::Srgb,
+            mip_offsets: Vec::new()
+        });
+
+        let model = Model {
+            meshes: vec![Mesh {
+                vertices: vec![Vertex::default(); 3],
+                indices: vec![0, 1, 2],
+                lods: Vec::new(),
+                min: Vector3::new(0.0, 0.0, 0.0),
+                max: Vector3::new(1.0, 1.0, 1.0),
+                material_idx: 0,
+                kind: MeshKind::Triangles
+            }],
+            materials: vec![Resource::new(material)]
+        };
+
+        let cooked_path = directory.join(format!("model.{}", MODEL_EXTENSION));
+        model.cook(&cooked_path);
+        (directory, cooked_path.into_os_string().into_string().unwrap())
+    }
+
+    #[test]
+    fn cooked_model_round_trip() {
+        let (directory, cooked_path) = cook_test_model("cooked_round_trip");
+
+        let model = Resources::init().try_load_cooked_model(&cooked_path).unwrap();
+        assert_eq!(model.meshes.len(), 1);
+        assert_eq!(model.meshes[0].indices, vec![0, 1, 2]);
+        assert!(model.materials[0].as_ref().base_color_texture.texture.as_ref().color_space == ColorSpace::Srgb);
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn truncated_cooked_files_are_a_cache_miss() {
+        let (directory, cooked_path) = cook_test_model("cooked_truncated");
+        let bytes = std::fs::read(&cooked_path).unwrap();
+
+        for len in [0, 8, 12, bytes.len() / 2, bytes.len() - 1] {
+            std::fs::write(&cooked_path, &bytes[..len]).unwrap();
+            assert!(Resources::init().try_load_cooked_model(&cooked_path).is_none(), "Loaded a model cut off after {} bytes", len);
+        }
+
+        // A broken texture invalidates the whole model
+        std::fs::write(&cooked_path, &bytes).unwrap();
+        let texture_path = directory.join(format!("model.0.{}", TEXTURE_EXTENSION));
+        let texture_bytes = std::fs::read(&texture_path).unwrap();
+        std::fs::write(&texture_path, &texture_bytes[..texture_bytes.len() - 1]).unwrap();
+        assert!(Resources::init().try_load_cooked_model(&cooked_path).is_none());
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
\ No newline at end of file
@@ -0,0 +1,535 @@
+extern crate mikktspace;
+
+use std::collections::HashMap;
+
+use bitmask_enum::bitmask;
+use cgmath::{Vector4, Vector3, Matrix, Matrix4, SquareMatrix, InnerSpace, Rad, Zero};
+
+use crate::resources::{Mesh, MeshKind, Vertex};
+
+/// Optional import time processing of meshes, applied in the order they are listed
+#[bitmask(u8)]
+pub enum MeshOptimizations {
+    /// Merges vertices whose attributes are exactly equal
+    WeldVertices,
+    /// Reorders triangles so their vertices are likely still in the post-transform cache
+    VertexCache,
+    /// Reorders clusters of triangles so outward facing ones are drawn first, keeping the vertex cache order within a cluster
+    Overdraw,
+    /// Simplifies the mesh into a chain of levels of detail that share its vertices
+    GenerateLods,
+    /// Reorders vertices in the order they are first used and drops unused ones
+    VertexFetch
+}
+
+/// Size of the simulated post-transform cache, a common size for current hardware
+const VERTEX_CACHE_SIZE: usize = 32;
+/// Every level of detail targets this fraction of the triangles of the previous one
+const LOD_REDUCTION: f32 = 0.5;
+const MAX_LOD_COUNT: usize = 4;
+
+/// Per corner view of a triangle mesh for the MikkTSpace generator
+struct TangentGeometry<'a> {
+    mesh: &'a Mesh,
+    tangents: Vec<Vector4<f32>>
+}
+
+impl<'a> TangentGeometry<'a> {
+    fn vertex(&self, face: usize, vert: usize) -> &Vertex {
+        &self.mesh.vertices[self.mesh.indices[face * 3 + vert] as usize]
+    }
+}
+
+impl<'a> mikktspace::Geometry for TangentGeometry<'a> {
+    fn num_faces(&self) -> usize {
+        self.mesh.indices.len() / 3
+    }
+
+    fn num_vertices_of_face(&self, _face: usize) -> usize {
+        3
+    }
+
+    fn position(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.vertex(face, vert).position.into()
+    }
+
+    fn normal(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.vertex(face, vert).normal.into()
+    }
+
+    fn tex_coord(&self, face: usize, vert: usize) -> [f32; 2] {
+        // Normal maps are baked with the uv origin at the bottom left, our tex coords start at the top left
+        let tex_coord = self.vertex(face, vert).tex_coord;
+        [tex_coord.x, 1.0 - tex_coord.y]
+    }
+
+    fn set_tangent_encoded(&mut self, tangent: [f32; 4], face: usize, vert: usize) {
+        self.tangents[face * 3 + vert] = Vector4::from(tangent);
+    }
+}
+
+impl Mesh {
+    /// Generates MikkTSpace tangents from the normals and first tex coords, matching the tangent space normal maps are baked in.
+    /// Vertices whose corners end up with different tangents are split. Only triangle meshes are processed.
+    pub fn generate_tangents(&mut self) {
+        if self.kind != MeshKind::Triangles {
+            return;
+        }
+
+        let mut geometry = TangentGeometry {
+            mesh: self,
+            tangents: vec![Vector4::new(1.0, 0.0, 0.0, 1.0); self.indices.len()]
+        };
+        if !mikktspace::generate_tangents(&mut geometry) {
+            return;
+        }
+        let tangents = geometry.tangents;
+
+        self.split_corners(|vertex, corner| vertex.tangent = tangents[corner]);
+    }
+
+    /// Gives every triangle the normal of its face, shared vertices are split
+    pub fn generate_flat_normals(&mut self) {
+        if self.kind != MeshKind::Triangles {
+            return;
+        }
+
+        let face_normals = self.face_normals();
+        self.split_corners(|vertex, corner| vertex.normal = face_normals[corner / 3]);
+    }
+
+    /// Averages the normals of the faces around every position, weighted by the angle of the face at that position.
+    /// Faces whose normals differ more than `angle_threshold` from the face of a corner are left out, which keeps hard edges hard.
+    /// Vertices are matched on position, so normals are also smoothed across uv seams.
+    pub fn generate_smooth_normals<A: Into<Rad<f32>>>(&mut self, angle_threshold: A) {
+        if self.kind != MeshKind::Triangles {
+            return;
+        }
+
+        let cos_threshold = angle_threshold.into().0.cos();
+        let face_normals = self.face_normals();
+
+        let mut corner_angles = Vec::with_capacity(self.indices.len());
+        for triangle in self.indices.chunks_exact(3) {
+            for i in 0..3 {
+                let position = self.vertices[triangle[i] as usize].position;
+                let a = self.vertices[triangle[(i + 1) % 3] as usize].position - position;
+                let b = self.vertices[triangle[(i + 2) % 3] as usize].position - position;
+
+                let angle = match a.magnitude2() > 0.0 && b.magnitude2() > 0.0 {
+                    true => a.angle(b).0,
+                    false => 0.0
+                };
+                corner_angles.push(angle);
+            }
+        }
+
+        let mut position_corners: HashMap<[u32; 3], Vec<usize>> = HashMap::new();
+        for (corner, index) in self.indices.iter().enumerate() {
+            let position = self.vertices[*index as usize].position;
+            position_corners.entry([position.x.to_bits(), position.y.to_bits(), position.z.to_bits()])
+                .or_default()
+                .push(corner);
+        }
+
+        let mut normals = vec![Vector3::new(0.0, 0.0, 0.0); self.indices.len()];
+        for corners in position_corners.values() {
+            for corner in corners {
+                let face_normal = face_normals[corner / 3];
+
+                let mut normal = Vector3::new(0.0, 0.0, 0.0);
+                for other in corners {
+                    let other_normal = face_normals[other / 3];
+                    if face_normal.dot(other_normal) >= cos_threshold {
+                        normal += other_normal * corner_angles[*other];
+                    }
+                }
+
+                normals[*corner] = match normal.magnitude2() > 0.0 {
+                    true => normal.normalize(),
+                    false => face_normal
+                };
+            }
+        }
+
+        self.split_corners(|vertex, corner| vertex.normal = normals[corner]);
+    }
+
+    /// Bakes a transform into the vertices and bounds, e.g. the node hierarchy of a scene
+    pub fn transform(&mut self, matrix: &Matrix4<f32>) {
+        let normal_matrix = match matrix.invert() {
+            Some(inverse) => inverse.transpose(),
+            None => *matrix
+        };
+        let mirrored = matrix.determinant() < 0.0;
+
+        for vertex in self.vertices.iter_mut() {
+            vertex.position = (matrix * vertex.position.extend(1.0)).truncate();
+            // Zero normals and tangents, e.g. of points and lines, stay zero instead of turning into NaN
+            let normal = (normal_matrix * vertex.normal.extend(0.0)).truncate();
+            vertex.normal = match normal.magnitude2() > 0.0 {
+                true => normal.normalize(),
+                false => normal
+            };
+
+            // Mirroring flips the bitangent, which the handedness in w has to undo
+            let tangent = (matrix * vertex.tangent.truncate().extend(0.0)).truncate();
+            let tangent = match tangent.magnitude2() > 0.0 {
+                true => tangent.normalize(),
+                false => tangent
+            };
+            let handedness = match mirrored {
+                true => -vertex.tangent.w,
+                false => vertex.tangent.w
+            };
+            vertex.tangent = tangent.extend(handedness);
+        }
+
+        // Mirroring also flips the winding order of triangles
+        if mirrored && self.kind == MeshKind::Triangles {
+            for triangle in self.indices.chunks_exact_mut(3) {
+                triangle.swap(1, 2);
+            }
+            for lod in self.lods.iter_mut() {
+                for triangle in lod.indices.chunks_exact_mut(3) {
+                    triangle.swap(1, 2);
+                }
+            }
+        }
+
+        if let Some(first) = self.vertices.first() {
+            let (min, max) = self.vertices.iter().fold((first.position, first.position), |(min, max), vertex| {
+                (
+                    Vector3::new(min.x.min(vertex.position.x), min.y.min(vertex.position.y), min.z.min(vertex.position.z)),
+                    Vector3::new(max.x.max(vertex.position.x), max.y.max(vertex.position.y), max.z.max(vertex.position.z))
+                )
+            });
+            self.min = min;
+            self.max = max;
+        }
+    }
+
+    /// Runs the enabled optimizations in the order they are declared in `MeshOptimizations`
+    pub fn optimize(&mut self, optimizations: MeshOptimizations) {
+        if optimizations.contains(MeshOptimizations::WeldVertices) {
+            self.weld_vertices();
+        }
+        if optimizations.contains(MeshOptimizations::VertexCache) {
+            self.optimize_vertex_cache();
+        }
+        if optimizations.contains(MeshOptimizations::Overdraw) {
+            self.optimize_overdraw();
+        }
+        if optimizations.contains(MeshOptimizations::GenerateLods) {
+            self.generate_lods(MAX_LOD_COUNT, LOD_REDUCTION);
+        }
+        if optimizations.contains(MeshOptimizations::VertexFetch) {
+            self.optimize_vertex_fetch();
+        }
+    }
+
+    /// Merges vertices with bitwise equal attributes, the first one of every group keeps its place
+    pub fn weld_vertices(&mut self) {
+        let mut unique: HashMap<[u32; 18], u32> = HashMap::with_capacity(self.vertices.len());
+        let mut remap = Vec::with_capacity(self.vertices.len());
+        let mut vertices = Vec::with_capacity(self.vertices.len());
+
+        for vertex in self.vertices.drain(..) {
+            let next_index = vertices.len() as u32;
+            let index = *unique.entry(Self::vertex_key(&vertex)).or_insert(next_index);
+            if index == next_index {
+                vertices.push(vertex);
+            }
+            remap.push(index);
+        }
+
+        for index in self.indices.iter_mut().chain(self.lods.iter_mut().flat_map(|lod| lod.indices.iter_mut())) {
+            *index = remap[*index as usize];
+        }
+        self.vertices = vertices;
+    }
+
+    /// Orders triangles with Tom Forsyth's linear-speed vertex cache optimisation.
+    /// https://tomforsyth1000.github.io/papers/fast_vert_cache_opt.html
+    pub fn optimize_vertex_cache(&mut self) {
+        if self.kind != MeshKind::Triangles {
+            return;
+        }
+
+        self.indices = Self::vertex_cache_order(&self.indices, self.vertices.len());
+    }
+
+    pub(super) fn vertex_cache_order(indices: &[u32], vertex_count: usize) -> Vec<u32> {
+        let triangle_count = indices.len() / 3;
+
+        // Triangles around every vertex that are not emitted yet, the first `valence` entries of its adjacency range
+        let mut valence = vec![0u32; vertex_count];
+        for index in indices {
+            valence[*index as usize] += 1;
+        }
+        let mut offsets = vec![0usize; vertex_count + 1];
+        for vertex in 0..vertex_count {
+            offsets[vertex + 1] = offsets[vertex] + valence[vertex] as usize;
+        }
+        let mut adjacency = vec![0u32; indices.len()];
+        let mut fill = offsets.clone();
+        for (corner, index) in indices.iter().enumerate() {
+            adjacency[fill[*index as usize]] = (corner / 3) as u32;
+            fill[*index as usize] += 1;
+        }
+
+        let mut vertex_scores: Vec<f32> = valence.iter()
+            .map(|valence| Self::vertex_cache_score(None, *valence))
+            .collect();
+        let mut emitted = vec![false; triangle_count];
+        let mut next_unemitted = 0;
+
+        let mut cache: Vec<u32> = Vec::with_capacity(VERTEX_CACHE_SIZE + 3);
+        let mut optimized = Vec::with_capacity(indices.len());
+        let mut best_triangle = None;
+
+        for _ in 0..triangle_count {
+            // Without candidates in the cache continue with the first triangle that is left
+            let triangle = match best_triangle {
+                Some(triangle) => triangle,
+                None => {
+                    while emitted[next_unemitted] {
+                        next_unemitted += 1;
+                    }
+                    next_unemitted
+                }
+            };
+            emitted[triangle] = true;
+
+            let corners = [indices[triangle * 3], indices[triangle * 3 + 1], indices[triangle * 3 + 2]];
+            optimized.extend_from_slice(&corners);
+
+            for vertex in corners {
+                let vertex = vertex as usize;
+                let start = offsets[vertex];
+                let end = start + valence[vertex] as usize;
+
+                let position = adjacency[start..end].iter()
+                    .position(|adjacent| *adjacent == triangle as u32)
+                    .unwrap();
+                adjacency.swap(start + position, end - 1);
+                valence[vertex] -= 1;
+            }
+
+            // The vertices of the triangle move to the front of the lru cache
+            cache.retain(|vertex| !corners.contains(vertex));
+            let mut front = 0;
+            for vertex in corners {
+                if !cache[..front].contains(&vertex) {
+                    cache.insert(front, vertex);
+                    front += 1;
+                }
+            }
+            let evicted = cache.split_off(cache.len().min(VERTEX_CACHE_SIZE));
+
+            for vertex in evicted {
+                vertex_scores[vertex as usize] = Self::vertex_cache_score(None, valence[vertex as usize]);
+            }
+            for (position, vertex) in cache.iter().enumerate() {
+                vertex_scores[*vertex as usize] = Self::vertex_cache_score(Some(position), valence[*vertex as usize]);
+            }
+
+            // Only triangles around cached vertices changed score enough to be worth picking
+            best_triangle = None;
+            let mut best_score = f32::MIN;
+            for vertex in cache.iter() {
+                let start = offsets[*vertex as usize];
+                let end = start + valence[*vertex as usize] as usize;
+
+                for adjacent in &adjacency[start..end] {
+                    let adjacent = *adjacent as usize;
+                    let score: f32 = indices[adjacent * 3..adjacent * 3 + 3].iter()
+                        .map(|index| vertex_scores[*index as usize])
+                        .sum();
+
+                    if score > best_score {
+                        best_score = score;
+                        best_triangle = Some(adjacent);
+                    }
+                }
+            }
+        }
+
+        optimized.extend_from_slice(&indices[triangle_count * 3..]);
+        optimized
+    }
+
+    fn vertex_cache_score(cache_position: Option<usize>, valence: u32) -> f32 {
+        if valence == 0 {
+            return -1.0;
+        }
+
+        // The last triangle's vertices get a fixed score so the next triangle does not simply reuse the same edge
+        let cache_score = match cache_position {
+            None => 0.0,
+            Some(position) if position < 3 => 0.75,
+            Some(position) => (1.0 - (position - 3) as f32 / (VERTEX_CACHE_SIZE - 3) as f32).powf(1.5)
+        };
+
+        // Vertices with few triangles left are finished first to avoid leaving lone triangles behind
+        cache_score + 2.0 * (valence as f32).powf(-0.5)
+    }
+
+    /// Splits the triangles into clusters where a simulated vertex cache misses on all three vertices,
+    /// then sorts the clusters so the ones facing away from the mesh center are drawn first.
+    /// Run after `optimize_vertex_cache`, the cache order within every cluster is kept.
+    /// Based on "Fast Triangle Reordering for Vertex Locality and Reduced Overdraw" by Sander et al.
+    pub fn optimize_overdraw(&mut self) {
+        if self.kind != MeshKind::Triangles {
+            return;
+        }
+
+        let triangle_count = self.indices.len() / 3;
+
+        // Fifo cache, a vertex is cached while fewer than VERTEX_CACHE_SIZE vertices were added after it
+        let mut cache_stamps: Vec<Option<usize>> = vec![None; self.vertices.len()];
+        let mut time = 0;
+
+        let mut cluster_starts = Vec::new();
+        for triangle in 0..triangle_count {
+            let mut misses = 0;
+            for index in &self.indices[triangle * 3..triangle * 3 + 3] {
+                let stamp = &mut cache_stamps[*index as usize];
+                if stamp.map_or(true, |stamp| time - stamp >= VERTEX_CACHE_SIZE) {
+                    *stamp = Some(time);
+                    time += 1;
+                    misses += 1;
+                }
+            }
+
+            if triangle == 0 || misses == 3 {
+                cluster_starts.push(triangle);
+            }
+        }
+        cluster_starts.push(triangle_count);
+
+        // Area weighted centroids and normals of every cluster
+        let mut mesh_centroid = Vector3::zero();
+        let mut mesh_area = 0.0;
+        let mut clusters = Vec::with_capacity(cluster_starts.len() - 1);
+        for range in cluster_starts.windows(2) {
+            let mut centroid = Vector3::zero();
+            let mut normal = Vector3::zero();
+            let mut area = 0.0;
+
+            for triangle in range[0]..range[1] {
+                let a = self.vertices[self.indices[triangle * 3] as usize].position;
+                let b = self.vertices[self.indices[triangle * 3 + 1] as usize].position;
+                let c = self.vertices[self.indices[triangle * 3 + 2] as usize].position;
+
+                let cross = (b - a).cross(c - a);
+                let triangle_area = cross.magnitude() * 0.5;
+
+                centroid += (a + b + c) / 3.0 * triangle_area;
+                normal += cross;
+                area += triangle_area;
+            }
+
+            mesh_centroid += centroid;
+            mesh_area += area;
+            clusters.push((range[0], range[1], centroid, normal, area));
+        }
+        if mesh_area > 0.0 {
+            mesh_centroid /= mesh_area;
+        }
+
+        let mut sorted: Vec<(f32, usize, usize)> = clusters.into_iter()
+            .map(|(start, end, centroid, normal, area)| {
+                let outwardness = match area > 0.0 && normal.magnitude2() > 0.0 {
+                    true => (centroid / area - mesh_centroid).dot(normal.normalize()),
+                    false => 0.0
+                };
+                (outwardness, start, end)
+            })
+            .collect();
+        sorted.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        let mut indices = Vec::with_capacity(self.indices.len());
+        for (_, start, end) in sorted {
+            indices.extend_from_slice(&self.indices[start * 3..end * 3]);
+        }
+        indices.extend_from_slice(&self.indices[triangle_count * 3..]);
+        self.indices = indices;
+    }
+
+    /// Reorders the vertices in the order the indices first reference them, unreferenced vertices are removed.
+    /// Vertices that are only used by levels of detail come last.
+    pub fn optimize_vertex_fetch(&mut self) {
+        let mut remap = vec![u32::MAX; self.vertices.len()];
+        let mut vertices = Vec::with_capacity(self.vertices.len());
+
+        for index in self.indices.iter_mut().chain(self.lods.iter_mut().flat_map(|lod| lod.indices.iter_mut())) {
+            let new_index = &mut remap[*index as usize];
+            if *new_index == u32::MAX {
+                *new_index = vertices.len() as u32;
+                vertices.push(self.vertices[*index as usize].clone());
+            }
+            *index = *new_index;
+        }
+
+        self.vertices = vertices;
+    }
+
+    fn vertex_key(vertex: &Vertex) -> [u32; 18] {
+        [
+            vertex.position.x, vertex.position.y, vertex.position.z,
+            vertex.normal.x, vertex.normal.y, vertex.normal.z,
+            vertex.tangent.x, vertex.tangent.y, vertex.tangent.z, vertex.tangent.w,
+            vertex.tex_coord.x, vertex.tex_coord.y,
+            vertex.tex_coord_1.x, vertex.tex_coord_1.y,
+            vertex.color.x, vertex.color.y, vertex.color.z, vertex.color.w
+        ].map(f32::to_bits)
+    }
+
+    /// Unit normals of every triangle, degenerate triangles get a zero normal
+    fn face_normals(&self) -> Vec<Vector3<f32>> {
+        self.indices.chunks_exact(3).map(|triangle| {
+            let a = self.vertices[triangle[0] as usize].position;
+            let b = self.vertices[triangle[1] as usize].position;
+            let c = self.vertices[triangle[2] as usize].position;
+
+            let normal = (b - a).cross(c - a);
+            match normal.magnitude2() > 0.0 {
+                true => normal.normalize(),
+                false => Vector3::new(0.0, 0.0, 0.0)
+            }
+        }).collect()
+    }
+
+    /// Applies a per corner attribute to the vertex of every corner.
+    /// Corners that share a vertex but end up different get their own copy of the vertex, the first one keeps the original index.
+    fn split_corners<F: Fn(&mut Vertex, usize)>(&mut self, set_attribute: F) {
+        let mut variants: Vec<Vec<u32>> = vec![Vec::new(); self.vertices.len()];
+
+        for corner in 0..self.indices.len() {
+            let index = self.indices[corner] as usize;
+
+            let mut vertex = self.vertices[index].clone();
+            set_attribute(&mut vertex, corner);
+
+            let existing = variants[index].iter()
+                .find(|variant| self.vertices[**variant as usize] == vertex)
+                .copied();
+
+            self.indices[corner] = match existing {
+                Some(variant) => variant,
+                None if variants[index].is_empty() => {
+                    self.vertices[index] = vertex;
+                    variants[index].push(index as u32);
+                    index as u32
+                },
+                None => {
+                    let variant = self.vertices.len() as u32;
+                    self.vertices.push(vertex);
+                    variants[index].push(variant);
+                    variant
+                }
+            };
+        }
+    }
+}
\ No newline at end of file
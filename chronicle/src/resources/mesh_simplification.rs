@@ -0,0 +1,415 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+
+use cgmath::{Vector3, InnerSpace};
+
+use crate::resources::{Mesh, MeshKind, MeshLod};
+
+/// Levels of detail with fewer indices than this are not worth the extra draw and blas
+const MIN_LOD_INDEX_COUNT: usize = 3 * 64;
+
+/// Symmetric 4x4 matrix of summed squared plane distances, stored as its 10 unique elements
+#[derive(Clone, Copy)]
+struct Quadric {
+    m: [f64; 10]
+}
+
+impl Quadric {
+    fn zero() -> Self {
+        Quadric {
+            m: [0.0; 10]
+        }
+    }
+
+    /// Plane through `point` with unit `normal`
+    fn from_plane(normal: Vector3<f64>, point: Vector3<f64>) -> Self {
+        let (a, b, c) = (normal.x, normal.y, normal.z);
+        let d = -normal.dot(point);
+
+        Quadric {
+            m: [a * a, a * b, a * c, a * d, b * b, b * c, b * d, c * c, c * d, d * d]
+        }
+    }
+
+    fn add(&mut self, other: &Quadric) {
+        for i in 0..10 {
+            self.m[i] += other.m[i];
+        }
+    }
+
+    fn error(&self, p: Vector3<f64>) -> f64 {
+        let m = &self.m;
+        let error = m[0] * p.x * p.x + 2.0 * m[1] * p.x * p.y + 2.0 * m[2] * p.x * p.z + 2.0 * m[3] * p.x
+            + m[4] * p.y * p.y + 2.0 * m[5] * p.y * p.z + 2.0 * m[6] * p.y
+            + m[7] * p.z * p.z + 2.0 * m[8] * p.z
+            + m[9];
+
+        error.max(0.0)
+    }
+}
+
+/// Moves every vertex at the `from` position onto the `to` position
+struct Collapse {
+    cost: f64,
+    from: u32,
+    to: u32,
+    from_version: u32,
+    to_version: u32
+}
+
+impl PartialEq for Collapse {
+    fn eq(&self, other: &Self) -> bool {
+        self.cost == other.cost
+    }
+}
+
+impl Eq for Collapse {}
+
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Collapse {
+    /// Reversed so the binary heap pops the cheapest collapse first
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.cost.total_cmp(&self.cost)
+    }
+}
+
+/// Edge collapse state, vertices with equal positions are collapsed together so uv and normal seams stay closed
+struct Simplifier<'a> {
+    indices: &'a [u32],
+    triangles: Vec<[u32; 3]>,
+    alive: Vec<bool>,
+    alive_count: usize,
+
+    group_of: Vec<u32>,
+    group_positions: Vec<Vector3<f64>>,
+    group_triangles: Vec<Vec<u32>>,
+    group_quadrics: Vec<Quadric>,
+    group_versions: Vec<u32>,
+    collapsed: Vec<bool>,
+
+    heap: BinaryHeap<Collapse>
+}
+
+impl<'a> Simplifier<'a> {
+    fn new(mesh: &'a Mesh) -> Self {
+        let mut groups: HashMap<[u32; 3], u32> = HashMap::new();
+        let mut group_of = Vec::with_capacity(mesh.vertices.len());
+        let mut group_positions = Vec::new();
+        for vertex in &mesh.vertices {
+            let position = vertex.position;
+            let next_group = group_positions.len() as u32;
+            let group = *groups.entry([position.x.to_bits(), position.y.to_bits(), position.z.to_bits()]).or_insert(next_group);
+            if group == next_group {
+                group_positions.push(Vector3::new(position.x as f64, position.y as f64, position.z as f64));
+            }
+            group_of.push(group);
+        }
+        let group_count = group_positions.len();
+
+        let mut simplifier = Simplifier {
+            indices: &mesh.indices,
+            triangles: Vec::with_capacity(mesh.indices.len() / 3),
+            alive: Vec::with_capacity(mesh.indices.len() / 3),
+            alive_count: 0,
+            group_of: group_of,
+            group_positions: group_positions,
+            group_triangles: vec![Vec::new(); group_count],
+            group_quadrics: vec![Quadric::zero(); group_count],
+            group_versions: vec![0; group_count],
+            collapsed: vec![false; group_count],
+            heap: BinaryHeap::new()
+        };
+        simplifier.init_triangles();
+        simplifier
+    }
+
+    fn init_triangles(&mut self) {
+        // Edges in position space that belong to a single triangle are borders
+        let mut edge_counts: HashMap<(u32, u32), u32> = HashMap::new();
+
+        for triangle in self.indices.chunks_exact(3) {
+            let groups = [self.group_of[triangle[0] as usize], self.group_of[triangle[1] as usize], self.group_of[triangle[2] as usize]];
+
+            // Triangles that are degenerate in position space are dropped right away
+            let degenerate = groups[0] == groups[1] || groups[1] == groups[2] || groups[2] == groups[0];
+            let index = self.triangles.len() as u32;
+            self.triangles.push([triangle[0], triangle[1], triangle[2]]);
+            self.alive.push(!degenerate);
+            if degenerate {
+                continue;
+            }
+            self.alive_count += 1;
+
+            let normal = self.triangle_normal(&groups);
+            for i in 0..3 {
+                let group = groups[i] as usize;
+                self.group_triangles[group].push(index);
+                if let Some(normal) = normal {
+                    let quadric = Quadric::from_plane(normal, self.group_positions[group]);
+                    self.group_quadrics[group].add(&quadric);
+                }
+
+                let edge = (groups[i].min(groups[(i + 1) % 3]), groups[i].max(groups[(i + 1) % 3]));
+                *edge_counts.entry(edge).or_default() += 1;
+            }
+        }
+
+        // A plane perpendicular to every border edge keeps open boundaries from shrinking
+        for index in 0..self.triangles.len() {
+            if !self.alive[index] {
+                continue;
+            }
+
+            let groups = self.triangles[index].map(|vertex| self.group_of[vertex as usize]);
+            let normal = match self.triangle_normal(&groups) {
+                Some(normal) => normal,
+                None => continue
+            };
+
+            for i in 0..3 {
+                let (a, b) = (groups[i], groups[(i + 1) % 3]);
+                if edge_counts[&(a.min(b), a.max(b))] != 1 {
+                    continue;
+                }
+
+                let edge = self.group_positions[b as usize] - self.group_positions[a as usize];
+                let border_normal = edge.cross(normal);
+                if border_normal.magnitude2() > 0.0 {
+                    let quadric = Quadric::from_plane(border_normal.normalize(), self.group_positions[a as usize]);
+                    self.group_quadrics[a as usize].add(&quadric);
+                    self.group_quadrics[b as usize].add(&quadric);
+                }
+            }
+        }
+
+        for group in 0..self.group_positions.len() as u32 {
+            self.push_collapses(group);
+        }
+    }
+
+    fn triangle_normal(&self, groups: &[u32; 3]) -> Option<Vector3<f64>> {
+        let a = self.group_positions[groups[0] as usize];
+        let b = self.group_positions[groups[1] as usize];
+        let c = self.group_positions[groups[2] as usize];
+
+        let normal = (b - a).cross(c - a);
+        match normal.magnitude2() > 0.0 {
+            true => Some(normal.normalize()),
+            false => None
+        }
+    }
+
+    /// Queues the collapses of every edge around `group`, in both directions
+    fn push_collapses(&mut self, group: u32) {
+        let mut neighbours = Vec::new();
+        for triangle in &self.group_triangles[group as usize] {
+            if !self.alive[*triangle as usize] {
+                continue;
+            }
+
+            for vertex in self.triangles[*triangle as usize] {
+                let neighbour = self.group_of[vertex as usize];
+                if neighbour != group && !neighbours.contains(&neighbour) {
+                    neighbours.push(neighbour);
+                }
+            }
+        }
+
+        for neighbour in neighbours {
+            for (from, to) in [(group, neighbour), (neighbour, group)] {
+                let mut quadric = self.group_quadrics[from as usize];
+                quadric.add(&self.group_quadrics[to as usize]);
+
+                self.heap.push(Collapse {
+                    cost: quadric.error(self.group_positions[to as usize]),
+                    from: from,
+                    to: to,
+                    from_version: self.group_versions[from as usize],
+                    to_version: self.group_versions[to as usize]
+                });
+            }
+        }
+    }
+
+    /// Collapses until at most `target_triangle_count` triangles are left or the next collapse would cost more than `max_cost`.
+    /// Returns the highest cost of the applied collapses.
+    fn run(&mut self, target_triangle_count: usize, max_cost: f64) -> f64 {
+        let mut highest_cost: f64 = 0.0;
+
+        while self.alive_count > target_triangle_count {
+            let collapse = match self.heap.pop() {
+                Some(collapse) => collapse,
+                None => break
+            };
+
+            let (from, to) = (collapse.from as usize, collapse.to as usize);
+            if self.collapsed[from] || self.collapsed[to]
+                || self.group_versions[from] != collapse.from_version
+                || self.group_versions[to] != collapse.to_version {
+                continue;
+            }
+            if collapse.cost > max_cost {
+                break;
+            }
+
+            if self.try_collapse(collapse.from, collapse.to) {
+                highest_cost = highest_cost.max(collapse.cost);
+            }
+        }
+
+        highest_cost
+    }
+
+    fn try_collapse(&mut self, from: u32, to: u32) -> bool {
+        let from_triangles: Vec<u32> = self.group_triangles[from as usize].iter()
+            .copied()
+            .filter(|triangle| self.alive[*triangle as usize])
+            .collect();
+
+        // Every vertex at `from` moves to the vertex at `to` it shares a triangle with, so attributes stay continuous
+        let mut remap: Vec<(u32, u32)> = Vec::new();
+        for triangle in &from_triangles {
+            let vertices = self.triangles[*triangle as usize];
+            let target = vertices.iter().copied().find(|vertex| self.group_of[*vertex as usize] == to);
+
+            for vertex in vertices {
+                if self.group_of[vertex as usize] != from {
+                    continue;
+                }
+
+                if let Some(target) = target {
+                    if !remap.iter().any(|(source, _)| *source == vertex) {
+                        remap.push((vertex, target));
+                    }
+                }
+            }
+        }
+
+        let to_position = self.group_positions[to as usize];
+        for triangle in &from_triangles {
+            let vertices = self.triangles[*triangle as usize];
+            if vertices.iter().any(|vertex| self.group_of[*vertex as usize] == to) {
+                continue;
+            }
+
+            // A vertex that is not connected to the target has nothing to merge with
+            if vertices.iter().any(|vertex| self.group_of[*vertex as usize] == from && !remap.iter().any(|(source, _)| source == vertex)) {
+                return false;
+            }
+
+            // Moving the vertex must not flip the triangle
+            let groups = vertices.map(|vertex| self.group_of[vertex as usize]);
+            let positions = groups.map(|group| self.group_positions[group as usize]);
+            let moved = groups.map(|group| match group == from {
+                true => to_position,
+                false => self.group_positions[group as usize]
+            });
+
+            let before = (positions[1] - positions[0]).cross(positions[2] - positions[0]);
+            let after = (moved[1] - moved[0]).cross(moved[2] - moved[0]);
+            if before.dot(after) <= 0.0 {
+                return false;
+            }
+        }
+
+        for triangle in from_triangles {
+            let vertices = &mut self.triangles[triangle as usize];
+            if vertices.iter().any(|vertex| self.group_of[*vertex as usize] == to) {
+                self.alive[triangle as usize] = false;
+                self.alive_count -= 1;
+                continue;
+            }
+
+            for vertex in vertices.iter_mut() {
+                if let Some((_, target)) = remap.iter().find(|(source, _)| source == vertex) {
+                    *vertex = *target;
+                }
+            }
+            self.group_triangles[to as usize].push(triangle);
+        }
+
+        let from_quadric = self.group_quadrics[from as usize];
+        self.group_quadrics[to as usize].add(&from_quadric);
+        self.group_triangles[from as usize].clear();
+        self.collapsed[from as usize] = true;
+
+        let alive = &self.alive;
+        self.group_triangles[to as usize].retain(|triangle| alive[*triangle as usize]);
+        self.group_versions[to as usize] += 1;
+        self.push_collapses(to);
+
+        true
+    }
+
+    fn indices(&self) -> Vec<u32> {
+        self.triangles.iter()
+            .zip(self.alive.iter())
+            .filter(|(_, alive)| **alive)
+            .flat_map(|(triangle, _)| triangle.iter().copied())
+            .collect()
+    }
+}
+
+impl Mesh {
+    /// Radius of the sphere around the mesh bounds, the unit lod errors are expressed in
+    pub fn get_bounds_radius(&self) -> f32 {
+        (self.max - self.min).magnitude() * 0.5
+    }
+
+    /// Simplifies the mesh with quadric error metrics by collapsing vertices onto their neighbours, the vertices are not modified.
+    /// Stops at `target_index_count` or when the error relative to the bounds radius would exceed `max_error`.
+    /// Returns the new indices and the error they ended up with.
+    pub fn simplify(&self, target_index_count: usize, max_error: f32) -> (Vec<u32>, f32) {
+        if self.kind != MeshKind::Triangles {
+            return (self.indices.clone(), 0.0);
+        }
+
+        let radius = self.get_bounds_radius().max(f32::EPSILON) as f64;
+        let max_distance = max_error as f64 * radius;
+
+        let mut simplifier = Simplifier::new(self);
+        let cost = simplifier.run(target_index_count / 3, max_distance * max_distance);
+
+        // The quadric error is a squared distance to the original planes
+        (simplifier.indices(), (cost.sqrt() / radius) as f32)
+    }
+
+    /// Replaces the levels of detail with up to `max_lod_count` new ones, each with about `reduction` times the triangles of the previous.
+    /// Every level is simplified from the full resolution mesh and ordered for the vertex cache.
+    pub fn generate_lods(&mut self, max_lod_count: usize, reduction: f32) {
+        self.lods.clear();
+        if self.kind != MeshKind::Triangles {
+            return;
+        }
+
+        let mut index_count = self.indices.len();
+        let mut error: f32 = 0.0;
+        for _ in 0..max_lod_count {
+            let target_index_count = (index_count as f32 * reduction) as usize / 3 * 3;
+            if target_index_count < MIN_LOD_INDEX_COUNT {
+                break;
+            }
+
+            let (indices, lod_error) = self.simplify(target_index_count, 1.0);
+
+            // Stop once the mesh barely simplifies any further
+            if indices.len() as f32 > index_count as f32 * (1.0 + reduction) * 0.5 {
+                break;
+            }
+
+            // Coarser levels never claim to be more accurate than finer ones, lod selection relies on it
+            error = error.max(lod_error);
+            index_count = indices.len();
+
+            self.lods.push(MeshLod {
+                indices: Self::vertex_cache_order(&indices, self.vertices.len()),
+                error: error
+            });
+        }
+    }
+}
\ No newline at end of file
@@ -1,28 +1,43 @@
 extern crate gltf;
 extern crate stb_image;
 extern crate bitmask_enum;
+extern crate tobj;
+extern crate ply_rs;
+extern crate urlencoding;
 
 use bitmask_enum::bitmask;
-use cgmath::{Vector4, Vector3, Vector2, Quaternion, InnerSpace};
+use cgmath::{Vector4, Vector3, Vector2, Matrix4, SquareMatrix, Deg};
 
-use std::fs;
-use std::ffi::CString;
 use std::path::Path;
+use std::collections::HashSet;
+
+use crate::graphics::Transform;
 
 pub mod texture;
 pub use texture::*;
 pub mod model;
 pub use model::*;
+pub mod mesh_processing;
+pub use mesh_processing::*;
+mod mesh_simplification;
 
 pub mod resource;
 pub use resource::*;
+pub mod vfs;
+pub use vfs::*;
 
 mod resource_manager;
 use resource_manager::*;
+pub use resource_manager::ResourceSize;
+
+mod cache;
+use cache::*;
 
 #[bitmask(u8)]
 pub enum ImageImportSettings {
-    FlipVertical
+    FlipVertical,
+    /// Treat the image as sRGB encoded color data instead of linear data
+    Srgb
 }
 
 pub struct Resources {
@@ -31,237 +46,497 @@ pub struct Resources {
     image_manager: ResourceManager<Texture>,
     binary_blob_manager: ResourceManager<Vec<u8>>,
 
+    /// Every loader reads through the vfs, paths look like `game://models/helmet.gltf`
+    pub vfs: Vfs,
+    /// Applied to every mesh of a model imported from source, cooked models keep the optimizations they were cooked with
+    pub mesh_optimizations: MeshOptimizations,
     pub kill_time: f32
 }
 
+/// A resource type that is cached by `Resources`, used to pick the right manager in the generic cache functions
+pub trait ManagedResource: Clone + ResourceSize + Sized {
+    #[doc(hidden)]
+    fn manager(resources: &mut Resources) -> &mut ResourceManager<Self>;
+}
+
+impl ManagedResource for Model {
+    fn manager(resources: &mut Resources) -> &mut ResourceManager<Self> {
+        &mut resources.model_manager
+    }
+}
+
+impl ManagedResource for String {
+    fn manager(resources: &mut Resources) -> &mut ResourceManager<Self> {
+        &mut resources.text_manager
+    }
+}
+
+impl ManagedResource for Texture {
+    fn manager(resources: &mut Resources) -> &mut ResourceManager<Self> {
+        &mut resources.image_manager
+    }
+}
+
+impl ManagedResource for Vec<u8> {
+    fn manager(resources: &mut Resources) -> &mut ResourceManager<Self> {
+        &mut resources.binary_blob_manager
+    }
+}
+
+/// Faces meeting at a sharper angle keep a hard edge when normals are generated for formats without smoothing information
+const SMOOTHING_ANGLE: f32 = 60.0;
+
 impl Resources {
     pub(crate) fn init() -> Box<Resources> {
         Box::new(Resources {
-            model_manager: ResourceManager::new(5.0),
-            text_manager: ResourceManager::new(5.0),
-            image_manager: ResourceManager::new(5.0),
-            binary_blob_manager: ResourceManager::new(5.0),
+            model_manager: ResourceManager::new(5.0, None),
+            text_manager: ResourceManager::new(5.0, None),
+            image_manager: ResourceManager::new(5.0, None),
+            binary_blob_manager: ResourceManager::new(5.0, None),
+            vfs: Vfs::with_default_mounts(),
+            mesh_optimizations: MeshOptimizations::none(),
             kill_time: 5.0
         })
     }
 
     pub(crate) fn update(&mut self) {
+        self.model_manager.kill_time = self.kill_time;
+        self.text_manager.kill_time = self.kill_time;
+        self.image_manager.kill_time = self.kill_time;
+        self.binary_blob_manager.kill_time = self.kill_time;
+
         self.model_manager.update();
         self.text_manager.update();
         self.image_manager.update();
+        self.binary_blob_manager.update();
+    }
+
+    /// Sets the cpu memory budget in bytes for a resource type, e.g. `set_memory_budget::<Texture>(Some(256 * 1024 * 1024))`.
+    /// None removes the budget, unused resources are then dropped after `kill_time` seconds instead. No budgets are set by default.
+    pub fn set_memory_budget<T: ManagedResource>(&mut self, budget: Option<usize>) {
+        T::manager(self).budget = budget;
+    }
+
+    pub fn get_memory_budget<T: ManagedResource>(&mut self) -> Option<usize> {
+        T::manager(self).budget
+    }
+
+    /// Bytes used by all cached resources of a type, referenced or not
+    pub fn get_memory_usage<T: ManagedResource>(&mut self) -> usize {
+        T::manager(self).get_memory_usage()
+    }
+
+    pub fn get_resource_count<T: ManagedResource>(&mut self) -> usize {
+        T::manager(self).get_resource_count()
+    }
+
+    /// Keeps a resource cached even when nothing references it anymore
+    pub fn pin<T: ManagedResource>(&mut self, resource: &Resource<T>) {
+        T::manager(self).set_pinned(resource, true);
+    }
+
+    pub fn unpin<T: ManagedResource>(&mut self, resource: &Resource<T>) {
+        T::manager(self).set_pinned(resource, false);
+    }
+
+    /// Loads models into the cache ahead of time, later `get_model` calls return them without touching the disk.
+    /// Preloaded models are evicted like any other unused resource unless they are pinned.
+    pub fn preload_models(&mut self, asset_paths: &[String]) {
+        for asset_path in asset_paths {
+            self.get_model(asset_path.clone());
+        }
+    }
+
+    pub fn preload_textures(&mut self, asset_paths: &[String], import_settings: Option<ImageImportSettings>) {
+        for asset_path in asset_paths {
+            self.get_texture(asset_path.clone(), import_settings);
+        }
+    }
+
+    fn process_sampler(sampler: &gltf::texture::Sampler) -> TextureSampler {
+        let wrap = |wrap: gltf::texture::WrappingMode| match wrap {
+            gltf::texture::WrappingMode::Repeat => TextureWrap::Repeat,
+            gltf::texture::WrappingMode::MirroredRepeat => TextureWrap::MirroredRepeat,
+            gltf::texture::WrappingMode::ClampToEdge => TextureWrap::ClampToEdge
+        };
+
+        let (min_filter, mipmap_mode) = match sampler.min_filter() {
+            Some(gltf::texture::MinFilter::Nearest) => (TextureFilter::Nearest, None),
+            Some(gltf::texture::MinFilter::Linear) => (TextureFilter::Linear, None),
+            Some(gltf::texture::MinFilter::NearestMipmapNearest) => (TextureFilter::Nearest, Some(TextureFilter::Nearest)),
+            Some(gltf::texture::MinFilter::LinearMipmapNearest) => (TextureFilter::Linear, Some(TextureFilter::Nearest)),
+            Some(gltf::texture::MinFilter::NearestMipmapLinear) => (TextureFilter::Nearest, Some(TextureFilter::Linear)),
+            Some(gltf::texture::MinFilter::LinearMipmapLinear) | None => (TextureFilter::Linear, Some(TextureFilter::Linear))
+        };
+
+        let mag_filter = match sampler.mag_filter() {
+            Some(gltf::texture::MagFilter::Nearest) => TextureFilter::Nearest,
+            Some(gltf::texture::MagFilter::Linear) | None => TextureFilter::Linear
+        };
+
+        TextureSampler {
+            wrap_s: wrap(sampler.wrap_s()),
+            wrap_t: wrap(sampler.wrap_t()),
+            min_filter: min_filter,
+            mag_filter: mag_filter,
+            mipmap_mode: mipmap_mode
+        }
+    }
+
+    fn process_texture_transform(extension: Option<&gltf::json::Value>, tex_coord: u32) -> (TextureTransform, u32) {
+        let mut transform = TextureTransform::default();
+        let mut tex_coord = tex_coord;
+
+        if let Some(extension) = extension {
+            let vec2 = |key: &str| extension.get(key)
+                .and_then(|v| v.as_array())
+                .filter(|v| v.len() == 2)
+                .map(|v| Vector2::new(
+                    v[0].as_f64().unwrap_or(0.0) as f32,
+                    v[1].as_f64().unwrap_or(0.0) as f32
+                ));
+
+            if let Some(offset) = vec2("offset") {
+                transform.offset = offset;
+            }
+            if let Some(scale) = vec2("scale") {
+                transform.scale = scale;
+            }
+            if let Some(rotation) = Self::json_f32(extension, "rotation") {
+                transform.rotation = rotation;
+            }
+            // The transform may override the texture coordinate set
+            if let Some(transform_tex_coord) = extension.get("texCoord").and_then(|v| v.as_u64()) {
+                tex_coord = transform_tex_coord as u32;
+            }
+        }
+
+        (transform, tex_coord)
+    }
+
+    fn process_material_tex(&mut self, texture: &gltf::Texture, tex_coord: u32, transform: Option<&gltf::json::Value>, buffers: &Vec<gltf::buffer::Data>, base_path: &String, color_space: ColorSpace) -> MaterialTexture {
+        let (transform, tex_coord) = Self::process_texture_transform(transform, tex_coord);
+
+        MaterialTexture {
+            texture: self.process_tex(texture, buffers, base_path, color_space),
+            sampler: Self::process_sampler(&texture.sampler()),
+            tex_coord: tex_coord,
+            transform: transform
+        }
+    }
+
+    /// Processes a texture info of an extension that the gltf crate only exposes as raw json
+    fn process_json_tex(&mut self, info: Option<&gltf::json::Value>, document: &gltf::Document, buffers: &Vec<gltf::buffer::Data>, base_path: &String, color_space: ColorSpace) -> Option<MaterialTexture> {
+        let info = info?;
+        let index = info.get("index")?.as_u64()? as usize;
+        let texture = document.textures().nth(index)?;
+        let tex_coord = info.get("texCoord").and_then(|v| v.as_u64()).unwrap_or(0) as u32;
+        let transform = info.get("extensions").and_then(|e| e.get("KHR_texture_transform"));
+
+        Some(self.process_material_tex(&texture, tex_coord, transform, buffers, base_path, color_space))
+    }
+
+    fn json_f32(value: &gltf::json::Value, key: &str) -> Option<f32> {
+        value.get(key).and_then(|v| v.as_f64()).map(|v| v as f32)
+    }
+
+    fn json_vec3(value: &gltf::json::Value, key: &str) -> Option<Vector3<f32>> {
+        let v = value.get(key)?.as_array()?;
+        if v.len() != 3 {
+            return None;
+        }
+
+        Some(Vector3::new(
+            v[0].as_f64()? as f32,
+            v[1].as_f64()? as f32,
+            v[2].as_f64()? as f32
+        ))
+    }
+
+    fn process_material(&mut self, gltf_material: &gltf::Material, material: &mut Material, document: &gltf::Document, buffers: &Vec<gltf::buffer::Data>, base_path: &String) {
+        let pbr = gltf_material.pbr_metallic_roughness();
+
+        material.name = gltf_material.name().map(|s| s.into()).unwrap_or(String::from("Unnamed"));
+        material.base_color_factor = Vector4::from(pbr.base_color_factor());
+        material.metallic_factor = pbr.metallic_factor();
+        material.roughness_factor = pbr.roughness_factor();
+        material.emissive_factor = Vector3::from(gltf_material.emissive_factor());
+
+        if let Some(color_tex) = pbr.base_color_texture() {
+            material.base_color_texture = self.process_material_tex(&color_tex.texture(), color_tex.tex_coord(), color_tex.extension_value("KHR_texture_transform"), buffers, base_path, ColorSpace::Srgb);
+        }
+
+        if let Some(normal_tex) = gltf_material.normal_texture() {
+            material.normal_texture = self.process_material_tex(&normal_tex.texture(), normal_tex.tex_coord(), normal_tex.extension_value("KHR_texture_transform"), buffers, base_path, ColorSpace::Linear);
+            material.normal_scale = normal_tex.scale();
+        }
+
+        if let Some(mr_tex) = pbr.metallic_roughness_texture() {
+            material.metallic_roughness_texture = self.process_material_tex(&mr_tex.texture(), mr_tex.tex_coord(), mr_tex.extension_value("KHR_texture_transform"), buffers, base_path, ColorSpace::Linear);
+        }
+
+        if let Some(occlusion_tex) = gltf_material.occlusion_texture() {
+            material.occlusion_texture = self.process_material_tex(&occlusion_tex.texture(), occlusion_tex.tex_coord(), occlusion_tex.extension_value("KHR_texture_transform"), buffers, base_path, ColorSpace::Linear);
+            material.occlusion_strength = occlusion_tex.strength();
+        }
+
+        if let Some(emissive_tex) = gltf_material.emissive_texture() {
+            material.emissive_texture = self.process_material_tex(&emissive_tex.texture(), emissive_tex.tex_coord(), emissive_tex.extension_value("KHR_texture_transform"), buffers, base_path, ColorSpace::Srgb);
+        }
+
+        if let Some(emissive_strength) = gltf_material.emissive_strength() {
+            material.emissive_strength = emissive_strength;
+        }
+
+        if let Some(ior) = gltf_material.ior() {
+            material.ior = ior;
+        }
+
+        if let Some(transmission) = gltf_material.transmission() {
+            material.transmission_factor = transmission.transmission_factor();
+
+            if let Some(transmission_tex) = transmission.transmission_texture() {
+                material.transmission_texture = self.process_material_tex(&transmission_tex.texture(), transmission_tex.tex_coord(), transmission_tex.extension_value("KHR_texture_transform"), buffers, base_path, ColorSpace::Linear);
+            }
+        }
+
+        if let Some(specular) = gltf_material.specular() {
+            material.specular_factor = specular.specular_factor();
+            material.specular_color_factor = Vector3::from(specular.specular_color_factor());
+
+            if let Some(specular_tex) = specular.specular_texture() {
+                material.specular_texture = self.process_material_tex(&specular_tex.texture(), specular_tex.tex_coord(), specular_tex.extension_value("KHR_texture_transform"), buffers, base_path, ColorSpace::Linear);
+            }
+            if let Some(specular_color_tex) = specular.specular_color_texture() {
+                material.specular_color_texture = self.process_material_tex(&specular_color_tex.texture(), specular_color_tex.tex_coord(), specular_color_tex.extension_value("KHR_texture_transform"), buffers, base_path, ColorSpace::Srgb);
+            }
+        }
+
+        // Clearcoat and sheen are not exposed by the gltf crate
+        if let Some(clearcoat) = gltf_material.extension_value("KHR_materials_clearcoat") {
+            material.clearcoat_factor = Self::json_f32(clearcoat, "clearcoatFactor").unwrap_or(0.0);
+            material.clearcoat_roughness_factor = Self::json_f32(clearcoat, "clearcoatRoughnessFactor").unwrap_or(0.0);
+
+            if let Some(texture) = self.process_json_tex(clearcoat.get("clearcoatTexture"), document, buffers, base_path, ColorSpace::Linear) {
+                material.clearcoat_texture = texture;
+            }
+            if let Some(texture) = self.process_json_tex(clearcoat.get("clearcoatRoughnessTexture"), document, buffers, base_path, ColorSpace::Linear) {
+                material.clearcoat_roughness_texture = texture;
+            }
+            if let Some(normal_info) = clearcoat.get("clearcoatNormalTexture") {
+                if let Some(texture) = self.process_json_tex(Some(normal_info), document, buffers, base_path, ColorSpace::Linear) {
+                    material.clearcoat_normal_texture = texture;
+                    material.clearcoat_normal_scale = Self::json_f32(normal_info, "scale").unwrap_or(1.0);
+                }
+            }
+        }
+
+        if let Some(sheen) = gltf_material.extension_value("KHR_materials_sheen") {
+            material.sheen_color_factor = Self::json_vec3(sheen, "sheenColorFactor").unwrap_or(Vector3::new(0.0, 0.0, 0.0));
+            material.sheen_roughness_factor = Self::json_f32(sheen, "sheenRoughnessFactor").unwrap_or(0.0);
+
+            if let Some(texture) = self.process_json_tex(sheen.get("sheenColorTexture"), document, buffers, base_path, ColorSpace::Srgb) {
+                material.sheen_color_texture = texture;
+            }
+            if let Some(texture) = self.process_json_tex(sheen.get("sheenRoughnessTexture"), document, buffers, base_path, ColorSpace::Linear) {
+                material.sheen_roughness_texture = texture;
+            }
+        }
     }
 
-    fn process_tex(&mut self, texture: &gltf::Texture, base_path: &String) -> Resource<Texture> {
+    fn process_tex(&mut self, texture: &gltf::Texture, buffers: &Vec<gltf::buffer::Data>, base_path: &String, color_space: ColorSpace) -> Resource<Texture> {
+        let import_settings = match color_space {
+            ColorSpace::Srgb => ImageImportSettings::FlipVertical | ImageImportSettings::Srgb,
+            ColorSpace::Linear => ImageImportSettings::FlipVertical
+        };
+
         let img = texture.source();
         let img = match img.source() {
-            gltf::image::Source::Uri { uri, .. } => {
+            gltf::image::Source::Uri { uri, .. } if !uri.starts_with("data:") => {
                 let base_path = Path::new(base_path);
-                let path = base_path.parent().unwrap_or_else(|| Path::new("./")).join(uri);
-                self.get_texture(path.into_os_string().into_string().unwrap(), Some(ImageImportSettings::FlipVertical))
+                let uri = urlencoding::decode(uri).expect("Failed to get model. (Invalid image uri)");
+                let path = base_path.parent().unwrap_or_else(|| Path::new("./")).join(&*uri);
+                self.get_texture(path.into_os_string().into_string().unwrap(), Some(import_settings))
+            },
+            // Buffer views and data URIs are embedded in the model itself
+            _ => {
+                let key = match color_space {
+                    ColorSpace::Srgb => format!("{}#image{} (sRGB)", base_path, img.index()),
+                    ColorSpace::Linear => format!("{}#image{}", base_path, img.index())
+                };
+
+                match self.image_manager.get(&key) {
+                    Some(resource) => resource,
+                    None => {
+                        // Only the images that are used by a material are decoded
+                        let image = gltf::image::Data::from_source(img.source(), Some(Path::new("./")), buffers)
+                            .expect(&format!("Failed to get model. (Invalid image {} in \"{}\")", img.index(), base_path));
+                        let mut texture = Texture::from_gltf_image(&image);
+                        texture.color_space = color_space;
+
+                        let resource = Resource::new(texture);
+
+                        self.image_manager.insert(resource.clone(), key);
+                        resource
+                    }
+                }
             }
-            _ => panic!("Failed to process tex. (Only uri support)")
         };
         img
     }
 
-    fn process_node(&mut self, node: &gltf::Node, buffers: &Vec<gltf::buffer::Data>, _images: &Vec<gltf::image::Data>, base_path: &String, meshes: &mut Vec<Mesh>, materials: &mut Vec<Material>) {
-        let (translation, rotation, scale) = node.transform().decomposed();
-        let _translation = Vector3::new(translation[0], translation[1], translation[2]);
-        let _rotation = Quaternion::new(rotation[3], rotation[0], rotation[1], rotation[2]); // Correct order?!?!?!?
-        let _scale = Vector3::new(scale[0], scale[1], scale[2]);
+    /// Converts strips, fans and loops into lists of the matching mesh kind
+    fn process_primitive_mode(mode: gltf::mesh::Mode, indices: Vec<u32>) -> (MeshKind, Vec<u32>) {
+        match mode {
+            gltf::mesh::Mode::Triangles => (MeshKind::Triangles, indices),
+            gltf::mesh::Mode::TriangleStrip => {
+                let mut triangles = Vec::with_capacity(indices.len().saturating_sub(2) * 3);
+                for i in 0..indices.len().saturating_sub(2) {
+                    // Every other triangle is flipped to keep a consistent winding order
+                    if i % 2 == 0 {
+                        triangles.extend_from_slice(&[indices[i], indices[i + 1], indices[i + 2]]);
+                    } else {
+                        triangles.extend_from_slice(&[indices[i + 1], indices[i], indices[i + 2]]);
+                    }
+                }
+                (MeshKind::Triangles, triangles)
+            },
+            gltf::mesh::Mode::TriangleFan => {
+                let mut triangles = Vec::with_capacity(indices.len().saturating_sub(2) * 3);
+                for i in 1..indices.len().saturating_sub(1) {
+                    triangles.extend_from_slice(&[indices[i], indices[i + 1], indices[0]]);
+                }
+                (MeshKind::Triangles, triangles)
+            },
+            gltf::mesh::Mode::Lines => (MeshKind::Lines, indices),
+            gltf::mesh::Mode::LineStrip => (MeshKind::LineStrip, indices),
+            gltf::mesh::Mode::LineLoop => {
+                let mut indices = indices;
+                if let Some(first) = indices.first().cloned() {
+                    indices.push(first);
+                }
+                (MeshKind::LineStrip, indices)
+            },
+            gltf::mesh::Mode::Points => (MeshKind::Points, indices)
+        }
+    }
+
+    /// Imports the meshes of a node and its children, with the transforms of the hierarchy baked into their vertices
+    fn process_node(&mut self, node: &gltf::Node, parent_matrix: &Matrix4<f32>, document: &gltf::Document, buffers: &Vec<gltf::buffer::Data>, base_path: &String, meshes: &mut Vec<Mesh>, materials: &mut Vec<Material>) {
+        // glTF requires node matrices to be decomposable into translation, rotation and scale
+        let mut node_transform = Transform::from_matrix(&Matrix4::from(node.transform().matrix()));
+        let node_matrix = parent_matrix * node_transform.get_matrix(false);
+
+        for child in node.children() {
+            self.process_node(&child, &node_matrix, document, buffers, base_path, meshes, materials);
+        }
 
         match node.mesh() {
             Some(mesh) => {
                 for primitive in mesh.primitives() {
-                    if primitive.mode() == gltf::mesh::Mode::Triangles {
-                        let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
-
-                        let bounds = primitive.bounding_box();
-                        let min = Vector3::from(bounds.min);
-                        let max = Vector3::from(bounds.max);
-
-                        let positions = {
-                            let iter = reader
-                                .read_positions()
-                                .expect("Failed to process mesh node. (Vertices must have positions)");
-
-                            iter.map(|arr| -> Vector3<f32> { Vector3::from(arr) }).collect::<Vec<_>>()
-                        };
-
-                        let mut vertices: Vec<Vertex> = positions
-                            .into_iter()
-                            .map(|position| {
-                                Vertex {
-                                    position: Vector3::from(position),
-                                    ..Vertex::default()
-                                }
-                        }).collect();
-
-                        let indices = reader
-                            .read_indices()
-                            .map(|read_indices| {
-                                read_indices.into_u32().collect::<Vec<_>>()
-                            }).expect("Failed to process mesh node. (Indices are required)");
-
-                        if let Some(normals) = reader.read_normals() {
+                    let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+
+                    let bounds = primitive.bounding_box();
+                    let min = Vector3::from(bounds.min);
+                    let max = Vector3::from(bounds.max);
+
+                    let positions = {
+                        let iter = reader
+                            .read_positions()
+                            .expect("Failed to process mesh node. (Vertices must have positions)");
+
+                        iter.map(|arr| -> Vector3<f32> { Vector3::from(arr) }).collect::<Vec<_>>()
+                    };
+
+                    let mut vertices: Vec<Vertex> = positions
+                        .into_iter()
+                        .map(|position| {
+                            Vertex {
+                                position: Vector3::from(position),
+                                ..Vertex::default()
+                            }
+                    }).collect();
+
+                    // Non-indexed primitives draw their vertices in order
+                    let indices = reader
+                        .read_indices()
+                        .map(|read_indices| {
+                            read_indices.into_u32().collect::<Vec<_>>()
+                        }).unwrap_or_else(|| (0..vertices.len() as u32).collect());
+                    let (kind, indices) = Self::process_primitive_mode(primitive.mode(), indices);
+
+                    let has_normals = match reader.read_normals() {
+                        Some(normals) => {
                             for (i, normal) in normals.enumerate() {
                                 vertices[i].normal = Vector3::from(normal);
                             }
-                        }
+                            true
+                        },
+                        None => false
+                    };
 
-                        let mut tex_coord_channel = 0;
-                        while let Some(tex_coords) = reader.read_tex_coords(tex_coord_channel) {
-                            for (i, tex_coord) in tex_coords.into_f32().enumerate() {
-                                match tex_coord_channel {
-                                    0 => vertices[i].tex_coord = Vector2::from(tex_coord),
-                                    1 => vertices[i].tex_coord_1 = Vector2::from(tex_coord),
-                                    _ => {}
-                                }
+                    let mut tex_coord_channel = 0;
+                    while let Some(tex_coords) = reader.read_tex_coords(tex_coord_channel) {
+                        for (i, tex_coord) in tex_coords.into_f32().enumerate() {
+                            match tex_coord_channel {
+                                0 => vertices[i].tex_coord = Vector2::from(tex_coord),
+                                1 => vertices[i].tex_coord_1 = Vector2::from(tex_coord),
+                                _ => {}
                             }
-
-                            tex_coord_channel += 1;
                         }
 
-                        if let Some(tangents) = reader.read_tangents() {
+                        tex_coord_channel += 1;
+                    }
+
+                    let has_tangents = match reader.read_tangents() {
+                        Some(tangents) => {
                             for (i, tangent) in tangents.enumerate() {
                                 vertices[i].tangent = Vector4::from(tangent);
                             }
-                        } else {
-                            // Source: 2001. http://www.terathon.com/code/tangent.html
-                            let mut tan1 = vec![Vector3::new(0.0, 0.0, 0.0); vertices.len()];
-                            let mut tan2 = vec![Vector3::new(0.0, 0.0, 0.0); vertices.len()];
-
-                            for i in (0..indices.len()).step_by(3) {
-                                let i1 = indices[i + 0] as usize;
-                                let i2 = indices[i + 1] as usize;
-                                let i3 = indices[i + 2] as usize;
-                            
-                                let v1 = vertices[i1].position;
-                                let v2 = vertices[i2].position;
-                                let v3 = vertices[i3].position;
-                            
-                                let w1 = vertices[i1].tex_coord;
-                                let w2 = vertices[i2].tex_coord;
-                                let w3 = vertices[i3].tex_coord;
-                            
-                                let x1 = v2.x - v1.x;
-                                let x2 = v3.x - v1.x;
-                                let y1 = v2.y - v1.y;
-                                let y2 = v3.y - v1.y;
-                                let z1 = v2.z - v1.z;
-                                let z2 = v3.z - v1.z;
-
-                                let s1 = w2.x - w1.x;
-                                let s2 = w3.x - w1.x;
-                                let t1 = w2.y - w1.y;
-                                let t2 = w3.y - w1.y;
-
-                                let rdiv = s1 * t2 - s2 * t1;
-                                let r;
-                                if rdiv == 0.0 {
-                                    r = 0.0;
-                                } else {
-                                    r = 1.0 / rdiv;
-                                }
-
-                                let sdir = Vector3::new(
-                                    (t2 * x1 - t1 * x2) * r,
-                                    (t2 * y1 - t1 * y2) * r,
-                                    (t2 * z1 - t1 * z2) * r
-                                );
-
-                                let tdir = Vector3::new(
-                                    (s1 * x2 - s2 * x1) * r,
-                                    (s1 * y2 - s2 * y1) * r,
-                                    (s1 * z2 - s2 * z1) * r
-                                );
-                            
-                                tan1[i1] += sdir;
-                                tan1[i2] += sdir;
-                                tan1[i3] += sdir;
-                            
-                                tan2[i1] += tdir;
-                                tan2[i2] += tdir;
-                                tan2[i3] += tdir;
-                            }
-                        
-                            for i in 0..vertices.len() {
-                                let n = vertices[i].normal;
-                                let t = tan1[i];
-                            
-                                let mut xyz = t - (n * n.dot(t));
-                                if xyz.magnitude() != 0.0 {
-                                    xyz = xyz.normalize();
-                                }
-                            
-                                let w;
-                                if n.cross(t).dot(tan2[i]) < 0.0 {
-                                    w = -1.0;
-                                } else {
-                                    w = 1.0;
-                                }
-
-                                if xyz.x.is_nan() {
-                                    println!("REEE");
-                                }
-
-                                vertices[i].tangent = Vector4::new(xyz.x, xyz.y, xyz.z, w);
-                            }
-                        }
+                            true
+                        },
+                        None => false
+                    };
 
-                        if let Some(colors) = reader.read_colors(0) {
-                            let colors = colors.into_rgba_f32();
-                            for (i, color) in colors.enumerate() {
-                                vertices[i].color = Vector4::from(color);
-                            }
+                    if let Some(colors) = reader.read_colors(0) {
+                        let colors = colors.into_rgba_f32();
+                        for (i, color) in colors.enumerate() {
+                            vertices[i].color = Vector4::from(color);
                         }
-                        
-                        let prim_material = primitive.material();
-                        let pbr = prim_material.pbr_metallic_roughness();
-                        let material_idx = primitive.material().index().unwrap_or(0);
-
-                        let material = &mut materials[material_idx];
-                        if material.index == None {
-                            material.index = Some(material_idx);
-                            material.name = prim_material.name().map(|s| s.into()).unwrap_or(String::from("Unnamed"));
-                            material.base_color_factor = Vector4::from(pbr.base_color_factor());
-                            material.metallic_factor = pbr.metallic_factor();
-                            material.roughness_factor = pbr.roughness_factor();
-                            material.emissive_factor = Vector3::from(prim_material.emissive_factor());
-
-                            if let Some(color_tex) = pbr.base_color_texture() {
-                                material.base_color_texture = self.process_tex(&color_tex.texture(), base_path);
-                            }
-
-                            if let Some(normal_tex) = prim_material.normal_texture() {
-                                material.normal_texture = self.process_tex(&normal_tex.texture(), base_path);
-                                material.normal_scale = normal_tex.scale();
-                            }
+                    }
+                    
+                    let prim_material = primitive.material();
+                    let material_idx = primitive.material().index().unwrap_or(0);
 
-                            if let Some(mr_tex) = pbr.metallic_roughness_texture() {
-                                material.metallic_roughness_texture = self.process_tex(&mr_tex.texture(), base_path);
-                            }
+                    let material = &mut materials[material_idx];
+                    if material.index == None {
+                        material.index = Some(material_idx);
+                        self.process_material(&prim_material, material, document, buffers, base_path);
+                    }
 
-                            if let Some(occlusion_tex) = prim_material.occlusion_texture() {
-                                material.occlusion_texture = self.process_tex(&occlusion_tex.texture(), base_path);
-                                material.occlusion_strength = occlusion_tex.strength();
-                            }
+                    let mut mesh = Mesh {
+                        vertices: vertices,
+                        indices: indices,
+                        lods: Vec::new(),
+                        min: min,
+                        max: max,
+                        material_idx: material_idx,
+                        kind: kind
+                    };
 
-                            if let Some(emissive_tex) = prim_material.emissive_texture() {
-                                material.emissive_texture = self.process_tex(&emissive_tex.texture(), base_path);
-                            }
-                        }
+                    // The glTF spec asks for flat normals when they are missing, tangents then follow from those normals
+                    if !has_normals {
+                        mesh.generate_flat_normals();
+                    }
+                    if !has_normals || !has_tangents {
+                        mesh.generate_tangents();
+                    }
 
-                        meshes.push(Mesh {
-                            vertices: vertices,
-                            indices: indices,
-                            min: min,
-                            max: max,
-                            material_idx: material_idx
-                        });
-                    } else {
-                        panic!("Failed to process mesh node. (Trying to parse a non-triangle)");
+                    if node_matrix != Matrix4::identity() {
+                        mesh.transform(&node_matrix);
                     }
+
+                    meshes.push(mesh);
                 }
             },
             None => {}
@@ -272,31 +547,393 @@ impl Resources {
         match self.model_manager.get(&asset_path) {
             Some(resource) => resource,
             None => {
-                let (document, buffers, images) = gltf::import(asset_path.clone()).expect("Failed to get model.");
+                // A cooked model next to the source skips all of the importing work
+                let cooked_path = cooked_path(&asset_path, MODEL_EXTENSION);
+                let cooked_model = match cooked_path != asset_path && is_cooked_fresh(&self.vfs, &asset_path, &cooked_path) {
+                    true => self.try_load_cooked_model(&cooked_path),
+                    false => None
+                };
+                let model = match cooked_model {
+                    Some(model) => model,
+                    None => self.load_source_model(&asset_path)
+                };
+
+                let resource = Resource::new(model);
+
+                self.model_manager.insert(resource.clone(), asset_path);
+                resource
+            }
+        }
+    }
+
+    fn load_source_model(&mut self, asset_path: &String) -> Model {
+        let extension = Path::new(asset_path).extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_lowercase());
+
+        let mut model = match extension.as_deref() {
+            Some(MODEL_EXTENSION) => return self.try_load_cooked_model(asset_path)
+                .expect(&format!("Failed to get model. (\"{}\" is invalid or was cooked by another engine version)", asset_path)),
+            Some("obj") => self.load_obj_model(asset_path),
+            Some("ply") => self.load_ply_model(asset_path),
+            _ => self.load_gltf_model(asset_path)
+        };
+
+        if !self.mesh_optimizations.is_none() {
+            for mesh in model.meshes.iter_mut() {
+                mesh.optimize(self.mesh_optimizations);
+            }
+        }
+        model
+    }
 
-                let mut meshes = Vec::new();
-                let mut materials = vec![Material::default(); document.materials().len()];
+    /// Imports a source model and writes it as a .chrmodel next to it, together with its textures as .chrtex files.
+    /// Later `get_model` calls on the source load the cooked files as long as they are newer than the source.
+    /// Returns the path of the cooked model.
+    pub fn cook_model(&mut self, asset_path: String) -> String {
+        let cooked_path = cooked_path(&asset_path, MODEL_EXTENSION);
+        assert!(cooked_path != asset_path, "Failed to cook model. (\"{}\" is already cooked)", asset_path);
 
-                for node in document.nodes() {
-                    self.process_node(&node, &buffers, &images, &asset_path, &mut meshes, &mut materials);
+        let disk_path = self.vfs.disk_path(&cooked_path)
+            .expect(&format!("Failed to cook model. (\"{}\" is not on a writable mount)", asset_path));
+
+        let model = self.load_source_model(&asset_path);
+        model.cook(&disk_path);
+        cooked_path
+    }
+
+    fn load_gltf_model(&mut self, asset_path: &String) -> Model {
+        let bytes = self.vfs.read(asset_path).expect(&format!("Failed to read model file at \"{:?}\"", asset_path));
+        let gltf::Gltf { document, mut blob } = gltf::Gltf::from_slice(&bytes).expect("Failed to get model.");
+        let buffers = self.load_gltf_buffers(&document, &mut blob, asset_path);
+
+        let mut meshes = Vec::new();
+        let mut materials = vec![Material::default(); document.materials().len()];
+
+        // Only nodes that are part of a scene are shown, files without scenes import every root node
+        let identity = Matrix4::identity();
+        match document.default_scene().or_else(|| document.scenes().next()) {
+            Some(scene) => {
+                for node in scene.nodes() {
+                    self.process_node(&node, &identity, &document, &buffers, asset_path, &mut meshes, &mut materials);
                 }
+            },
+            None => {
+                let children: HashSet<usize> = document.nodes()
+                    .flat_map(|node| node.children().map(|child| child.index()))
+                    .collect();
+                for node in document.nodes().filter(|node| !children.contains(&node.index())) {
+                    self.process_node(&node, &identity, &document, &buffers, asset_path, &mut meshes, &mut materials);
+                }
+            }
+        }
 
-                let resource = Resource::new(Model {
-                    meshes: meshes,
-                    materials: materials.into_iter().map(|m| Resource::new(m)).collect()
-                });
+        Model {
+            meshes: meshes,
+            materials: materials.into_iter().map(|m| Resource::new(m)).collect()
+        }
+    }
 
-                self.model_manager.insert(resource.clone(), asset_path);
-                resource
+    /// External buffers are read through the vfs instead of relative to the working directory
+    fn load_gltf_buffers(&self, document: &gltf::Document, blob: &mut Option<Vec<u8>>, asset_path: &String) -> Vec<gltf::buffer::Data> {
+        let base_path = Path::new(asset_path).parent().unwrap_or_else(|| Path::new("./"));
+
+        document.buffers().map(|buffer| {
+            let mut data = match buffer.source() {
+                gltf::buffer::Source::Uri(uri) if !uri.starts_with("data:") => {
+                    let uri = urlencoding::decode(uri).expect("Failed to get model. (Invalid buffer uri)");
+                    let path = base_path.join(&*uri).into_os_string().into_string().unwrap();
+                    self.vfs.read(&path).expect(&format!("Failed to read model buffer at \"{:?}\"", path))
+                },
+                source => gltf::buffer::Data::from_source_and_blob(source, None, blob).expect("Failed to get model.").0
+            };
+
+            assert!(data.len() >= buffer.length(), "Failed to get model. (Buffer {} is too short)", buffer.index());
+            while data.len() % 4 != 0 {
+                data.push(0);
+            }
+            gltf::buffer::Data(data)
+        }).collect()
+    }
+
+    fn load_obj_model(&mut self, asset_path: &String) -> Model {
+        let load_options = tobj::LoadOptions {
+            single_index: true,
+            triangulate: true,
+            ignore_points: true,
+            ignore_lines: true
+        };
+        let bytes = self.vfs.read(asset_path).expect(&format!("Failed to read model file at \"{:?}\"", asset_path));
+        let base_path = Path::new(asset_path).parent().unwrap_or_else(|| Path::new("./"));
+        let (obj_models, obj_materials) = tobj::load_obj_buf(&mut std::io::Cursor::new(bytes), &load_options, |mtl_path| {
+            let mtl_path = base_path.join(mtl_path).into_os_string().into_string().unwrap();
+            let mtl_bytes = self.vfs.read(&mtl_path).ok_or(tobj::LoadError::OpenFileFailed)?;
+            tobj::load_mtl_buf(&mut std::io::Cursor::new(mtl_bytes))
+        }).expect(&format!("Failed to get model. (Invalid obj file \"{}\")", asset_path));
+
+        // A missing mtl file is not fatal, the meshes fall back to the default material
+        let obj_materials = obj_materials.unwrap_or_default();
+        let default_material_idx = obj_materials.len();
+
+        let mut materials: Vec<Material> = obj_materials.iter()
+            .enumerate()
+            .map(|(i, obj_material)| self.process_mtl_material(obj_material, i, asset_path))
+            .collect();
+        materials.push(Material::default());
+
+        let mut meshes = Vec::new();
+        for obj_model in obj_models {
+            let obj_mesh = obj_model.mesh;
+            let vertex_count = obj_mesh.positions.len() / 3;
+
+            let mut vertices = Vec::with_capacity(vertex_count);
+            for i in 0..vertex_count {
+                let mut vertex = Vertex {
+                    position: Vector3::new(obj_mesh.positions[i * 3], obj_mesh.positions[i * 3 + 1], obj_mesh.positions[i * 3 + 2]),
+                    ..Vertex::default()
+                };
+
+                if obj_mesh.normals.len() >= (i + 1) * 3 {
+                    vertex.normal = Vector3::new(obj_mesh.normals[i * 3], obj_mesh.normals[i * 3 + 1], obj_mesh.normals[i * 3 + 2]);
+                }
+                // Obj texture coordinates start at the bottom left
+                if obj_mesh.texcoords.len() >= (i + 1) * 2 {
+                    vertex.tex_coord = Vector2::new(obj_mesh.texcoords[i * 2], 1.0 - obj_mesh.texcoords[i * 2 + 1]);
+                }
+                if obj_mesh.vertex_color.len() >= (i + 1) * 3 {
+                    vertex.color = Vector4::new(obj_mesh.vertex_color[i * 3], obj_mesh.vertex_color[i * 3 + 1], obj_mesh.vertex_color[i * 3 + 2], 1.0);
+                }
+
+                vertices.push(vertex);
+            }
+
+            let (min, max) = Self::vertex_bounds(&vertices);
+            let mut mesh = Mesh {
+                vertices: vertices,
+                indices: obj_mesh.indices,
+                lods: Vec::new(),
+                min: min,
+                max: max,
+                material_idx: obj_mesh.material_id.unwrap_or(default_material_idx),
+                kind: MeshKind::Triangles
+            };
+
+            if obj_mesh.normals.is_empty() {
+                mesh.generate_smooth_normals(Deg(SMOOTHING_ANGLE));
+            }
+            mesh.generate_tangents();
+
+            meshes.push(mesh);
+        }
+
+        Model {
+            meshes: meshes,
+            materials: materials.into_iter().map(|m| Resource::new(m)).collect()
+        }
+    }
+
+    /// Maps the classic and PBR extension (Pr, Pm, Ke, ...) mtl parameters onto a metallic roughness material
+    fn process_mtl_material(&mut self, obj_material: &tobj::Material, index: usize, asset_path: &String) -> Material {
+        let mut material = Material::default();
+        material.name = obj_material.name.clone();
+        material.index = Some(index);
+
+        let param = |key: &str| obj_material.unknown_param.get(key)
+            .and_then(|value| value.trim().parse::<f32>().ok());
+
+        let diffuse = obj_material.diffuse.unwrap_or([1.0, 1.0, 1.0]);
+        let dissolve = obj_material.dissolve.unwrap_or(1.0);
+        material.base_color_factor = Vector4::new(diffuse[0], diffuse[1], diffuse[2], dissolve);
+
+        if let Some(emissive) = obj_material.emissive {
+            material.emissive_factor = Vector3::from(emissive);
+        }
+
+        material.metallic_factor = param("Pm").unwrap_or(0.0);
+        material.roughness_factor = match (param("Pr"), obj_material.shininess) {
+            (Some(roughness), _) => roughness,
+            // Blinn-Phong exponent to GGX, alpha = sqrt(2 / (Ns + 2)) and roughness = sqrt(alpha)
+            (None, Some(shininess)) => (2.0 / (shininess.max(0.0) + 2.0)).powf(0.25),
+            (None, None) => 1.0
+        };
+
+        if let Some(ior) = obj_material.optical_density {
+            material.ior = ior;
+        }
+        if let Some(clearcoat) = param("Pc") {
+            material.clearcoat_factor = clearcoat;
+        }
+        if let Some(clearcoat_roughness) = param("Pcr") {
+            material.clearcoat_roughness_factor = clearcoat_roughness;
+        }
+        if let Some(sheen) = param("Ps") {
+            material.sheen_color_factor = Vector3::new(sheen, sheen, sheen);
+        }
+
+        let base_path = Path::new(asset_path).parent().unwrap_or_else(|| Path::new("./")).to_path_buf();
+        // Texture statements may carry options in front of the file name (map_Bump -bm 1.0 normal.png)
+        let texture_path = |statement: &String| statement.split_whitespace().last()
+            .map(|file| base_path.join(file).into_os_string().into_string().unwrap());
+
+        let mut load = |statement: Option<&String>, color_space: ColorSpace| -> Option<Resource<Texture>> {
+            let path = texture_path(statement?)?;
+            let import_settings = match color_space {
+                ColorSpace::Srgb => ImageImportSettings::FlipVertical | ImageImportSettings::Srgb,
+                ColorSpace::Linear => ImageImportSettings::FlipVertical
+            };
+            Some(self.get_texture(path, Some(import_settings)))
+        };
+
+        if let Some(texture) = load(obj_material.diffuse_texture.as_ref(), ColorSpace::Srgb) {
+            material.base_color_texture.texture = texture;
+        }
+        if let Some(texture) = load(obj_material.unknown_param.get("map_Ke"), ColorSpace::Srgb) {
+            material.emissive_texture.texture = texture;
+        }
+        if let Some(texture) = load(obj_material.unknown_param.get("norm").or(obj_material.normal_texture.as_ref()), ColorSpace::Linear) {
+            material.normal_texture.texture = texture;
+        }
+
+        let roughness_texture = load(obj_material.unknown_param.get("map_Pr"), ColorSpace::Linear);
+        let metallic_texture = load(obj_material.unknown_param.get("map_Pm"), ColorSpace::Linear);
+        if roughness_texture.is_some() || metallic_texture.is_some() {
+            material.metallic_roughness_texture.texture = Resource::new(
+                Texture::pack_metallic_roughness(roughness_texture, metallic_texture)
+            );
+        }
+
+        material
+    }
+
+    fn load_ply_model(&self, asset_path: &String) -> Model {
+        use ply_rs::ply::Property;
+
+        let bytes = self.vfs.read(asset_path).expect(&format!("Failed to read model file at \"{:?}\"", asset_path));
+        let mut reader = std::io::Cursor::new(bytes);
+        let ply = ply_rs::parser::Parser::<ply_rs::ply::DefaultElement>::new()
+            .read_ply(&mut reader)
+            .expect(&format!("Failed to get model. (Invalid ply file \"{}\")", asset_path));
+
+        let scalar = |property: &Property| -> Option<f32> {
+            match *property {
+                Property::Char(v) => Some(v as f32),
+                Property::UChar(v) => Some(v as f32),
+                Property::Short(v) => Some(v as f32),
+                Property::UShort(v) => Some(v as f32),
+                Property::Int(v) => Some(v as f32),
+                Property::UInt(v) => Some(v as f32),
+                Property::Float(v) => Some(v),
+                Property::Double(v) => Some(v as f32),
+                _ => None
+            }
+        };
+        // Integer colors are stored as 0-255, floating point colors as 0-1
+        let color = |property: &Property| -> Option<f32> {
+            match *property {
+                Property::Float(v) => Some(v),
+                Property::Double(v) => Some(v as f32),
+                _ => scalar(property).map(|v| v / 255.0)
+            }
+        };
+        let list = |property: &Property| -> Option<Vec<u32>> {
+            match property {
+                Property::ListChar(v) => Some(v.iter().map(|i| *i as u32).collect()),
+                Property::ListUChar(v) => Some(v.iter().map(|i| *i as u32).collect()),
+                Property::ListShort(v) => Some(v.iter().map(|i| *i as u32).collect()),
+                Property::ListUShort(v) => Some(v.iter().map(|i| *i as u32).collect()),
+                Property::ListInt(v) => Some(v.iter().map(|i| *i as u32).collect()),
+                Property::ListUInt(v) => Some(v.clone()),
+                _ => None
+            }
+        };
+
+        let ply_vertices = ply.payload.get("vertex").expect("Failed to get model. (Ply file has no vertices)");
+
+        let mut vertices = Vec::with_capacity(ply_vertices.len());
+        let mut has_normals = false;
+        for ply_vertex in ply_vertices {
+            let get = |keys: &[&str]| keys.iter().find_map(|key| ply_vertex.get(*key).and_then(|p| scalar(p)));
+            let get_color = |key: &str| ply_vertex.get(key).and_then(|p| color(p));
+
+            let mut vertex = Vertex {
+                position: Vector3::new(get(&["x"]).unwrap_or(0.0), get(&["y"]).unwrap_or(0.0), get(&["z"]).unwrap_or(0.0)),
+                ..Vertex::default()
+            };
+
+            if let (Some(r), Some(g), Some(b)) = (get_color("red"), get_color("green"), get_color("blue")) {
+                vertex.color = Vector4::new(r, g, b, get_color("alpha").unwrap_or(1.0));
+            }
+            if let (Some(nx), Some(ny), Some(nz)) = (get(&["nx"]), get(&["ny"]), get(&["nz"])) {
+                vertex.normal = Vector3::new(nx, ny, nz);
+                has_normals = true;
+            }
+            if let (Some(u), Some(v)) = (get(&["u", "s", "texture_u"]), get(&["v", "t", "texture_v"])) {
+                vertex.tex_coord = Vector2::new(u, 1.0 - v);
             }
+
+            vertices.push(vertex);
+        }
+
+        let mut indices = Vec::new();
+        if let Some(faces) = ply.payload.get("face") {
+            for face in faces {
+                let face_indices = face.get("vertex_indices")
+                    .or(face.get("vertex_index"))
+                    .and_then(|p| list(p))
+                    .unwrap_or_default();
+
+                // Polygons are triangulated as fans
+                for i in 1..face_indices.len().saturating_sub(1) {
+                    indices.extend_from_slice(&[face_indices[0], face_indices[i], face_indices[i + 1]]);
+                }
+            }
+        }
+
+        // Without faces the file is a point cloud
+        let kind = if indices.is_empty() {
+            indices = (0..vertices.len() as u32).collect();
+            MeshKind::Points
+        } else {
+            MeshKind::Triangles
+        };
+
+        let (min, max) = Self::vertex_bounds(&vertices);
+        let mut mesh = Mesh {
+            vertices: vertices,
+            indices: indices,
+            lods: Vec::new(),
+            min: min,
+            max: max,
+            material_idx: 0,
+            kind: kind
+        };
+
+        if !has_normals {
+            mesh.generate_smooth_normals(Deg(SMOOTHING_ANGLE));
+        }
+        mesh.generate_tangents();
+
+        Model {
+            meshes: vec![mesh],
+            materials: vec![Resource::new(Material::default())]
         }
     }
 
+    fn vertex_bounds(vertices: &Vec<Vertex>) -> (Vector3<f32>, Vector3<f32>) {
+        let mut min = Vector3::new(f32::MAX, f32::MAX, f32::MAX);
+        let mut max = Vector3::new(f32::MIN, f32::MIN, f32::MIN);
+        for vertex in vertices {
+            min = Vector3::new(min.x.min(vertex.position.x), min.y.min(vertex.position.y), min.z.min(vertex.position.z));
+            max = Vector3::new(max.x.max(vertex.position.x), max.y.max(vertex.position.y), max.z.max(vertex.position.z));
+        }
+        (min, max)
+    }
+
     pub fn get_text(&mut self, asset_path: String) -> Resource<String> {
         match self.text_manager.get(&asset_path) {
             Some(resource) => resource,
             None => {
-                let contents = fs::read_to_string(asset_path.clone()).expect(&format!("Failed to read text file at \"{:?}\"", asset_path));
+                let contents = self.vfs.read_to_string(&asset_path).expect(&format!("Failed to read text file at \"{:?}\"", asset_path));
                 let resource = Resource::new(contents);
 
                 self.text_manager.insert(resource.clone(), asset_path);
@@ -306,45 +943,81 @@ impl Resources {
     }
 
     pub fn get_texture(&mut self, asset_path: String, import_settings: Option<ImageImportSettings>) -> Resource<Texture> {
-        match self.image_manager.get(&asset_path) {
+        let color_space = match import_settings {
+            Some(import_settings) if import_settings.contains(ImageImportSettings::Srgb) => ColorSpace::Srgb,
+            _ => ColorSpace::Linear
+        };
+
+        // The same image used as color and as data ends up as two separate textures
+        let key = match color_space {
+            ColorSpace::Srgb => format!("{} (sRGB)", asset_path),
+            ColorSpace::Linear => asset_path.clone()
+        };
+
+        match self.image_manager.get(&key) {
             Some(resource) => resource,
             None => {
-                let c_asset_path = CString::new(asset_path.as_bytes()).unwrap();
+                let extension = Path::new(&asset_path).extension()
+                    .and_then(|extension| extension.to_str())
+                    .map(|extension| extension.to_lowercase());
 
-                unsafe {
-                    if let Some(import_settings) = import_settings {
-                        if !import_settings.contains(ImageImportSettings::FlipVertical) {
-                            stb_image::stb_image::bindgen::stbi_set_flip_vertically_on_load(1);
-                        }
-                    }
-            
-                    let mut width = 0;
-                    let mut height = 0;
-                    let mut channels = 0;
-                    let data = stb_image::stb_image::bindgen::stbi_load(
-                c_asset_path.as_ptr(),
-                        &mut width,
-                        &mut height,
-                        &mut channels,
-                        4
-                    );
-                    assert!(!data.is_null(), "Failed to read texture file at \"{:?}\"", asset_path);
-                    let data: Vec<u8> = std::slice::from_raw_parts(data, (width * height * 4) as usize).to_vec();
-
-                    let mip_levels = ((width.max(height) as f32).log2().floor() as u32) + 1;
-
-                    let resource = Resource::new(Texture {
-                        data: data,
-                        width: width as u32,
-                        height: height as u32,
-                        channel_count: 4 as u32,
-                        mip_levels: mip_levels
-                    });
-
-                    self.image_manager.insert(resource.clone(), asset_path);
-                    resource
+                // Block compressed containers can't be flipped on load, they are expected to be authored top-down.
+                // Cooked textures were already flipped when they were cooked.
+                let mut texture = match extension.as_deref() {
+                    Some("ktx2") => Texture::from_ktx2(&self.read_bytes(&asset_path), &asset_path),
+                    Some("dds") => Texture::from_dds(&self.read_bytes(&asset_path), &asset_path),
+                    Some("basis") => Texture::from_basis(self.read_bytes(&asset_path), &asset_path),
+                    Some(TEXTURE_EXTENSION) => Texture::from_cooked(&self.read_bytes(&asset_path), &asset_path),
+                    _ => Self::load_stb_texture(&self.read_bytes(&asset_path), &asset_path, import_settings)
+                };
+                texture.color_space = color_space;
+
+                let resource = Resource::new(texture);
+
+                self.image_manager.insert(resource.clone(), key);
+                resource
+            }
+        }
+    }
+
+    fn read_bytes(&self, asset_path: &String) -> Vec<u8> {
+        self.vfs.read(asset_path).expect(&format!("Failed to read texture file at \"{:?}\"", asset_path))
+    }
+
+    fn load_stb_texture(bytes: &[u8], asset_path: &String, import_settings: Option<ImageImportSettings>) -> Texture {
+        unsafe {
+            if let Some(import_settings) = import_settings {
+                if !import_settings.contains(ImageImportSettings::FlipVertical) {
+                    stb_image::stb_image::bindgen::stbi_set_flip_vertically_on_load(1);
                 }
             }
+    
+            let mut width = 0;
+            let mut height = 0;
+            let mut channels = 0;
+            let data = stb_image::stb_image::bindgen::stbi_load_from_memory(
+                bytes.as_ptr(),
+                bytes.len() as i32,
+                &mut width,
+                &mut height,
+                &mut channels,
+                4
+            );
+            assert!(!data.is_null(), "Failed to read texture file at \"{:?}\"", asset_path);
+            let data: Vec<u8> = std::slice::from_raw_parts(data, (width * height * 4) as usize).to_vec();
+
+            let mip_levels = ((width.max(height) as f32).log2().floor() as u32) + 1;
+
+            Texture {
+                data: data,
+                width: width as u32,
+                height: height as u32,
+                channel_count: 4 as u32,
+                mip_levels: mip_levels,
+                format: TextureFormat::Rgba8,
+                color_space: ColorSpace::Linear,
+                mip_offsets: Vec::new()
+            }
         }
     }
 
@@ -352,12 +1025,8 @@ impl Resources {
         match self.binary_blob_manager.get(&asset_path) {
             Some(resource) => resource,
             None => {
-                use std::fs::File;
-                use std::io::Read;
-
-                let spv_file = File::open(&asset_path)
+                let bytes_code = self.vfs.read(&asset_path)
                     .expect(&format!("Failed to read binary file at \"{:?}\"", asset_path));
-                let bytes_code: Vec<u8> = spv_file.bytes().filter_map(|byte| byte.ok()).collect();
 
                 let resource = Resource::new(bytes_code);
 
@@ -366,4 +1035,13 @@ impl Resources {
             }
         }
     }
+}
+
+/// Offline cook step, converts every source model to the engine native .chrmodel and .chrtex formats.
+/// Can be run without a window or renderer.
+pub fn cook(asset_paths: &[String]) {
+    let mut resources = Resources::init();
+    for asset_path in asset_paths {
+        resources.cook_model(asset_path.clone());
+    }
 }
\ No newline at end of file
@@ -1,7 +1,115 @@
-use cgmath::{Vector4, Vector3, Vector2};
+use cgmath::{Vector4, Vector3, Vector2, Matrix3, Zero};
 
 use crate::resources::Texture;
 use crate::resources::Resource;
+use crate::resources::ResourceSize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TextureWrap {
+    Repeat,
+    MirroredRepeat,
+    ClampToEdge
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TextureFilter {
+    Nearest,
+    Linear
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TextureSampler {
+    pub wrap_s: TextureWrap,
+    pub wrap_t: TextureWrap,
+    pub min_filter: TextureFilter,
+    pub mag_filter: TextureFilter,
+    /// None disables mipmapping, only the base level is sampled
+    pub mipmap_mode: Option<TextureFilter>
+}
+
+impl Default for TextureSampler {
+    fn default() -> Self {
+        TextureSampler {
+            wrap_s: TextureWrap::Repeat,
+            wrap_t: TextureWrap::Repeat,
+            min_filter: TextureFilter::Linear,
+            mag_filter: TextureFilter::Linear,
+            mipmap_mode: Some(TextureFilter::Linear)
+        }
+    }
+}
+
+/// UV transform from KHR_texture_transform, applied as translation * rotation * scale
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextureTransform {
+    pub offset: Vector2<f32>,
+    /// Counter-clockwise rotation in radians
+    pub rotation: f32,
+    pub scale: Vector2<f32>
+}
+
+impl TextureTransform {
+    pub fn is_identity(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub fn matrix(&self) -> Matrix3<f32> {
+        let (sin, cos) = self.rotation.sin_cos();
+
+        let translation = Matrix3::new(
+            1.0, 0.0, 0.0,
+            0.0, 1.0, 0.0,
+            self.offset.x, self.offset.y, 1.0
+        );
+        let rotation = Matrix3::new(
+            cos, -sin, 0.0,
+            sin, cos, 0.0,
+            0.0, 0.0, 1.0
+        );
+        let scale = Matrix3::new(
+            self.scale.x, 0.0, 0.0,
+            0.0, self.scale.y, 0.0,
+            0.0, 0.0, 1.0
+        );
+
+        translation * rotation * scale
+    }
+}
+
+impl Default for TextureTransform {
+    fn default() -> Self {
+        TextureTransform {
+            offset: Vector2::zero(),
+            rotation: 0.0,
+            scale: Vector2::new(1.0, 1.0)
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct MaterialTexture {
+    pub texture: Resource<Texture>,
+    pub sampler: TextureSampler,
+    pub tex_coord: u32,
+    pub transform: TextureTransform
+}
+
+impl MaterialTexture {
+    pub fn is_empty(&self) -> bool {
+        self.texture.is_empty()
+    }
+}
+
+impl Default for MaterialTexture {
+    fn default() -> Self {
+        MaterialTexture {
+            texture: Resource::empty(),
+            sampler: TextureSampler::default(),
+            tex_coord: 0,
+            transform: TextureTransform::default()
+        }
+    }
+}
 
 #[derive(Clone)]
 pub struct Material {
@@ -9,20 +117,89 @@ pub struct Material {
     pub index: Option<usize>,
 
     pub base_color_factor: Vector4<f32>,
-    pub base_color_texture: Resource<Texture>,
+    pub base_color_texture: MaterialTexture,
 
     pub normal_scale: f32,
-    pub normal_texture: Resource<Texture>,
+    pub normal_texture: MaterialTexture,
 
     pub metallic_factor: f32,
     pub roughness_factor: f32,
-    pub metallic_roughness_texture: Resource<Texture>,
+    pub metallic_roughness_texture: MaterialTexture,
 
     pub occlusion_strength: f32,
-    pub occlusion_texture: Resource<Texture>,
+    pub occlusion_texture: MaterialTexture,
 
     pub emissive_factor: Vector3<f32>,
-    pub emissive_texture: Resource<Texture>,
+    pub emissive_texture: MaterialTexture,
+    /// KHR_materials_emissive_strength
+    pub emissive_strength: f32,
+
+    /// KHR_materials_clearcoat
+    pub clearcoat_factor: f32,
+    pub clearcoat_texture: MaterialTexture,
+    pub clearcoat_roughness_factor: f32,
+    pub clearcoat_roughness_texture: MaterialTexture,
+    pub clearcoat_normal_scale: f32,
+    pub clearcoat_normal_texture: MaterialTexture,
+
+    /// KHR_materials_transmission
+    pub transmission_factor: f32,
+    pub transmission_texture: MaterialTexture,
+
+    /// KHR_materials_ior
+    pub ior: f32,
+
+    /// KHR_materials_sheen
+    pub sheen_color_factor: Vector3<f32>,
+    pub sheen_color_texture: MaterialTexture,
+    pub sheen_roughness_factor: f32,
+    pub sheen_roughness_texture: MaterialTexture,
+
+    /// KHR_materials_specular
+    pub specular_factor: f32,
+    pub specular_texture: MaterialTexture,
+    pub specular_color_factor: Vector3<f32>,
+    pub specular_color_texture: MaterialTexture
+}
+
+impl Material {
+    /// Every texture slot of the material, in a fixed order
+    pub fn textures(&self) -> Vec<&MaterialTexture> {
+        vec![
+            &self.base_color_texture,
+            &self.normal_texture,
+            &self.metallic_roughness_texture,
+            &self.occlusion_texture,
+            &self.emissive_texture,
+            &self.clearcoat_texture,
+            &self.clearcoat_roughness_texture,
+            &self.clearcoat_normal_texture,
+            &self.transmission_texture,
+            &self.sheen_color_texture,
+            &self.sheen_roughness_texture,
+            &self.specular_texture,
+            &self.specular_color_texture
+        ]
+    }
+
+    /// Every texture slot of the material, in the same order as `textures`
+    pub fn textures_mut(&mut self) -> Vec<&mut MaterialTexture> {
+        vec![
+            &mut self.base_color_texture,
+            &mut self.normal_texture,
+            &mut self.metallic_roughness_texture,
+            &mut self.occlusion_texture,
+            &mut self.emissive_texture,
+            &mut self.clearcoat_texture,
+            &mut self.clearcoat_roughness_texture,
+            &mut self.clearcoat_normal_texture,
+            &mut self.transmission_texture,
+            &mut self.sheen_color_texture,
+            &mut self.sheen_roughness_texture,
+            &mut self.specular_texture,
+            &mut self.specular_color_texture
+        ]
+    }
 }
 
 impl Default for Material {
@@ -31,22 +208,40 @@ impl Default for Material {
             name: String::from("default"),
             index: None,
             base_color_factor: Vector4::<f32>::new(1.0, 1.0, 1.0, 1.0),
-            base_color_texture: Resource::empty(),
+            base_color_texture: MaterialTexture::default(),
             normal_scale: 1.0,
-            normal_texture: Resource::empty(),
+            normal_texture: MaterialTexture::default(),
             metallic_factor: 0.0,
             roughness_factor: 1.0,
-            metallic_roughness_texture: Resource::empty(),
+            metallic_roughness_texture: MaterialTexture::default(),
             occlusion_strength: 1.0,
-            occlusion_texture: Resource::empty(),
+            occlusion_texture: MaterialTexture::default(),
             emissive_factor: Vector3::<f32>::new(0.0, 0.0, 0.0),
-            emissive_texture: Resource::empty(),
+            emissive_texture: MaterialTexture::default(),
+            emissive_strength: 1.0,
+            clearcoat_factor: 0.0,
+            clearcoat_texture: MaterialTexture::default(),
+            clearcoat_roughness_factor: 0.0,
+            clearcoat_roughness_texture: MaterialTexture::default(),
+            clearcoat_normal_scale: 1.0,
+            clearcoat_normal_texture: MaterialTexture::default(),
+            transmission_factor: 0.0,
+            transmission_texture: MaterialTexture::default(),
+            ior: 1.5,
+            sheen_color_factor: Vector3::<f32>::new(0.0, 0.0, 0.0),
+            sheen_color_texture: MaterialTexture::default(),
+            sheen_roughness_factor: 0.0,
+            sheen_roughness_texture: MaterialTexture::default(),
+            specular_factor: 1.0,
+            specular_texture: MaterialTexture::default(),
+            specular_color_factor: Vector3::<f32>::new(1.0, 1.0, 1.0),
+            specular_color_texture: MaterialTexture::default()
         }
     }
 }
 
 #[repr(C)]
-#[derive(Clone)]
+#[derive(Clone, PartialEq)]
 pub struct Vertex {
     pub position: Vector3::<f32>,
     pub normal: Vector3::<f32>,
@@ -69,19 +264,137 @@ impl Default for Vertex {
     }
 }
 
+/// Quantised `Vertex` for the compact vertex layout, 32 instead of 72 bytes.
+/// Normals and tangents are stored as 8 bit snorm, tex coords as half floats and colors as 8 bit unorm.
+/// Positions stay full floats. Half floats keep about three significant digits, so tex coords that tile far beyond [0, 1] lose precision.
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq)]
+pub struct CompactVertex {
+    pub position: Vector3::<f32>,
+    pub normal: [i8; 4],
+    pub tangent: [i8; 4],
+    pub tex_coord: [u16; 2],
+    pub tex_coord_1: [u16; 2],
+    pub color: [u8; 4]
+}
+
+impl From<&Vertex> for CompactVertex {
+    fn from(vertex: &Vertex) -> Self {
+        CompactVertex {
+            position: vertex.position,
+            normal: [snorm8(vertex.normal.x), snorm8(vertex.normal.y), snorm8(vertex.normal.z), 0],
+            tangent: [snorm8(vertex.tangent.x), snorm8(vertex.tangent.y), snorm8(vertex.tangent.z), snorm8(vertex.tangent.w)],
+            tex_coord: [half(vertex.tex_coord.x), half(vertex.tex_coord.y)],
+            tex_coord_1: [half(vertex.tex_coord_1.x), half(vertex.tex_coord_1.y)],
+            color: [unorm8(vertex.color.x), unorm8(vertex.color.y), unorm8(vertex.color.z), unorm8(vertex.color.w)]
+        }
+    }
+}
+
+fn snorm8(value: f32) -> i8 {
+    (value.clamp(-1.0, 1.0) * 127.0).round() as i8
+}
+
+fn unorm8(value: f32) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+/// Converts to an IEEE 754 half float, rounding half up
+fn half(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+
+    // Infinity and NaN
+    if exponent == 0xff {
+        return sign | 0x7c00 | if mantissa != 0 { 0x200 } else { 0 };
+    }
+
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+    if exponent <= 0 {
+        // Too small for a subnormal half
+        if exponent < -10 {
+            return sign;
+        }
+
+        let mantissa = mantissa | 0x80_0000;
+        let shift = (14 - exponent) as u32;
+        let rounding = ((mantissa >> (shift - 1)) & 1) as u16;
+        return sign | ((mantissa >> shift) as u16 + rounding);
+    }
+
+    // A rounding carry into the exponent is still the correctly rounded value
+    let rounding = ((mantissa >> 12) & 1) as u16;
+    (sign | ((exponent as u16) << 10) | (mantissa >> 13) as u16) + rounding
+}
+
+/// How mesh vertices are laid out on the gpu
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VertexLayout {
+    /// `Vertex` as is
+    Full,
+    /// `CompactVertex`, less than half the memory and bandwidth at a small loss of precision
+    Compact
+}
+
+impl VertexLayout {
+    pub fn get_stride(&self) -> u32 {
+        match self {
+            VertexLayout::Full => std::mem::size_of::<Vertex>() as u32,
+            VertexLayout::Compact => std::mem::size_of::<CompactVertex>() as u32
+        }
+    }
+}
+
+/// Primitive topology of a mesh, strips and fans are converted to triangle lists on import
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MeshKind {
+    Triangles,
+    Lines,
+    LineStrip,
+    Points
+}
+
+/// A simplified version of a mesh that shares its vertices
+#[derive(Clone)]
+pub struct MeshLod {
+    pub indices: Vec<u32>,
+    /// Largest geometric deviation from the full resolution mesh, relative to the radius of the mesh bounds
+    pub error: f32
+}
+
 #[derive(Clone)]
 pub struct Mesh {
     pub vertices: Vec<Vertex>,
     pub indices: Vec<u32>,
+    /// Levels of detail below the full resolution `indices`, from fine to coarse
+    pub lods: Vec<MeshLod>,
 
     pub min: Vector3::<f32>,
     pub max: Vector3::<f32>,
 
-    pub material_idx: usize
+    pub material_idx: usize,
+    pub kind: MeshKind
 }
 
 #[derive(Clone)]
 pub struct Model {
     pub meshes: Vec<Mesh>,
     pub materials: Vec<Resource<Material>>
+}
+
+impl ResourceSize for Model {
+    /// Only the mesh data, textures are accounted for by their own manager
+    fn size_in_bytes(&self) -> usize {
+        self.meshes.iter()
+            .map(|mesh| {
+                let lod_index_count: usize = mesh.lods.iter().map(|lod| lod.indices.capacity()).sum();
+                mesh.vertices.capacity() * std::mem::size_of::<Vertex>() + (mesh.indices.capacity() + lod_index_count) * std::mem::size_of::<u32>()
+            })
+            .sum()
+    }
 }
\ No newline at end of file
@@ -1,4 +1,5 @@
 pub use std::rc::Rc;
+use std::rc::Weak;
 pub use std::cell::{RefCell, Ref, RefMut};
 use std::hash::{Hash, Hasher};
 
@@ -32,6 +33,31 @@ impl<T> Eq for Resource<T> {
     }
 }
 
+/// Non owning handle to a resource, used by caches that must not keep a resource alive
+#[derive(Debug, Clone)]
+pub struct WeakResource<T> {
+    value: Weak<RefCell<T>>,
+    /// `Resource::as_ptr` of the resource it was downgraded from
+    ptr: *const T
+}
+
+impl<T> WeakResource<T> {
+    pub fn upgrade(&self) -> Option<Resource<T>> {
+        self.value.upgrade().map(|value| Resource {
+            value: Some(value)
+        })
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.value.strong_count() > 0
+    }
+
+    /// Same as `Resource::as_ptr`, stays unique while the weak handle exists, even after the resource itself is dropped
+    pub fn as_ptr(&self) -> *const T {
+        self.ptr
+    }
+}
+
 impl<T> Resource<T> {
     pub fn new(value: T) -> Self {
         Resource {
@@ -68,6 +94,13 @@ impl<T> Resource<T> {
         RefCell::as_ptr(self.value.as_ref().unwrap())
     }
 
+    pub fn downgrade(&self) -> WeakResource<T> {
+        WeakResource {
+            value: Rc::downgrade(self.value.as_ref().unwrap()),
+            ptr: self.as_ptr()
+        }
+    }
+
     pub fn try_as_ref(&self) -> Option<Ref<'_, T>> {
         match self.value.as_ref() {
             Some(value) => Some(value.as_ref().borrow()),
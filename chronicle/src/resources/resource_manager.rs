@@ -3,82 +3,160 @@ use std::collections::HashMap;
 use crate::resources::Resource;
 use crate::common::Timer;
 
-pub struct ResourceManager<T: Clone> {
-    resources: Vec<(Resource<T>, String)>,
-    asset_paths: HashMap<String, Resource<T>>,
-    kill_times: HashMap<*const T, f32>,
+/// Approximate cpu memory used by a resource, used for the memory budgets of the resource managers
+pub trait ResourceSize {
+    fn size_in_bytes(&self) -> usize;
+}
 
-    pub kill_time: f32,
-    timer: Timer
+struct CachedResource<T> {
+    resource: Resource<T>,
+    size: usize,
+    last_used: f32,
+    unused_since: Option<f32>,
+    pinned: bool
 }
 
-fn vec_remove_multiple<T>(vec: &mut Vec<T>, indices: &mut Vec<usize>) {
-    indices.sort();    
+pub struct ResourceManager<T: Clone + ResourceSize> {
+    resources: HashMap<String, CachedResource<T>>,
+    memory_usage: usize,
 
-    let mut j: usize = 0;
-    for i in indices.iter() {
-        vec.remove(i - j);
-        j += 1;
-    }
+    /// Without a budget unused resources are removed after `kill_time` seconds.
+    /// With a budget they stay cached until the budget is exceeded, the least recently used ones are removed first.
+    pub budget: Option<usize>,
+    pub kill_time: f32,
+    timer: Timer
 }
 
-impl<T: Clone> ResourceManager<T> {
-    pub fn new(kill_time: f32) -> Self {
+impl<T: Clone + ResourceSize> ResourceManager<T> {
+    pub fn new(kill_time: f32, budget: Option<usize>) -> Self {
         ResourceManager {
-            resources: Vec::new(),
-            asset_paths: HashMap::new(),
-            kill_times: HashMap::new(),
+            resources: HashMap::new(),
+            memory_usage: 0,
+            budget: budget,
             kill_time: kill_time,
             timer: Timer::new()
         }
     }
 
     pub fn update(&mut self) {
-        let mut resources_to_remove: Vec<(usize, String)> = Vec::new();
-
-        for i in 0..self.resources.len() {
-            let resource_ptr = self.resources[i].0.as_ptr();
-
-            let use_count = self.resources[i].0.strong_count();
-            if use_count <= 2 {
-                match self.kill_times.get(&resource_ptr) {
-                    Some(start_time) => {
-                        if start_time + self.kill_time < self.timer.elapsed() {
-                            resources_to_remove.push((i, self.resources[i].1.clone()));
-                            self.kill_times.remove(&resource_ptr);
-                        }
-                    },
-                    None => {
-                        self.kill_times.insert(resource_ptr, self.timer.elapsed());
-                    }
-                }
-            }
-            else {
-                self.kill_times.remove(&resource_ptr);
+        let now = self.timer.elapsed();
+
+        // Resources can be modified after loading, so sizes are refreshed every update
+        self.memory_usage = 0;
+        for managed in self.resources.values_mut() {
+            managed.size = managed.resource.as_ref().size_in_bytes();
+            self.memory_usage += managed.size;
+
+            // The manager holds one reference itself
+            if managed.resource.strong_count() > 1 {
+                managed.last_used = now;
+                managed.unused_since = None;
+            } else if managed.unused_since.is_none() {
+                managed.unused_since = Some(now);
             }
         }
 
-        if resources_to_remove.len() > 0 {
-            let mut indices: Vec<usize> = Vec::with_capacity(resources_to_remove.len());
-            let mut paths: Vec<String> = Vec::with_capacity(resources_to_remove.len());
-            for i in resources_to_remove {
-                indices.push(i.0);
-                paths.push(i.1);
+        match self.budget {
+            Some(budget) => self.evict_to_budget(budget),
+            None => {
+                let kill_time = self.kill_time;
+                let expired: Vec<String> = self.resources.iter()
+                    .filter(|(_, managed)| Self::is_evictable(managed))
+                    .filter(|(_, managed)| managed.unused_since.unwrap() + kill_time < now)
+                    .map(|(asset_path, _)| asset_path.clone())
+                    .collect();
+
+                for asset_path in expired {
+                    self.remove(&asset_path);
+                }
             }
+        }
+    }
+
+    fn is_evictable(managed: &CachedResource<T>) -> bool {
+        !managed.pinned && managed.unused_since.is_some() && managed.resource.strong_count() <= 1
+    }
+
+    fn evict_to_budget(&mut self, budget: usize) {
+        if self.memory_usage <= budget {
+            return;
+        }
 
-            vec_remove_multiple(&mut self.resources, &mut indices);
-            for path in paths {
-                self.asset_paths.remove(&path);
+        let mut candidates: Vec<(f32, String)> = self.resources.iter()
+            .filter(|(_, managed)| Self::is_evictable(managed))
+            .map(|(asset_path, managed)| (managed.last_used, asset_path.clone()))
+            .collect();
+        candidates.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        for (_, asset_path) in candidates {
+            if self.memory_usage <= budget {
+                break;
             }
+            self.remove(&asset_path);
+        }
+    }
+
+    fn remove(&mut self, asset_path: &String) {
+        if let Some(managed) = self.resources.remove(asset_path) {
+            self.memory_usage -= managed.size;
         }
     }
 
-    pub fn get(&self, asset_path: &String) -> Option<Resource<T>> {
-        self.asset_paths.get(asset_path).cloned()
+    pub fn get(&mut self, asset_path: &String) -> Option<Resource<T>> {
+        let now = self.timer.elapsed();
+        self.resources.get_mut(asset_path).map(|managed| {
+            managed.last_used = now;
+            managed.resource.clone()
+        })
     }
 
     pub fn insert(&mut self, resource: Resource<T>, asset_path: String) {
-        self.resources.push((resource.clone(), asset_path.clone()));
-        self.asset_paths.insert(asset_path, resource);
+        let size = resource.as_ref().size_in_bytes();
+        self.memory_usage += size;
+
+        let managed = CachedResource {
+            resource: resource,
+            size: size,
+            last_used: self.timer.elapsed(),
+            unused_since: None,
+            pinned: false
+        };
+        if let Some(previous) = self.resources.insert(asset_path, managed) {
+            self.memory_usage -= previous.size;
+        }
+
+        // Make room right away so loading many assets in a single frame stays within budget
+        if let Some(budget) = self.budget {
+            self.evict_to_budget(budget);
+        }
+    }
+
+    /// Pinned resources are never evicted, even when they are unused and over budget
+    pub fn set_pinned(&mut self, resource: &Resource<T>, pinned: bool) {
+        for managed in self.resources.values_mut() {
+            if managed.resource == *resource {
+                managed.pinned = pinned;
+            }
+        }
+    }
+
+    pub fn get_memory_usage(&self) -> usize {
+        self.memory_usage
+    }
+
+    pub fn get_resource_count(&self) -> usize {
+        self.resources.len()
+    }
+}
+
+impl ResourceSize for String {
+    fn size_in_bytes(&self) -> usize {
+        self.capacity()
+    }
+}
+
+impl ResourceSize for Vec<u8> {
+    fn size_in_bytes(&self) -> usize {
+        self.capacity()
     }
 }
\ No newline at end of file
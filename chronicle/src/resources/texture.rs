@@ -1,8 +1,642 @@
+extern crate basis_universal;
+extern crate ktx2;
+extern crate ddsfile;
+extern crate ruzstd;
+extern crate gltf;
+
+use std::io::Read;
+
+use crate::resources::Resource;
+use crate::resources::ResourceSize;
+
+use basis_universal::{Transcoder, LowLevelUastcTranscoder, SliceParametersUastc, TranscoderTextureFormat, TranscoderBlockFormat, TranscodeParameters, DecodeFlags};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TextureFormat {
+    Rgba8,
+    Bc1,
+    Bc2,
+    Bc3,
+    Bc4,
+    Bc5,
+    Bc6h,
+    Bc7,
+    Etc2Rgb8,
+    Etc2Rgba8,
+    Astc4x4,
+    /// A .basis file, must be transcoded before uploading.
+    Basis,
+    /// UASTC blocks from a KTX2 container, must be transcoded before uploading.
+    Uastc
+}
+
+impl TextureFormat {
+    pub fn is_compressed(&self) -> bool {
+        *self != TextureFormat::Rgba8
+    }
+
+    pub fn needs_transcoding(&self) -> bool {
+        match self {
+            TextureFormat::Basis | TextureFormat::Uastc => true,
+            _ => false
+        }
+    }
+
+    /// Size in bytes of a single 4x4 block, or a single pixel for uncompressed formats.
+    pub fn block_size(&self) -> usize {
+        match self {
+            TextureFormat::Rgba8 => 4,
+            TextureFormat::Bc1 | TextureFormat::Bc4 | TextureFormat::Etc2Rgb8 => 8,
+            _ => 16
+        }
+    }
+
+    pub fn level_size(&self, width: u32, height: u32) -> usize {
+        if self.is_compressed() {
+            let blocks_x = width.div_ceil(4).max(1) as usize;
+            let blocks_y = height.div_ceil(4).max(1) as usize;
+            blocks_x * blocks_y * self.block_size()
+        } else {
+            width as usize * height as usize * self.block_size()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ColorSpace {
+    Srgb,
+    Linear
+}
+
 #[derive(Clone)]
 pub struct Texture {
     pub data: Vec<u8>,
     pub width: u32,
     pub height: u32,
     pub channel_count: u32,
-    pub mip_levels: u32
+    pub mip_levels: u32,
+    pub format: TextureFormat,
+    pub color_space: ColorSpace,
+    /// Byte offsets of every precomputed mip level in `data`.
+    /// Empty if only the base level is stored and the mips have to be generated.
+    pub mip_offsets: Vec<usize>
+}
+
+impl ResourceSize for Texture {
+    fn size_in_bytes(&self) -> usize {
+        self.data.capacity()
+    }
+}
+
+impl Texture {
+    /// Packs separate roughness and metallic maps (obj/mtl) into the glTF layout, roughness in green and metallic in blue.
+    /// A missing map is treated as fully white so the material factor is used as is.
+    pub(super) fn pack_metallic_roughness(roughness: Option<Resource<Texture>>, metallic: Option<Resource<Texture>>) -> Texture {
+        let (width, height) = match (&roughness, &metallic) {
+            (Some(texture), _) | (None, Some(texture)) => (texture.as_ref().width, texture.as_ref().height),
+            (None, None) => (1, 1)
+        };
+
+        // Nearest sample of the red channel, the maps are allowed to differ in resolution
+        let sample = |texture: &Option<Resource<Texture>>, x: u32, y: u32| -> u8 {
+            match texture {
+                Some(texture) => {
+                    let texture = texture.as_ref();
+                    assert!(texture.format == TextureFormat::Rgba8, "Failed to pack metallic roughness texture. (Compressed maps are not supported)");
+
+                    let tx = x * texture.width / width;
+                    let ty = y * texture.height / height;
+                    texture.data[((ty * texture.width + tx) * 4) as usize]
+                },
+                None => 255
+            }
+        };
+
+        let mut data = Vec::with_capacity((width * height * 4) as usize);
+        for y in 0..height {
+            for x in 0..width {
+                data.extend_from_slice(&[0, sample(&roughness, x, y), sample(&metallic, x, y), 255]);
+            }
+        }
+
+        Texture {
+            data: data,
+            width: width,
+            height: height,
+            channel_count: 4,
+            mip_levels: ((width.max(height) as f32).log2().floor() as u32) + 1,
+            format: TextureFormat::Rgba8,
+            color_space: ColorSpace::Linear,
+            mip_offsets: Vec::new()
+        }
+    }
+
+    /// Converts an image decoded by the glTF importer (buffer views and data URIs) into an RGBA8 texture.
+    pub(super) fn from_gltf_image(image: &gltf::image::Data) -> Texture {
+        let (channel_count, channel_size) = match image.format {
+            gltf::image::Format::R8 => (1, 1),
+            gltf::image::Format::R8G8 => (2, 1),
+            gltf::image::Format::R8G8B8 => (3, 1),
+            gltf::image::Format::R8G8B8A8 => (4, 1),
+            gltf::image::Format::R16 => (1, 2),
+            gltf::image::Format::R16G16 => (2, 2),
+            gltf::image::Format::R16G16B16 => (3, 2),
+            gltf::image::Format::R16G16B16A16 => (4, 2),
+            gltf::image::Format::R32G32B32FLOAT => (3, 4),
+            gltf::image::Format::R32G32B32A32FLOAT => (4, 4)
+        };
+
+        let read_channel = |offset: usize| -> u8 {
+            match channel_size {
+                1 => image.pixels[offset],
+                2 => image.pixels[offset + 1], // Little endian, keep the most significant byte
+                _ => {
+                    let value = f32::from_le_bytes(image.pixels[offset..offset + 4].try_into().unwrap());
+                    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+                }
+            }
+        };
+
+        let pixel_count = (image.width * image.height) as usize;
+        let mut data = Vec::with_capacity(pixel_count * 4);
+        for i in 0..pixel_count {
+            let offset = i * channel_count * channel_size;
+            let channel = |c: usize| read_channel(offset + c * channel_size);
+
+            // Grayscale images are expanded to rgb, the second channel of a two channel image is alpha
+            let rgba = match channel_count {
+                1 => [channel(0), channel(0), channel(0), 255],
+                2 => [channel(0), channel(0), channel(0), channel(1)],
+                3 => [channel(0), channel(1), channel(2), 255],
+                _ => [channel(0), channel(1), channel(2), channel(3)]
+            };
+            data.extend_from_slice(&rgba);
+        }
+
+        Texture {
+            data: data,
+            width: image.width,
+            height: image.height,
+            channel_count: 4,
+            mip_levels: ((image.width.max(image.height) as f32).log2().floor() as u32) + 1,
+            format: TextureFormat::Rgba8,
+            color_space: ColorSpace::Linear,
+            mip_offsets: Vec::new()
+        }
+    }
+
+    pub(super) fn from_ktx2(bytes: &[u8], asset_path: &String) -> Texture {
+        let reader = ktx2::Reader::new(bytes).expect(&format!("Failed to read ktx2 file at \"{:?}\"", asset_path));
+        let header = reader.header();
+
+        assert!(header.pixel_depth <= 1 && header.layer_count <= 1 && header.face_count == 1,
+            "Failed to read ktx2 file at \"{:?}\". (Only 2D textures are supported)", asset_path);
+
+        let format = match header.format {
+            Some(ktx2::Format::R8G8B8A8_UNORM) | Some(ktx2::Format::R8G8B8A8_SRGB) => TextureFormat::Rgba8,
+            Some(ktx2::Format::BC1_RGB_UNORM_BLOCK) | Some(ktx2::Format::BC1_RGB_SRGB_BLOCK)
+                | Some(ktx2::Format::BC1_RGBA_UNORM_BLOCK) | Some(ktx2::Format::BC1_RGBA_SRGB_BLOCK) => TextureFormat::Bc1,
+            Some(ktx2::Format::BC2_UNORM_BLOCK) | Some(ktx2::Format::BC2_SRGB_BLOCK) => TextureFormat::Bc2,
+            Some(ktx2::Format::BC3_UNORM_BLOCK) | Some(ktx2::Format::BC3_SRGB_BLOCK) => TextureFormat::Bc3,
+            Some(ktx2::Format::BC4_UNORM_BLOCK) => TextureFormat::Bc4,
+            Some(ktx2::Format::BC5_UNORM_BLOCK) => TextureFormat::Bc5,
+            Some(ktx2::Format::BC6H_UFLOAT_BLOCK) => TextureFormat::Bc6h,
+            Some(ktx2::Format::BC7_UNORM_BLOCK) | Some(ktx2::Format::BC7_SRGB_BLOCK) => TextureFormat::Bc7,
+            Some(ktx2::Format::ETC2_R8G8B8_UNORM_BLOCK) | Some(ktx2::Format::ETC2_R8G8B8_SRGB_BLOCK) => TextureFormat::Etc2Rgb8,
+            Some(ktx2::Format::ETC2_R8G8B8A8_UNORM_BLOCK) | Some(ktx2::Format::ETC2_R8G8B8A8_SRGB_BLOCK) => TextureFormat::Etc2Rgba8,
+            Some(ktx2::Format::ASTC_4x4_UNORM_BLOCK) | Some(ktx2::Format::ASTC_4x4_SRGB_BLOCK) => TextureFormat::Astc4x4,
+            None if reader.color_model() == Some(ktx2::ColorModel::UASTC) => TextureFormat::Uastc,
+            None if header.supercompression_scheme == Some(ktx2::SupercompressionScheme::BasisLZ) =>
+                return Self::from_basis(basis_lz_to_basis(&reader, asset_path), asset_path),
+            format => panic!("Failed to read ktx2 file at \"{:?}\". (Unsupported format {:?})", asset_path, format)
+        };
+
+        let mut data = Vec::new();
+        let mut mip_offsets = Vec::new();
+        for level in reader.levels() {
+            mip_offsets.push(data.len());
+
+            match header.supercompression_scheme {
+                None => data.extend_from_slice(level.data),
+                Some(ktx2::SupercompressionScheme::Zstandard) => {
+                    let mut decoder = ruzstd::StreamingDecoder::new(level.data)
+                        .expect(&format!("Failed to read ktx2 file at \"{:?}\". (Invalid zstd data)", asset_path));
+                    decoder.read_to_end(&mut data)
+                        .expect(&format!("Failed to read ktx2 file at \"{:?}\". (Invalid zstd data)", asset_path));
+                },
+                Some(scheme) => panic!("Failed to read ktx2 file at \"{:?}\". (Unsupported supercompression {:?})", asset_path, scheme)
+            }
+        }
+
+        // UASTC only carries alpha for the RGBA (3) and RRRG (5) channel layouts
+        let channel_count = match reader.basic_dfd() {
+            Some(dfd) if format == TextureFormat::Uastc => match dfd.sample_information.first().map(|sample| sample.channel_type) {
+                Some(3) | Some(5) => 4,
+                _ => 3
+            },
+            _ => 4
+        };
+
+        let (width, height) = (header.pixel_width, header.pixel_height.max(1));
+        let mip_levels = Self::stored_mip_levels(format, width, height, &mut mip_offsets);
+
+        Texture {
+            data: data,
+            width: width,
+            height: height,
+            channel_count: channel_count,
+            mip_levels: mip_levels,
+            format: format,
+            color_space: ColorSpace::Linear,
+            mip_offsets: mip_offsets
+        }
+    }
+
+    pub(super) fn from_dds(bytes: &[u8], asset_path: &String) -> Texture {
+        let dds = ddsfile::Dds::read(bytes).expect(&format!("Failed to read dds file at \"{:?}\"", asset_path));
+
+        let format = if let Some(format) = dds.get_dxgi_format() {
+            match format {
+                ddsfile::DxgiFormat::R8G8B8A8_UNorm | ddsfile::DxgiFormat::R8G8B8A8_UNorm_sRGB => TextureFormat::Rgba8,
+                ddsfile::DxgiFormat::BC1_UNorm | ddsfile::DxgiFormat::BC1_UNorm_sRGB => TextureFormat::Bc1,
+                ddsfile::DxgiFormat::BC2_UNorm | ddsfile::DxgiFormat::BC2_UNorm_sRGB => TextureFormat::Bc2,
+                ddsfile::DxgiFormat::BC3_UNorm | ddsfile::DxgiFormat::BC3_UNorm_sRGB => TextureFormat::Bc3,
+                ddsfile::DxgiFormat::BC4_UNorm => TextureFormat::Bc4,
+                ddsfile::DxgiFormat::BC5_UNorm => TextureFormat::Bc5,
+                ddsfile::DxgiFormat::BC6H_UF16 => TextureFormat::Bc6h,
+                ddsfile::DxgiFormat::BC7_UNorm | ddsfile::DxgiFormat::BC7_UNorm_sRGB => TextureFormat::Bc7,
+                format => panic!("Failed to read dds file at \"{:?}\". (Unsupported format {:?})", asset_path, format)
+            }
+        } else {
+            match dds.get_d3d_format() {
+                Some(ddsfile::D3DFormat::DXT1) => TextureFormat::Bc1,
+                Some(ddsfile::D3DFormat::DXT3) => TextureFormat::Bc2,
+                Some(ddsfile::D3DFormat::DXT5) => TextureFormat::Bc3,
+                Some(ddsfile::D3DFormat::A8B8G8R8) => TextureFormat::Rgba8,
+                format => panic!("Failed to read dds file at \"{:?}\". (Unsupported format {:?})", asset_path, format)
+            }
+        };
+
+        let (width, height) = (dds.get_width(), dds.get_height());
+        let level_count = dds.get_num_mipmap_levels().max(1);
+
+        let mut mip_offsets = Vec::with_capacity(level_count as usize);
+        let mut offset = 0;
+        for level in 0..level_count {
+            mip_offsets.push(offset);
+            offset += format.level_size((width >> level).max(1), (height >> level).max(1));
+        }
+
+        let data = dds.get_data(0).expect(&format!("Failed to read dds file at \"{:?}\"", asset_path));
+        assert!(data.len() >= offset, "Failed to read dds file at \"{:?}\". (Missing mip data)", asset_path);
+        let mip_levels = Self::stored_mip_levels(format, width, height, &mut mip_offsets);
+
+        Texture {
+            data: data[..offset].to_vec(),
+            width: width,
+            height: height,
+            channel_count: 4,
+            mip_levels: mip_levels,
+            format: format,
+            color_space: ColorSpace::Linear,
+            mip_offsets: mip_offsets
+        }
+    }
+
+    pub(super) fn from_basis(bytes: Vec<u8>, asset_path: &String) -> Texture {
+        let transcoder = Transcoder::new();
+        let description = transcoder.image_level_description(&bytes, 0, 0)
+            .expect(&format!("Failed to read basis file at \"{:?}\"", asset_path));
+        let mip_levels = transcoder.image_level_count(&bytes, 0);
+
+        Texture {
+            data: bytes,
+            width: description.original_width,
+            height: description.original_height,
+            channel_count: 4,
+            mip_levels: mip_levels,
+            format: TextureFormat::Basis,
+            color_space: ColorSpace::Linear,
+            mip_offsets: Vec::new()
+        }
+    }
+
+    /// Computes the full mip chain on the cpu with a box filter, sRGB textures are filtered in linear space.
+    pub fn generate_mips(&self) -> Texture {
+        assert!(self.format == TextureFormat::Rgba8, "Failed to generate mips. (Only Rgba8 textures are supported)");
+        if self.has_precomputed_mips() {
+            return self.clone();
+        }
+
+        let to_linear: Vec<f32> = (0..256).map(|i| {
+            let c = i as f32 / 255.0;
+            match self.color_space {
+                ColorSpace::Srgb if c <= 0.04045 => c / 12.92,
+                ColorSpace::Srgb => ((c + 0.055) / 1.055).powf(2.4),
+                ColorSpace::Linear => c
+            }
+        }).collect();
+        let from_linear = |c: f32| -> u8 {
+            let c = match self.color_space {
+                ColorSpace::Srgb if c <= 0.0031308 => c * 12.92,
+                ColorSpace::Srgb => 1.055 * c.powf(1.0 / 2.4) - 0.055,
+                ColorSpace::Linear => c
+            };
+            (c.clamp(0.0, 1.0) * 255.0).round() as u8
+        };
+
+        let base_size = self.format.level_size(self.width, self.height);
+        let mut data = self.data[..base_size].to_vec();
+        let mut mip_offsets = vec![0];
+
+        for level in 1..self.mip_levels {
+            let (src_width, src_height) = self.level_extent(level - 1);
+            let (width, height) = self.level_extent(level);
+            let src_offset = mip_offsets[level as usize - 1];
+
+            let mut level_data = Vec::with_capacity(self.format.level_size(width, height));
+            for y in 0..height {
+                for x in 0..width {
+                    let mut sum = [0.0f32; 4];
+                    for (sx, sy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                        let px = (x * 2 + sx).min(src_width - 1) as usize;
+                        let py = (y * 2 + sy).min(src_height - 1) as usize;
+                        let pixel = src_offset + (py * src_width as usize + px) * 4;
+                        for c in 0..3 {
+                            sum[c] += to_linear[data[pixel + c] as usize];
+                        }
+                        // Alpha is always stored linearly
+                        sum[3] += data[pixel + 3] as f32 / 255.0;
+                    }
+
+                    for c in 0..3 {
+                        level_data.push(from_linear(sum[c] * 0.25));
+                    }
+                    level_data.push((sum[3] * 0.25 * 255.0).round() as u8);
+                }
+            }
+
+            mip_offsets.push(data.len());
+            data.extend_from_slice(&level_data);
+        }
+
+        Texture {
+            data: data,
+            width: self.width,
+            height: self.height,
+            channel_count: self.channel_count,
+            mip_levels: self.mip_levels,
+            format: self.format,
+            color_space: self.color_space,
+            mip_offsets: mip_offsets
+        }
+    }
+
+    /// Mip count of a texture loaded with the levels at `mip_offsets`.
+    /// A lone uncompressed base level is not a precomputed chain, its offsets are cleared so the mips get generated
+    fn stored_mip_levels(format: TextureFormat, width: u32, height: u32, mip_offsets: &mut Vec<usize>) -> u32 {
+        match mip_offsets.len() <= 1 && !format.is_compressed() {
+            true => {
+                mip_offsets.clear();
+                ((width.max(height) as f32).log2().floor() as u32) + 1
+            },
+            false => mip_offsets.len() as u32
+        }
+    }
+
+    pub fn has_precomputed_mips(&self) -> bool {
+        !self.mip_offsets.is_empty()
+    }
+
+    pub fn level_extent(&self, level: u32) -> (u32, u32) {
+        ((self.width >> level).max(1), (self.height >> level).max(1))
+    }
+
+    /// Transcodes a Basis Universal texture into `target`, keeping all of its mip levels.
+    pub fn transcode(&self, target: TextureFormat) -> Texture {
+        assert!(self.format.needs_transcoding(), "Failed to transcode texture. (Not a basis texture)");
+
+        let mut data = Vec::new();
+        let mut mip_offsets = Vec::new();
+
+        match self.format {
+            TextureFormat::Basis => {
+                let target_format = match target {
+                    TextureFormat::Rgba8 => TranscoderTextureFormat::RGBA32,
+                    TextureFormat::Bc1 => TranscoderTextureFormat::BC1_RGB,
+                    TextureFormat::Bc3 => TranscoderTextureFormat::BC3_RGBA,
+                    TextureFormat::Bc7 => TranscoderTextureFormat::BC7_RGBA,
+                    TextureFormat::Etc2Rgba8 => TranscoderTextureFormat::ETC2_RGBA,
+                    TextureFormat::Astc4x4 => TranscoderTextureFormat::ASTC_4x4_RGBA,
+                    _ => panic!("Failed to transcode texture. (Unsupported target {:?})", target)
+                };
+
+                let mut transcoder = Transcoder::new();
+                transcoder.prepare_transcoding(&self.data).expect("Failed to transcode texture. (Invalid basis data)");
+
+                for level in 0..transcoder.image_level_count(&self.data, 0) {
+                    let level_data = transcoder.transcode_image_level(&self.data, target_format, TranscodeParameters {
+                        image_index: 0,
+                        level_index: level,
+                        ..Default::default()
+                    }).expect("Failed to transcode texture.");
+
+                    mip_offsets.push(data.len());
+                    data.extend_from_slice(&level_data);
+                }
+
+                transcoder.end_transcoding();
+            },
+            TextureFormat::Uastc => {
+                let target_format = match target {
+                    TextureFormat::Rgba8 => TranscoderBlockFormat::RGBA32,
+                    TextureFormat::Bc1 => TranscoderBlockFormat::BC1,
+                    TextureFormat::Bc3 => TranscoderBlockFormat::BC3,
+                    TextureFormat::Bc7 => TranscoderBlockFormat::BC7,
+                    TextureFormat::Etc2Rgba8 => TranscoderBlockFormat::ETC2_RGBA,
+                    TextureFormat::Astc4x4 => TranscoderBlockFormat::ASTC_4x4,
+                    _ => panic!("Failed to transcode texture. (Unsupported target {:?})", target)
+                };
+
+                let transcoder = LowLevelUastcTranscoder::new();
+                for level in 0..self.mip_levels {
+                    let (width, height) = self.level_extent(level);
+                    let start = self.mip_offsets[level as usize];
+                    let end = self.mip_offsets.get(level as usize + 1).cloned().unwrap_or(self.data.len());
+
+                    let level_data = transcoder.transcode_slice(
+                        &self.data[start..end],
+                        SliceParametersUastc {
+                            num_blocks_x: width.div_ceil(4),
+                            num_blocks_y: height.div_ceil(4),
+                            has_alpha: self.channel_count == 4,
+                            original_width: width,
+                            original_height: height
+                        },
+                        DecodeFlags::HIGH_QUALITY,
+                        target_format
+                    ).expect("Failed to transcode texture.");
+
+                    mip_offsets.push(data.len());
+                    data.extend_from_slice(&level_data);
+                }
+            },
+            _ => unreachable!()
+        }
+
+        Texture {
+            data: data,
+            width: self.width,
+            height: self.height,
+            channel_count: 4,
+            mip_levels: mip_offsets.len() as u32,
+            format: target,
+            color_space: self.color_space,
+            mip_offsets: mip_offsets
+        }
+    }
+}
+
+
+/// Repackages the ETC1S slices of a KTX2 file with BasisLZ supercompression as a .basis file for the Basis transcoder.
+/// Both containers store the same codebooks, huffman tables and slices, only the headers differ
+fn basis_lz_to_basis(reader: &ktx2::Reader<&[u8]>, asset_path: &String) -> Vec<u8> {
+    const HEADER_SIZE: usize = 77;
+    const SLICE_DESC_SIZE: usize = 23;
+    const IMAGE_DESC_SIZE: usize = 20;
+
+    let header = reader.header();
+    let global_data = reader.supercompression_global_data();
+    let levels: Vec<ktx2::Level> = reader.levels().collect();
+
+    let read_u32 = |offset: usize| -> usize {
+        let bytes = global_data.get(offset..offset + 4)
+            .expect(&format!("Failed to read ktx2 file at \"{:?}\". (Truncated BasisLZ global data)", asset_path));
+        u32::from_le_bytes(bytes.try_into().unwrap()) as usize
+    };
+    let endpoint_count = read_u32(0) & 0xFFFF;
+    let selector_count = read_u32(0) >> 16;
+    let codebook_sizes = [read_u32(4), read_u32(8), read_u32(12), read_u32(16)];
+    let codebooks_offset = 20 + levels.len() * IMAGE_DESC_SIZE;
+    let codebooks = global_data.get(codebooks_offset..codebooks_offset + codebook_sizes.iter().sum::<usize>())
+        .expect(&format!("Failed to read ktx2 file at \"{:?}\". (Truncated BasisLZ global data)", asset_path));
+
+    // ETC1S with alpha has a second sample for the alpha slices
+    let has_alpha = reader.basic_dfd().map(|dfd| dfd.sample_information.len() == 2).unwrap_or(false);
+    let slices_per_level = if has_alpha { 2 } else { 1 };
+
+    let slice_descs_offset = HEADER_SIZE;
+    let codebooks_file_offset = slice_descs_offset + levels.len() * slices_per_level * SLICE_DESC_SIZE;
+    let mut slices_file_offset = codebooks_file_offset + codebooks.len();
+
+    // Little endian integers of arbitrary byte width, like basisu's packed_uint
+    let put = |bytes: &mut Vec<u8>, value: usize, size: usize| bytes.extend_from_slice(&(value as u64).to_le_bytes()[..size]);
+
+    let mut slice_descs = Vec::new();
+    let mut slices = Vec::new();
+    for (level_index, level) in levels.iter().enumerate() {
+        let image_desc = 20 + level_index * IMAGE_DESC_SIZE;
+        let (width, height) = ((header.pixel_width >> level_index).max(1), (header.pixel_height >> level_index).max(1));
+
+        for slice in 0..slices_per_level {
+            let offset = read_u32(image_desc + 4 + slice * 8);
+            let size = read_u32(image_desc + 8 + slice * 8);
+            let data = level.data.get(offset..offset + size)
+                .expect(&format!("Failed to read ktx2 file at \"{:?}\". (Slice out of bounds)", asset_path));
+
+            put(&mut slice_descs, 0, 3); // Image index
+            put(&mut slice_descs, level_index, 1);
+            put(&mut slice_descs, slice, 1); // Alpha slices are flagged with 1
+            put(&mut slice_descs, width as usize, 2);
+            put(&mut slice_descs, height as usize, 2);
+            put(&mut slice_descs, width.div_ceil(4) as usize, 2);
+            put(&mut slice_descs, height.div_ceil(4) as usize, 2);
+            put(&mut slice_descs, slices_file_offset, 4);
+            put(&mut slice_descs, size, 4);
+            put(&mut slice_descs, 0, 2); // Checksum
+
+            slices_file_offset += size;
+            slices.extend_from_slice(data);
+        }
+    }
+
+    let file_size = slices_file_offset;
+    let [endpoints_size, selectors_size, tables_size, extended_size] = codebook_sizes;
+
+    // The checksums are left at 0, the transcoder only verifies them when asked to
+    let mut bytes = Vec::with_capacity(file_size);
+    put(&mut bytes, 0x4273, 2); // "sB"
+    put(&mut bytes, 0x13, 2); // Version
+    put(&mut bytes, HEADER_SIZE, 2);
+    put(&mut bytes, 0, 2); // Header checksum
+    put(&mut bytes, file_size - HEADER_SIZE, 4);
+    put(&mut bytes, 0, 2); // Data checksum
+    put(&mut bytes, levels.len() * slices_per_level, 3);
+    put(&mut bytes, 1, 3); // Image count
+    put(&mut bytes, 0, 1); // ETC1S
+    put(&mut bytes, if has_alpha { 1 | 4 } else { 1 }, 2); // ETC1S and has alpha slices flags
+    put(&mut bytes, 0, 1); // 2D texture
+    put(&mut bytes, 0, 3); // Microseconds per frame
+    put(&mut bytes, 0, 4); // Reserved
+    put(&mut bytes, 0, 8); // User data
+    put(&mut bytes, endpoint_count, 2);
+    put(&mut bytes, codebooks_file_offset, 4);
+    put(&mut bytes, endpoints_size, 3);
+    put(&mut bytes, selector_count, 2);
+    put(&mut bytes, codebooks_file_offset + endpoints_size, 4);
+    put(&mut bytes, selectors_size, 3);
+    put(&mut bytes, codebooks_file_offset + endpoints_size + selectors_size, 4);
+    put(&mut bytes, tables_size, 4);
+    put(&mut bytes, slice_descs_offset, 4);
+    put(&mut bytes, codebooks_file_offset + endpoints_size + selectors_size + tables_size, 4);
+    put(&mut bytes, extended_size, 4);
+    debug_assert_eq!(bytes.len(), HEADER_SIZE);
+
+    bytes.extend_from_slice(&slice_descs);
+    bytes.extend_from_slice(codebooks);
+    bytes.extend_from_slice(&slices);
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// BasisLZ KTX2 files and .basis files encoded by basisu from the same image with the same settings
+    fn assert_same_transcode(ktx2_bytes: &[u8], basis_bytes: &[u8]) {
+        let asset_path = "test.ktx2".to_owned();
+        let from_ktx2 = Texture::from_ktx2(ktx2_bytes, &asset_path);
+        let from_basis = Texture::from_basis(basis_bytes.to_vec(), &asset_path);
+        assert!(from_ktx2.format == TextureFormat::Basis);
+        assert_eq!((from_ktx2.width, from_ktx2.height, from_ktx2.mip_levels), (from_basis.width, from_basis.height, from_basis.mip_levels));
+
+        for target in [TextureFormat::Rgba8, TextureFormat::Bc7] {
+            let transcoded = from_ktx2.transcode(target);
+            let expected = from_basis.transcode(target);
+            assert_eq!(transcoded.mip_offsets, expected.mip_offsets);
+            assert!(transcoded.data == expected.data);
+        }
+    }
+
+    #[test]
+    fn basis_lz_matches_basis() {
+        assert_same_transcode(include_bytes!("../../tests/data/basis_lz_rgb.ktx2"), include_bytes!("../../tests/data/basis_lz_rgb.basis"));
+    }
+
+    #[test]
+    fn basis_lz_with_alpha_matches_basis() {
+        assert_same_transcode(include_bytes!("../../tests/data/basis_lz_rgba.ktx2"), include_bytes!("../../tests/data/basis_lz_rgba.basis"));
+    }
+
+    #[test]
+    fn single_level_rgba8_generates_mips() {
+        let mut mip_offsets = vec![0];
+        assert_eq!(Texture::stored_mip_levels(TextureFormat::Rgba8, 16, 4, &mut mip_offsets), 5);
+        assert!(mip_offsets.is_empty());
+
+        // Block compressed levels can't be generated, a lone level stays as it is
+        let mut mip_offsets = vec![0];
+        assert_eq!(Texture::stored_mip_levels(TextureFormat::Bc7, 16, 4, &mut mip_offsets), 1);
+        assert_eq!(mip_offsets, vec![0]);
+    }
 }
\ No newline at end of file
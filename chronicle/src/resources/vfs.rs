@@ -0,0 +1,309 @@
+extern crate zip;
+extern crate tar;
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// A source of files that can be mounted in the `Vfs`.
+/// Paths are always relative to the mount point, use forward slashes and contain no `.` or `..` segments.
+pub trait VfsBackend {
+    fn read(&self, path: &str) -> Option<Vec<u8>>;
+    fn exists(&self, path: &str) -> bool;
+    fn modified(&self, path: &str) -> Option<SystemTime>;
+
+    /// The location of the file on disk, only backends that can be written to return one
+    fn disk_path(&self, _path: &str) -> Option<PathBuf> {
+        None
+    }
+}
+
+pub struct DirectoryBackend {
+    root: PathBuf
+}
+
+impl DirectoryBackend {
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        DirectoryBackend {
+            root: root.as_ref().to_path_buf()
+        }
+    }
+}
+
+impl VfsBackend for DirectoryBackend {
+    fn read(&self, path: &str) -> Option<Vec<u8>> {
+        std::fs::read(self.root.join(path)).ok()
+    }
+
+    fn exists(&self, path: &str) -> bool {
+        self.root.join(path).is_file()
+    }
+
+    fn modified(&self, path: &str) -> Option<SystemTime> {
+        std::fs::metadata(self.root.join(path)).and_then(|metadata| metadata.modified()).ok()
+    }
+
+    fn disk_path(&self, path: &str) -> Option<PathBuf> {
+        Some(self.root.join(path))
+    }
+}
+
+/// Archives are read only and report the modification time of the archive itself for every file
+pub struct ZipBackend {
+    archive: RefCell<zip::ZipArchive<File>>,
+    modified: Option<SystemTime>
+}
+
+impl ZipBackend {
+    pub fn new<P: AsRef<Path>>(archive_path: P) -> Self {
+        let archive_path = archive_path.as_ref();
+        let file = File::open(archive_path).expect(&format!("Failed to mount archive at \"{:?}\"", archive_path));
+        let modified = file.metadata().and_then(|metadata| metadata.modified()).ok();
+        let archive = zip::ZipArchive::new(file).expect(&format!("Failed to mount archive at \"{:?}\" (Invalid zip file)", archive_path));
+
+        ZipBackend {
+            archive: RefCell::new(archive),
+            modified: modified
+        }
+    }
+}
+
+impl VfsBackend for ZipBackend {
+    fn read(&self, path: &str) -> Option<Vec<u8>> {
+        let mut archive = self.archive.borrow_mut();
+        let mut file = archive.by_name(path).ok()?;
+
+        let mut bytes = Vec::with_capacity(file.size() as usize);
+        file.read_to_end(&mut bytes).ok()?;
+        Some(bytes)
+    }
+
+    fn exists(&self, path: &str) -> bool {
+        self.archive.borrow_mut().by_name(path).is_ok()
+    }
+
+    fn modified(&self, path: &str) -> Option<SystemTime> {
+        match self.exists(path) {
+            true => self.modified,
+            false => None
+        }
+    }
+}
+
+/// Tar files are uncompressed, so only the offset of every entry is kept and files are read on demand
+pub struct TarBackend {
+    archive_path: PathBuf,
+    entries: HashMap<String, (u64, u64)>,
+    modified: Option<SystemTime>
+}
+
+impl TarBackend {
+    pub fn new<P: AsRef<Path>>(archive_path: P) -> Self {
+        let archive_path = archive_path.as_ref();
+        let file = File::open(archive_path).expect(&format!("Failed to mount archive at \"{:?}\"", archive_path));
+        let modified = file.metadata().and_then(|metadata| metadata.modified()).ok();
+
+        let mut entries = HashMap::new();
+        let mut archive = tar::Archive::new(file);
+        for entry in archive.entries().expect(&format!("Failed to mount archive at \"{:?}\" (Invalid tar file)", archive_path)) {
+            let entry = entry.expect(&format!("Failed to mount archive at \"{:?}\" (Invalid tar entry)", archive_path));
+            if entry.header().entry_type() != tar::EntryType::Regular {
+                continue;
+            }
+
+            if let Ok(path) = entry.path() {
+                if let Some(path) = Vfs::normalize(&path.to_string_lossy()) {
+                    entries.insert(path, (entry.raw_file_position(), entry.size()));
+                }
+            }
+        }
+
+        TarBackend {
+            archive_path: archive_path.to_path_buf(),
+            entries: entries,
+            modified: modified
+        }
+    }
+}
+
+impl VfsBackend for TarBackend {
+    fn read(&self, path: &str) -> Option<Vec<u8>> {
+        let (offset, size) = *self.entries.get(path)?;
+
+        let mut file = File::open(&self.archive_path).ok()?;
+        file.seek(SeekFrom::Start(offset)).ok()?;
+
+        let mut bytes = vec![0; size as usize];
+        file.read_exact(&mut bytes).ok()?;
+        Some(bytes)
+    }
+
+    fn exists(&self, path: &str) -> bool {
+        self.entries.contains_key(path)
+    }
+
+    fn modified(&self, path: &str) -> Option<SystemTime> {
+        match self.exists(path) {
+            true => self.modified,
+            false => None
+        }
+    }
+}
+
+struct Mount {
+    scheme: String,
+    backend: Box<dyn VfsBackend>
+}
+
+/// Virtual file system that resolves paths like `game://models/helmet.gltf` through mounted backends.
+/// Multiple backends can be mounted on the same scheme, the most recently mounted one is searched first so packs can override loose files.
+/// Paths without a scheme are read from disk as is.
+pub struct Vfs {
+    mounts: Vec<Mount>
+}
+
+impl Default for Vfs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Vfs {
+    pub fn new() -> Self {
+        Vfs {
+            mounts: Vec::new()
+        }
+    }
+
+    /// Mounts the default `builtin://` and `game://` schemes on the assets folder.
+    /// `<scheme>.zip` and `<scheme>.tar` packs inside the assets folder are mounted on top of the loose files.
+    pub(crate) fn with_default_mounts() -> Self {
+        let mut vfs = Vfs::new();
+
+        if let Some(assets) = Self::find_assets_root() {
+            vfs.mount_directory("builtin", assets.join("builtin"));
+            vfs.mount_directory("game", &assets);
+
+            for scheme in ["builtin", "game"] {
+                for extension in ["zip", "tar"] {
+                    let pack = assets.join(format!("{}.{}", scheme, extension));
+                    if pack.is_file() {
+                        vfs.mount_archive(scheme, pack);
+                    }
+                }
+            }
+        }
+
+        vfs
+    }
+
+    /// Looks for an assets folder in the working directory, then next to the executable and its parent folders
+    fn find_assets_root() -> Option<PathBuf> {
+        let working_dir = std::env::current_dir().ok().map(|dir| dir.join("assets"));
+        let exe_dirs = std::env::current_exe().ok()
+            .map(|exe| exe.ancestors().skip(1).map(|dir| dir.join("assets")).collect::<Vec<_>>())
+            .unwrap_or_default();
+
+        working_dir.into_iter().chain(exe_dirs).find(|dir| dir.is_dir())
+    }
+
+    pub fn mount(&mut self, scheme: &str, backend: Box<dyn VfsBackend>) {
+        self.mounts.push(Mount {
+            scheme: scheme.to_owned(),
+            backend: backend
+        });
+    }
+
+    pub fn mount_directory<P: AsRef<Path>>(&mut self, scheme: &str, path: P) {
+        self.mount(scheme, Box::new(DirectoryBackend::new(path)));
+    }
+
+    /// Mounts a .zip or .tar archive, picked by its extension
+    pub fn mount_archive<P: AsRef<Path>>(&mut self, scheme: &str, path: P) {
+        let path = path.as_ref();
+        let extension = path.extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_lowercase());
+
+        match extension.as_deref() {
+            Some("zip") => self.mount(scheme, Box::new(ZipBackend::new(path))),
+            Some("tar") => self.mount(scheme, Box::new(TarBackend::new(path))),
+            _ => panic!("Failed to mount archive at \"{:?}\" (Unsupported archive type)", path)
+        }
+    }
+
+    /// Removes every backend mounted on `scheme`
+    pub fn unmount(&mut self, scheme: &str) {
+        self.mounts.retain(|mount| mount.scheme != scheme);
+    }
+
+    pub fn read(&self, path: &str) -> Option<Vec<u8>> {
+        match Self::split(path) {
+            Some((scheme, path)) => self.backends(scheme).find_map(|backend| backend.read(&path)),
+            None => std::fs::read(path).ok()
+        }
+    }
+
+    pub fn read_to_string(&self, path: &str) -> Option<String> {
+        self.read(path).and_then(|bytes| String::from_utf8(bytes).ok())
+    }
+
+    pub fn exists(&self, path: &str) -> bool {
+        match Self::split(path) {
+            Some((scheme, path)) => self.backends(scheme).any(|backend| backend.exists(&path)),
+            None => Path::new(path).is_file()
+        }
+    }
+
+    pub fn modified(&self, path: &str) -> Option<SystemTime> {
+        match Self::split(path) {
+            Some((scheme, path)) => self.backends(scheme).find_map(|backend| backend.modified(&path)),
+            None => std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+        }
+    }
+
+    /// Where a file lives or would be written on disk.
+    /// Resolves to the first mount that already has the file, or else the most recent writable mount.
+    pub fn disk_path(&self, path: &str) -> Option<PathBuf> {
+        match Self::split(path) {
+            Some((scheme, path)) => {
+                self.backends(scheme)
+                    .find(|backend| backend.exists(&path))
+                    .and_then(|backend| backend.disk_path(&path))
+                    .or_else(|| self.backends(scheme).find_map(|backend| backend.disk_path(&path)))
+            },
+            None => Some(PathBuf::from(path))
+        }
+    }
+
+    fn backends<'a>(&'a self, scheme: &'a str) -> impl Iterator<Item = &'a Box<dyn VfsBackend>> + 'a {
+        self.mounts.iter()
+            .rev()
+            .filter(move |mount| mount.scheme == scheme)
+            .map(|mount| &mount.backend)
+    }
+
+    /// Splits `scheme://path` into its scheme and normalized path
+    fn split(path: &str) -> Option<(&str, String)> {
+        let (scheme, path) = path.split_once("://")?;
+        Some((scheme, Self::normalize(path)?))
+    }
+
+    /// Resolves `.` and `..` segments and backslashes, paths that escape the mount point are rejected
+    fn normalize(path: &str) -> Option<String> {
+        let mut segments: Vec<&str> = Vec::new();
+        for segment in path.split(|c| c == '/' || c == '\\') {
+            match segment {
+                "" | "." => {},
+                ".." => {
+                    segments.pop()?;
+                },
+                _ => segments.push(segment)
+            }
+        }
+        Some(segments.join("/"))
+    }
+}
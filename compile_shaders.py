@@ -9,7 +9,10 @@ shaders = [
     "raytracing/raytrace.rgen",
     "raytracing/raytrace.rchit",
     "raytracing/raytrace.rmiss",
-    "raytracing/raytrace_shadow.rmiss"
+    "raytracing/raytrace_shadow.rmiss",
+    "gpu_driven/cull.comp",
+    "gpu_driven/draw.vert",
+    "gpu_driven/draw.frag"
 ]
 
 cwd = ""
@@ -0,0 +1,20 @@
+struct Instance
+{
+    mat4 model;
+    vec4 boundsMin;
+    vec4 boundsMax;
+    vec4 baseColor;
+    uint indexCount;
+    uint firstIndex;
+    int  vertexOffset;
+    uint padding;
+};
+
+struct DrawCommand
+{
+    uint indexCount;
+    uint instanceCount;
+    uint firstIndex;
+    int  vertexOffset;
+    uint firstInstance;
+};
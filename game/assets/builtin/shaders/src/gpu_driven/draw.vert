@@ -0,0 +1,32 @@
+#version 460
+#extension GL_GOOGLE_include_directive : enable
+
+#include "common.glsl"
+
+layout(location = 0) in vec3 inPosition;
+layout(location = 1) in vec3 inNormal;
+layout(location = 2) in vec4 inTangent;
+layout(location = 3) in vec2 inTexCoord0;
+layout(location = 4) in vec2 inTexCoord1;
+layout(location = 5) in vec4 inColor;
+
+layout(location = 0) out vec3 fragColor;
+
+layout(std430, set = 0, binding = 0) readonly buffer Instances { Instance instances[]; };
+
+layout(push_constant) uniform PushConstants {
+    mat4 viewProj;
+} pc;
+
+out gl_PerVertex {
+    vec4 gl_Position;
+};
+
+void main()
+{
+    // firstInstance of every indirect draw is the index of the culled instance
+    Instance instance = instances[gl_InstanceIndex];
+
+    gl_Position = pc.viewProj * instance.model * vec4(inPosition.xyz, 1.0);
+    fragColor = instance.baseColor.rgb * (inNormal * 0.5 + 0.5);
+}
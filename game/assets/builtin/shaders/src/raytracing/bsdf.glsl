@@ -0,0 +1,318 @@
+// Layered glTF BSDF: clearcoat on top of sheen on top of the metallic-roughness base,
+// where the dielectric part of the base is split into diffuse and transmission.
+// All directions are in world space, N faces the viewer and V points away from the surface.
+
+const float PI = 3.14159265359;
+
+struct BsdfMaterial
+{
+    vec3  baseColor;
+    float metallic;
+    float roughness;
+
+    float transmission;
+    float ior;
+    bool  frontFacing;
+
+    float specular;
+    vec3  specularColor;
+
+    float clearcoat;
+    float clearcoatRoughness;
+    vec3  clearcoatNormal;
+
+    vec3  sheenColor;
+    float sheenRoughness;
+};
+
+struct BsdfLobes
+{
+    float diffuse;
+    float specular;
+    float transmission;
+    float clearcoat;
+};
+
+float luminance(vec3 c)
+{
+    return dot(c, vec3(0.2126, 0.7152, 0.0722));
+}
+
+float max3(vec3 v)
+{
+    return max(v.x, max(v.y, v.z));
+}
+
+mat3 orthonormalBasis(vec3 n)
+{
+    // Duff et al. 2017, "Building an Orthonormal Basis, Revisited"
+    float s = n.z >= 0.0 ? 1.0 : -1.0;
+    float a = -1.0 / (s + n.z);
+    float b = n.x * n.y * a;
+    vec3 t = vec3(1.0 + s * n.x * n.x * a, s * b, -s * n.x);
+    vec3 bt = vec3(b, s + n.y * n.y * a, -n.y);
+    return mat3(t, bt, n);
+}
+
+vec3 fresnelSchlick(vec3 f0, vec3 f90, float VdotH)
+{
+    return f0 + (f90 - f0) * pow(clamp(1.0 - VdotH, 0.0, 1.0), 5.0);
+}
+
+float fresnelSchlick(float f0, float f90, float VdotH)
+{
+    return f0 + (f90 - f0) * pow(clamp(1.0 - VdotH, 0.0, 1.0), 5.0);
+}
+
+// eta = ior of the incident side / ior of the transmitted side
+float fresnelDielectric(float cosThetaI, float eta)
+{
+    float sinThetaTSq = eta * eta * (1.0 - cosThetaI * cosThetaI);
+    if(sinThetaTSq >= 1.0)
+    {
+        return 1.0; // Total internal reflection
+    }
+
+    float cosThetaT = sqrt(1.0 - sinThetaTSq);
+    float rs = (eta * cosThetaI - cosThetaT) / (eta * cosThetaI + cosThetaT);
+    float rp = (cosThetaI - eta * cosThetaT) / (cosThetaI + eta * cosThetaT);
+    return 0.5 * (rs * rs + rp * rp);
+}
+
+float D_GGX(float NdotH, float alpha)
+{
+    float a2 = alpha * alpha;
+    float d  = NdotH * NdotH * (a2 - 1.0) + 1.0;
+    return a2 / (PI * d * d);
+}
+
+float G1_GGX(float NdotX, float alpha)
+{
+    float a2 = alpha * alpha;
+    return 2.0 * NdotX / (NdotX + sqrt(a2 + (1.0 - a2) * NdotX * NdotX));
+}
+
+// Height correlated Smith visibility, includes the 1 / (4 NdotL NdotV) term
+float V_GGX(float NdotL, float NdotV, float alpha)
+{
+    float a2   = alpha * alpha;
+    float ggxV = NdotL * sqrt(NdotV * NdotV * (1.0 - a2) + a2);
+    float ggxL = NdotV * sqrt(NdotL * NdotL * (1.0 - a2) + a2);
+    float ggx  = ggxV + ggxL;
+    return ggx > 0.0 ? 0.5 / ggx : 0.0;
+}
+
+// Estevez and Kulla 2017, "Production Friendly Microfacet Sheen BRDF"
+float D_Charlie(float NdotH, float sheenRoughness)
+{
+    float alpha  = max(sheenRoughness * sheenRoughness, 0.000001);
+    float invR   = 1.0 / alpha;
+    float sin2h  = max(1.0 - NdotH * NdotH, 0.0);
+    return (2.0 + invR) * pow(sin2h, invR * 0.5) / (2.0 * PI);
+}
+
+float V_Neubelt(float NdotL, float NdotV)
+{
+    return clamp(1.0 / (4.0 * (NdotL + NdotV - NdotL * NdotV)), 0.0, 1.0);
+}
+
+// Heitz 2018, "Sampling the GGX Distribution of Visible Normals", returns a tangent space half vector
+vec3 sampleGGXVNDF(vec3 Ve, float alpha, vec2 u)
+{
+    vec3 Vh = normalize(vec3(alpha * Ve.x, alpha * Ve.y, Ve.z));
+
+    float lensq = Vh.x * Vh.x + Vh.y * Vh.y;
+    vec3 T1 = lensq > 0.0 ? vec3(-Vh.y, Vh.x, 0.0) * inversesqrt(lensq) : vec3(1.0, 0.0, 0.0);
+    vec3 T2 = cross(Vh, T1);
+
+    float r   = sqrt(u.x);
+    float phi = 2.0 * PI * u.y;
+    float t1  = r * cos(phi);
+    float t2  = r * sin(phi);
+    float s   = 0.5 * (1.0 + Vh.z);
+    t2 = (1.0 - s) * sqrt(1.0 - t1 * t1) + s * t2;
+
+    vec3 Nh = t1 * T1 + t2 * T2 + sqrt(max(0.0, 1.0 - t1 * t1 - t2 * t2)) * Vh;
+    return normalize(vec3(alpha * Nh.x, alpha * Nh.y, max(0.0, Nh.z)));
+}
+
+// Pdf of a reflected direction generated with sampleGGXVNDF
+float pdfGGXReflection(float NdotH, float NdotV, float alpha)
+{
+    return G1_GGX(NdotV, alpha) * D_GGX(NdotH, alpha) / (4.0 * NdotV);
+}
+
+vec3 sampleCosineHemisphere(vec2 u)
+{
+    float r   = sqrt(u.x);
+    float phi = 2.0 * PI * u.y;
+    return vec3(r * cos(phi), r * sin(phi), sqrt(max(0.0, 1.0 - u.x)));
+}
+
+float bsdfAlpha(float roughness)
+{
+    return max(roughness * roughness, 0.0001);
+}
+
+vec3 bsdfF0(BsdfMaterial m)
+{
+    float dielectric = (m.ior - 1.0) / (m.ior + 1.0);
+    vec3 dielectricF0 = min(vec3(dielectric * dielectric) * m.specularColor, vec3(1.0)) * m.specular;
+    return mix(dielectricF0, m.baseColor, m.metallic);
+}
+
+vec3 bsdfF90(BsdfMaterial m)
+{
+    return mix(vec3(m.specular), vec3(1.0), m.metallic);
+}
+
+// Energy that passes through the sheen layer, approximating the directional albedo of the Charlie lobe
+float sheenScaling(BsdfMaterial m)
+{
+    return 1.0 - max3(m.sheenColor) * 0.157;
+}
+
+float clearcoatFresnel(BsdfMaterial m, float NdotV)
+{
+    return m.clearcoat * fresnelSchlick(0.04, 1.0, NdotV);
+}
+
+BsdfLobes bsdfLobes(BsdfMaterial m, vec3 N, vec3 V)
+{
+    float NdotV  = max(dot(N, V), 0.0001);
+    float NcdotV = max(dot(m.clearcoatNormal, V), 0.0001);
+
+    float coat = clearcoatFresnel(m, NcdotV);
+    float base = 1.0 - coat;
+
+    BsdfLobes lobes;
+    lobes.clearcoat    = coat;
+    lobes.specular     = base * luminance(fresnelSchlick(bsdfF0(m), bsdfF90(m), NdotV));
+    lobes.diffuse      = base * ((1.0 - m.metallic) * (1.0 - m.transmission) * luminance(m.baseColor) + luminance(m.sheenColor));
+    lobes.transmission = base * (1.0 - m.metallic) * m.transmission;
+
+    float total = lobes.clearcoat + lobes.specular + lobes.diffuse + lobes.transmission;
+    if(total <= 0.0)
+    {
+        lobes.diffuse = 1.0;
+        return lobes;
+    }
+
+    lobes.clearcoat    /= total;
+    lobes.specular     /= total;
+    lobes.diffuse      /= total;
+    lobes.transmission /= total;
+    return lobes;
+}
+
+// Evaluates the reflection lobes times the cosine term, transmission is only reachable through bsdfSample
+vec3 bsdfEval(BsdfMaterial m, vec3 N, vec3 V, vec3 L, out float pdf)
+{
+    pdf = 0.0;
+
+    float NdotL = dot(N, L);
+    float NdotV = dot(N, V);
+    if(NdotL <= 0.0 || NdotV <= 0.0)
+    {
+        return vec3(0.0);
+    }
+
+    vec3  H     = normalize(V + L);
+    float NdotH = max(dot(N, H), 0.0);
+    float VdotH = max(dot(V, H), 0.0);
+    float alpha = bsdfAlpha(m.roughness);
+
+    // Base layer
+    vec3 F        = fresnelSchlick(bsdfF0(m), bsdfF90(m), VdotH);
+    vec3 specular = F * D_GGX(NdotH, alpha) * V_GGX(NdotL, NdotV, alpha);
+    vec3 diffuse  = (vec3(1.0) - F) * (1.0 - m.metallic) * (1.0 - m.transmission) * m.baseColor / PI;
+
+    // Sheen layer
+    vec3 sheen = m.sheenColor * D_Charlie(NdotH, m.sheenRoughness) * V_Neubelt(NdotL, NdotV);
+    vec3 f = (diffuse + specular) * sheenScaling(m) + sheen;
+
+    // Clearcoat layer
+    vec3  Nc          = m.clearcoatNormal;
+    float NcdotL      = max(dot(Nc, L), 0.0);
+    float NcdotV      = max(dot(Nc, V), 0.0001);
+    float NcdotH      = max(dot(Nc, H), 0.0);
+    float alphaCoat   = bsdfAlpha(m.clearcoatRoughness);
+    float coatFresnel = clearcoatFresnel(m, VdotH);
+    float coat        = coatFresnel * D_GGX(NcdotH, alphaCoat) * V_GGX(NcdotL, NcdotV, alphaCoat);
+    f = f * (1.0 - clearcoatFresnel(m, NcdotV)) + vec3(coat * NcdotL / NdotL);
+
+    BsdfLobes lobes = bsdfLobes(m, N, V);
+    pdf = lobes.diffuse * NdotL / PI
+        + lobes.specular * pdfGGXReflection(NdotH, NdotV, alpha);
+    if(NcdotL > 0.0)
+    {
+        pdf += lobes.clearcoat * pdfGGXReflection(NcdotH, NcdotV, alphaCoat);
+    }
+
+    return f * NdotL;
+}
+
+// Samples an outgoing direction L, returns the path weight f * cos / pdf
+vec3 bsdfSample(BsdfMaterial m, vec3 N, vec3 V, vec3 u, out vec3 L)
+{
+    BsdfLobes lobes = bsdfLobes(m, N, V);
+
+    float lobe = u.z;
+    if(lobe < lobes.transmission)
+    {
+        // Rough dielectric interface, reflects or refracts depending on the microfacet fresnel
+        mat3  basis = orthonormalBasis(N);
+        float alpha = bsdfAlpha(m.roughness);
+        vec3  H     = basis * sampleGGXVNDF(transpose(basis) * V, alpha, u.xy);
+        float VdotH = max(dot(V, H), 0.0);
+        float eta   = m.frontFacing ? 1.0 / m.ior : m.ior;
+
+        float F = fresnelDielectric(VdotH, eta);
+        // Reuse the lobe selection sample to choose between reflection and refraction
+        float choice = lobe / max(lobes.transmission, 0.0001);
+
+        vec3 tint;
+        if(choice < F)
+        {
+            L    = reflect(-V, H);
+            tint = vec3(1.0);
+        }
+        else
+        {
+            L    = refract(-V, H, eta);
+            tint = m.baseColor;
+        }
+
+        float NdotL = abs(dot(N, L));
+        if(NdotL <= 0.0 || dot(L, L) <= 0.0)
+        {
+            return vec3(0.0);
+        }
+
+        float coat   = clearcoatFresnel(m, max(dot(m.clearcoatNormal, V), 0.0001));
+        float weight = (1.0 - coat) * (1.0 - m.metallic) * m.transmission * sheenScaling(m);
+        return tint * G1_GGX(NdotL, alpha) * weight / lobes.transmission;
+    }
+    lobe -= lobes.transmission;
+
+    if(lobe < lobes.diffuse)
+    {
+        L = orthonormalBasis(N) * sampleCosineHemisphere(u.xy);
+    }
+    else if(lobe < lobes.diffuse + lobes.specular)
+    {
+        mat3 basis = orthonormalBasis(N);
+        vec3 H     = basis * sampleGGXVNDF(transpose(basis) * V, bsdfAlpha(m.roughness), u.xy);
+        L = reflect(-V, H);
+    }
+    else
+    {
+        mat3 basis = orthonormalBasis(m.clearcoatNormal);
+        vec3 H     = basis * sampleGGXVNDF(transpose(basis) * V, bsdfAlpha(m.clearcoatRoughness), u.xy);
+        L = reflect(-V, H);
+    }
+
+    float pdf;
+    vec3 f = bsdfEval(m, N, V, L, pdf);
+    return pdf > 0.0 ? f / pdf : vec3(0.0);
+}
@@ -1,4 +1,46 @@
 struct Payload
 {
-    vec3 hitValue;
-};
\ No newline at end of file
+    vec3 hitValue;  // Radiance emitted or reflected towards the ray origin
+    vec3 weight;    // Path weight of the sampled continuation ray
+    vec3 rayOrigin;
+    vec3 rayDir;
+    uint seed;
+    bool done;
+};
+
+uint pcgHash(uint v)
+{
+    uint state = v * 747796405u + 2891336453u;
+    uint word  = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+float rand(inout uint seed)
+{
+    seed = pcgHash(seed);
+    return float(seed) / 4294967295.0;
+}
+
+// Uniform point on the unit disk, concentric mapping keeps strata intact
+vec2 sampleDisk(inout uint seed)
+{
+    vec2 u = vec2(rand(seed), rand(seed)) * 2.0 - 1.0;
+    if(u.x == 0.0 && u.y == 0.0)
+    {
+        return vec2(0.0);
+    }
+
+    const float PI_4 = 0.78539816339;
+    float r, theta;
+    if(abs(u.x) > abs(u.y))
+    {
+        r     = u.x;
+        theta = PI_4 * (u.y / u.x);
+    }
+    else
+    {
+        r     = u.y;
+        theta = 2.0 * PI_4 - PI_4 * (u.x / u.y);
+    }
+    return r * vec2(cos(theta), sin(theta));
+}
\ No newline at end of file
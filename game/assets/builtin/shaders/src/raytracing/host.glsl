@@ -1,18 +1,27 @@
 struct GlobalUniforms
 {
-    mat4 viewProj;
-    mat4 viewInverse;
-    mat4 projInverse;
+    mat4  viewProj;
+    mat4  viewInverse;
+    mat4  projInverse;
+    float apertureRadius; // 0 disables depth of field
+    float focusDistance;  // Along the view direction
+    float exposure;
+    float _padding;
 };
 
+const int VERTEX_LAYOUT_FULL    = 0;
+const int VERTEX_LAYOUT_COMPACT = 1;
+
 struct ObjDesc
 {
     int textureOffset;
+    int vertexLayout; // VERTEX_LAYOUT_FULL or VERTEX_LAYOUT_COMPACT
 
     uint64_t  vertexAddress;
-    uint64_t  indexAddress;
+    uint64_t  indexAddress; // Of the level of detail in the tlas
     uint64_t  materialAddress;
-    uint64_t  materialIndexAddress;
+    int       materialIndex; // Every triangle of a mesh shares its material
+    int       _padding;
 };
 
 struct Vertex
@@ -25,9 +34,56 @@ struct Vertex
     vec4 color;
 };
 
+// Mirrors CompactVertex, the attributes are packed as snorm8x4, half2x16 and unorm8x4
+struct CompactVertex
+{
+    vec3 position;
+    uint normal;
+    uint tangent;
+    uint texCoord0;
+    uint texCoord1;
+    uint color;
+};
+
+struct TextureRef
+{
+    int   index; // -1 when the material has no texture in this slot
+    uint  texCoord;
+    float rotation;
+    vec2  offset;
+    vec2  scale;
+};
+
 struct Material
 {
-    vec3 diffuse;
+    vec4  baseColorFactor;
+    vec3  emissiveFactor;
+    float emissiveStrength;
+    float metallicFactor;
+    float roughnessFactor;
+    float normalScale;
+    float occlusionStrength;
+    float transmissionFactor;
+    float ior;
+    float specularFactor;
+    float clearcoatFactor;
+    vec3  specularColorFactor;
+    float clearcoatRoughnessFactor;
+    vec3  sheenColorFactor;
+    float sheenRoughnessFactor;
+    float clearcoatNormalScale;
 
-    int textureId;
+    TextureRef baseColorTexture;
+    TextureRef normalTexture;
+    TextureRef metallicRoughnessTexture;
+    TextureRef occlusionTexture;
+    TextureRef emissiveTexture;
+    TextureRef transmissionTexture;
+    TextureRef specularTexture;
+    TextureRef specularColorTexture;
+    TextureRef clearcoatTexture;
+    TextureRef clearcoatRoughnessTexture;
+    TextureRef clearcoatNormalTexture;
+    TextureRef sheenColorTexture;
+    TextureRef sheenRoughnessTexture;
 };
\ No newline at end of file
@@ -14,6 +14,7 @@ layout(location = 1) out vec2 fragTexCoord;
 
 out gl_PerVertex {
     vec4 gl_Position;
+    float gl_PointSize;
 };
 
 // layout(set = 0, binding = 0) uniform UniformBufferObject {
@@ -34,6 +35,7 @@ layout(push_constant) uniform PushConstants {
 
 void main() {
     gl_Position = pc.mvp * vec4(inPosition.xyz, 1.0);
+    gl_PointSize = 1.0; // Required when drawing point meshes
     fragColor = inNormal * 0.5 + 0.5;
     fragTexCoord = inTexCoord0;
 }
\ No newline at end of file
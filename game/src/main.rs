@@ -4,20 +4,41 @@ extern crate chronicle;
 
 use chronicle::{*, timer::Timer};
 use resources::{Resource, Model};
-use input::{VirtualKeyCode, MouseButton};
+use input::{VirtualKeyCode, MouseButton, ActionMap, Binding};
+use graphics::CameraController;
+
+/// Written with the default bindings when it is missing
+const INPUT_CONFIG: &str = "input.cfg";
 
 fn main() {
     std::env::set_var("RUST_BACKTRACE", "1");
 
+    // cargo run -- --cook <model>... converts the given models to .chrmodel files and exits
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(|arg| arg.as_str()) == Some("--cook") {
+        resources::cook(&args[1..]);
+        return;
+    }
+
     let core_loop = CoreLoop::new();
     chronicle::init("Example", Example::new(), &core_loop);
     core_loop.run();
 }
 
+fn default_action_map() -> ActionMap {
+    let mut action_map = ActionMap::new();
+    action_map.bind("toggle_cursor", Binding::key(VirtualKeyCode::Tab));
+    action_map.bind("pick", Binding::mouse_button(MouseButton::Left));
+    action_map
+}
+
 struct Example {
     helmet_model: Option<Resource<Model>>,
     helmet_render_models: Vec<RcCell<graphics::DynamicRenderModelProperties>>,
     render_camera: Option<RcCell<graphics::RenderCameraProperties>>,
+    camera_controller: Option<graphics::FlyController>,
+    picked: Option<graphics::RaycastHit>,
+    render_stats: graphics::RenderStats,
 
     fps_histogram: VecDeque<f32>,
     ms_histogram: VecDeque<f32>,
@@ -30,6 +51,9 @@ impl Game for Example {
             helmet_model: None,
             helmet_render_models: Vec::new(),
             render_camera: None,
+            camera_controller: None,
+            picked: None,
+            render_stats: graphics::RenderStats::default(),
             fps_histogram: VecDeque::new(),
             ms_histogram: VecDeque::new(),
             fps_histogram_timer: Timer::new()
@@ -39,8 +63,23 @@ impl Game for Example {
     fn start(&mut self) {
         //app().input().set_cursor_mode(input::CursorMode::LOCKED);
 
+        // A config with mistakes is kept so the edits are not lost, the defaults are only used until it is fixed
+        let action_map = match std::path::Path::new(INPUT_CONFIG).exists() {
+            true => ActionMap::load(INPUT_CONFIG).unwrap_or_else(|error| {
+                eprintln!("{}, using the default bindings", error);
+                default_action_map()
+            }),
+            false => {
+                let action_map = default_action_map();
+                action_map.save(INPUT_CONFIG);
+                action_map
+            }
+        };
+        app().input().set_action_map(action_map);
+
+        app().resources().mesh_optimizations = resources::MeshOptimizations::all_flags();
         self.helmet_model = Some(app().resources()
-            .get_model(String::from("assets/models/DamagedHelmet/glTF/DamagedHelmet.gltf"))
+            .get_model(String::from("game://models/DamagedHelmet/glTF/DamagedHelmet.gltf"))
         );
 
         for x in 0..10 {
@@ -60,6 +99,13 @@ impl Game for Example {
         );
         self.render_camera.as_ref().unwrap().as_mut()
             .main = true;
+
+        {
+            let mut render_camera = self.render_camera.as_ref().unwrap().as_mut();
+            render_camera.camera.set_translation(&Vector3::new(9.0, 9.0, 10.0));
+            render_camera.camera.look_at(&Vector3::new(9.0, 9.0, -15.0), &up());
+        }
+        self.camera_controller = Some(graphics::FlyController::new(self.render_camera.as_ref().unwrap().clone()));
     }
 
     fn update(&mut self, delta_time: f32) {
@@ -74,28 +120,29 @@ impl Game for Example {
                 ));
         }
 
-        const SPEED: f32 = 10.0;
-        let mut translation = Vector3::new(0.0, 0.0, 0.0);
-        if app().input().key(VirtualKeyCode::W) {
-            translation += forward() * SPEED * delta_time;
-        }
-        if app().input().key(VirtualKeyCode::S) {
-            translation += -forward() * SPEED * delta_time;
-        }
-        if app().input().key(VirtualKeyCode::D) {
-            translation += right() * SPEED * delta_time;
-        }
-        if app().input().key(VirtualKeyCode::A) {
-            translation += -right() * SPEED * delta_time;
-        }
-        if app().input().key(VirtualKeyCode::Q) {
-            translation += up() * SPEED * delta_time;
+        // Tab locks the cursor to look around freely, otherwise the right mouse button has to be held
+        if app().input().action_down("toggle_cursor") {
+            let camera_controller = self.camera_controller.as_mut().unwrap();
+            match app().input().get_cursor_mode() {
+                input::CursorMode::FREE => {
+                    app().input().set_cursor_mode(input::CursorMode::LOCKED);
+                    camera_controller.look_button = None;
+                },
+                input::CursorMode::LOCKED => {
+                    app().input().set_cursor_mode(input::CursorMode::FREE);
+                    camera_controller.look_button = Some(MouseButton::Right);
+                }
+            }
         }
-        if app().input().key(VirtualKeyCode::E) {
-            translation += -up() * SPEED * delta_time;
+        self.camera_controller.as_mut().unwrap()
+            .update(app().input(), delta_time);
+
+        if app().input().action_down("pick") {
+            let mouse_pos = app().input().mouse_pos();
+            self.picked = app().graphics().pick(Vector2::new(mouse_pos.x as f32, mouse_pos.y as f32));
         }
-        self.render_camera.as_ref().unwrap().as_mut()
-            .camera.translate(&translation);
+
+        self.render_stats = app().graphics().get_stats();
     }
 
     fn gui(&mut self, delta_time: f32, gui: &mut graphics::ImGuiUI) {
@@ -130,6 +177,28 @@ impl Game for Example {
                 .scale_min(1.0)
                 .scale_max(50.0)
                 .build();
+
+            gui.text(format!("Triangles {}", self.render_stats.triangle_count));
+            gui.text(format!("Blas count {}", self.render_stats.blas_count));
+            gui.text(format!("Blas memory {:.2} MiB", self.render_stats.blas_memory as f32 / (1024.0 * 1024.0)));
+            gui.text(format!("Blas compaction saved {:.2} MiB", self.render_stats.blas_memory_saved as f32 / (1024.0 * 1024.0)));
+            gui.text(format!("Resident meshes {}", self.render_stats.resident_mesh_count));
+            gui.text(format!("Resident textures {}", self.render_stats.resident_texture_count));
+            gui.text(format!("Resident memory {:.2} MiB", self.render_stats.resident_memory as f32 / (1024.0 * 1024.0)));
+
+            match &self.picked {
+                Some(picked) => gui.text(format!(
+                    "Picked mesh {} triangle {} at {:.2} {:.2} {:.2}",
+                    picked.mesh_idx, picked.primitive, picked.position.x, picked.position.y, picked.position.z
+                )),
+                None => gui.text("Picked nothing")
+            }
+
+            let input = app().input();
+            for id in input.get_connected_gamepads() {
+                let gamepad = input.get_gamepad(id).unwrap();
+                gui.text(format!("Gamepad {}: {}", id, gamepad.get_name()));
+            }
         });
     }
 